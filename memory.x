@@ -3,6 +3,11 @@ MEMORY
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
   /* TODO Adjust these memory regions to match your device memory layout */
   /* These values correspond to the LM3S6965, one of the few devices QEMU can emulate */
-  FLASH : ORIGIN = 0x08000000, LENGTH = 512K
+  /* Sector 0 holds vector table only, code starts at sector 3, see _stext below */
+  /* Sectors 1 and 2 at 0x08004000 are kept for configuration, see src/config */
+  /* Sectors 6 and 7 at 0x08040000 are kept for measurement log, see src/datalog */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
   RAM : ORIGIN = 0x20000000, LENGTH = 128K
-}
\ No newline at end of file
+}
+
+_stext = 0x0800C000;
\ No newline at end of file
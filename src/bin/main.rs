@@ -3,14 +3,66 @@
 #![feature(type_alias_impl_trait)]
 
 use cortex_m_rt::entry;
-use embassy_time::{Duration, Timer};
+use embassy_futures::select::{select, Either};
 use {defmt_rtt as _, panic_probe as _}; // global logger
 
 use embassy_stm32_temp::{
-    bsp,
-    drivers::sensors::{humidity::HumiditySensor, temperature::TemperatureSensor},
+    bsp, calibration, clock, config, datalog, display,
+    drivers::{
+        at24cxx::{self, At24cxx},
+        sensors::{humidity::HumiditySensor, status::StatusSensor, temperature::TemperatureSensor},
+    },
+    history, hourly,
+    measurement::{Measurement, MeasurementChannel, SensorId, Timestamp},
+    modbus, mqtt,
+    schedule::{Period, PeriodSignal, Scheduler},
+    sdlog, shell, telemetry,
+    units::Temperature,
+    usb,
+    watchdog::{self, TaskId},
 };
 
+/// When measurements are taken
+const MEASUREMENT_PERIOD: Period = Period::EverySecond;
+
+/// Time between measurements kept in flash log and on microSD card
+const LOG_INTERVAL: embassy_time::Duration = embassy_time::Duration::from_secs(60);
+
+/// Time between writes of measurements queued for microSD card
+const SD_FLUSH_INTERVAL: embassy_time::Duration = embassy_time::Duration::from_secs(5 * 60);
+
+/// Time between readings published to MQTT broker
+const MQTT_PUBLISH_INTERVAL: embassy_time::Duration = embassy_time::Duration::from_secs(10);
+
+/// Controller of connected display
+const DISPLAY_KIND: display::backend::Kind = display::backend::Kind::Ssd1306;
+
+/// Measurements for display and other consumers
+static MEASUREMENTS: MeasurementChannel = MeasurementChannel::new();
+
+/// Wall-clock time shared with log timestamps
+static CLOCK: clock::Shared = clock::Shared::new();
+
+static DISPLAY: display::Shared = display::Shared::new();
+
+static WATCHDOG: watchdog::Shared = watchdog::Shared::new();
+
+static TELEMETRY: telemetry::TelemetryChannel = telemetry::TelemetryChannel::new();
+
+/// Period of measurements requested from shell
+static MEASUREMENT_PERIOD_REQUEST: PeriodSignal = PeriodSignal::new();
+
+static USB: usb::State = usb::State::new();
+
+// Logs are stamped with UTC time, or with uptime (shown as 1970-01-01) until clock is synced
+defmt::timestamp!(
+    "{=u64:iso8601ms}",
+    CLOCK
+        .unix_millis()
+        .map(|millis| millis as u64)
+        .unwrap_or_else(|| embassy_time::Instant::now().as_millis())
+);
+
 // mod display;
 // mod i2c;
 // mod temperature;
@@ -30,11 +82,97 @@ async fn lm75_temp_task(runner: embassy_stm32_temp::drivers::sensors::lm75::Runn
     runner.run().await;
 }
 
+#[embassy_executor::task]
+async fn lm75_alert_task(
+    sensor: embassy_stm32_temp::drivers::sensors::lm75::Lm75<'static>,
+    display: display::Display<'static>,
+    telemetry: telemetry::Telemetry<'static>,
+) {
+    use embassy_stm32_temp::drivers::sensors::lm75::OsEvent;
+
+    loop {
+        let event = sensor.wait_os_event().await;
+        defmt::warn!("lm75b: {}", event);
+        display.set_alarm(event == OsEvent::OverTemperature);
+        telemetry.send_alarm(event);
+    }
+}
+
+#[embassy_executor::task]
+async fn ds3231_task(runner: embassy_stm32_temp::drivers::ds3231::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn clock_task(runner: embassy_stm32_temp::clock::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn button_task(runner: bsp::button::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn history_task(runner: history::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn telemetry_task(runner: telemetry::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn shell_task(runner: shell::Runner<'static, bsp::ShellUart>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn usb_task(runner: usb::Runner<'static, bsp::UsbDriver>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn usb_shell_task(runner: shell::Runner<'static, usb::Port<'static, bsp::UsbDriver>>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn modbus_task(runner: modbus::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn datalog_task(runner: datalog::Runner<'static>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn hourly_task(runner: hourly::Runner<'static, At24cxx>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn sdlog_task(runner: sdlog::Runner<'static, bsp::SdCard, bsp::SdDetectPin>) {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn mqtt_task(runner: mqtt::Runner<'static, bsp::ModemUart>) {
+    runner.run().await;
+}
+
 #[embassy_executor::task]
 async fn dht22_temp_task(runner: embassy_stm32_temp::drivers::sensors::dht22::Runner<'static>) {
     runner.run().await;
 }
 
+#[embassy_executor::task]
+async fn watchdog_task(runner: watchdog::Runner<'static>) {
+    runner.run().await;
+}
+
 #[entry]
 fn entry() -> ! {
     // BSP prepares peripherals and runtime and runs main task in LOWEST priority
@@ -43,17 +181,111 @@ fn entry() -> ! {
 
 #[embassy_executor::task]
 async fn main(p: bsp::Peripherals, runtime: bsp::Runtime) {
+    let ds3231_shared = mk_static!(
+        embassy_stm32_temp::drivers::ds3231::Shared,
+        embassy_stm32_temp::drivers::ds3231::Shared::new()
+    );
+    let (rtc, rtc_runner) = embassy_stm32_temp::drivers::ds3231::new(p.i2c1(), ds3231_shared);
+
+    let (clock, clock_runner) = clock::new(rtc, &CLOCK);
+    runtime.lowest().must_spawn(clock_task(clock_runner));
+
+    let lm75_bus = p.i2c1();
+    let display_bus = p.i2c1();
+    let shell_bus = p.i2c1();
+    let usb_shell_bus = p.i2c1();
+    let eeprom_bus = p.i2c1();
+
+    // Pins and flash are moved out of peripherals after all buses are taken
+    watchdog::report_reset();
+    let (supervisor, watchdog_runner) = watchdog::new(p.watchdog, &WATCHDOG);
+    runtime.highest().must_spawn(watchdog_task(watchdog_runner));
+
+    let config_store = mk_static!(
+        config::SharedStore,
+        config::SharedStore::new(config::store::Store::new(p.config_flash, 0))
+    );
+    let settings = config::load(&mut *config_store.lock().await);
+    settings.apply_preferences();
+
+    let log = mk_static!(
+        datalog::SharedLog,
+        datalog::SharedLog::new(datalog::ring::Ring::new(p.log_flash))
+    );
+    let datalog_runner = datalog::new(&MEASUREMENTS, log, LOG_INTERVAL);
+    runtime.lowest().must_spawn(datalog_task(datalog_runner));
+
+    let eeprom = At24cxx::new(
+        eeprom_bus,
+        at24cxx::ZS042_ADDRESS,
+        at24cxx::Geometry::AT24C32,
+    );
+    let hourly_log = mk_static!(
+        hourly::SharedHourlyLog<At24cxx>,
+        hourly::SharedHourlyLog::new(hourly::HourlyLog::new(eeprom))
+    );
+    let hourly_runner = hourly::new(&MEASUREMENTS, hourly_log);
+    runtime.lowest().must_spawn(hourly_task(hourly_runner));
+
+    let sdlog_runner = sdlog::new(
+        &MEASUREMENTS,
+        p.sd_card,
+        p.sd_detect_pin,
+        LOG_INTERVAL,
+        SD_FLUSH_INTERVAL,
+    );
+    runtime.lowest().must_spawn(sdlog_task(sdlog_runner));
+
     let lm75b_shared = mk_static!(
         embassy_stm32_temp::drivers::sensors::lm75::Shared,
         embassy_stm32_temp::drivers::sensors::lm75::Shared::new()
     );
 
-    let (first_sensor, first_sensor_runner) =
-        embassy_stm32_temp::drivers::sensors::lm75::new(p.i2c1(), lm75b_shared);
+    let (first_sensor, first_sensor_runner) = embassy_stm32_temp::drivers::sensors::lm75::new(
+        lm75_bus,
+        settings.lm75_config(embassy_stm32_temp::drivers::sensors::lm75::Config::default()),
+        lm75b_shared,
+    );
+
+    let display_backend =
+        display::backend::Backend::new(DISPLAY_KIND, display_bus, p.display_spi, p.display_pins);
+    let button_shared = mk_static!(bsp::button::Shared, bsp::button::Shared::new());
+    let (button, button_runner) = bsp::button::new(p.user_button, button_shared);
+    runtime.lowest().must_spawn(button_task(button_runner));
+
+    let history_shared = mk_static!(history::Shared, history::Shared::new());
+    let (history, history_runner) = history::new(&MEASUREMENTS, history_shared);
+    runtime.lowest().must_spawn(history_task(history_runner));
 
+    let (telemetry, telemetry_runner) = telemetry::new(&MEASUREMENTS, &TELEMETRY);
     runtime
         .lowest()
-        .must_spawn(lm75_temp_task(first_sensor_runner));
+        .must_spawn(telemetry_task(telemetry_runner));
+
+    let display = display::spawn_display_tasks(
+        &MEASUREMENTS,
+        display_backend,
+        button,
+        history,
+        display::power::Config::default(),
+        &DISPLAY,
+        config_store,
+        supervisor.register(TaskId::Display),
+        &runtime.lowest(),
+    );
+
+    runtime
+        .lowest()
+        .must_spawn(ds3231_task(rtc_runner.with_int_pin(p.rtc_int_pin)));
+
+    runtime.lowest().must_spawn(lm75_temp_task(
+        first_sensor_runner
+            .with_os_pin(p.lm75_os_pin)
+            .with_watchdog(supervisor.register(TaskId::Lm75)),
+    ));
+    runtime
+        .lowest()
+        .must_spawn(lm75_alert_task(first_sensor, display, telemetry));
 
     let dht22_shared = mk_static!(
         embassy_stm32_temp::drivers::sensors::dht22::Shared,
@@ -61,23 +293,111 @@ async fn main(p: bsp::Peripherals, runtime: bsp::Runtime) {
     );
     let (second_sensor, second_sensor_runner) =
         embassy_stm32_temp::drivers::sensors::dht22::new(p.dht_pin, dht22_shared);
+    second_sensor
+        .set_measurement_interval(settings.interval(SensorId::Dht22))
+        .await;
+    runtime.medium().must_spawn(dht22_temp_task(
+        second_sensor_runner.with_watchdog(supervisor.register(TaskId::Dht22)),
+    ));
+
+    let shell_context = shell::Context {
+        lm75: first_sensor,
+        dht22: second_sensor,
+        i2c: shell_bus,
+        period: &MEASUREMENT_PERIOD_REQUEST,
+        telemetry,
+        config: config_store,
+        settings,
+        log,
+        hourly: hourly_log,
+    };
+    let shell_runner = shell::new(p.shell_uart, &MEASUREMENTS, shell_context);
+    runtime.lowest().must_spawn(shell_task(shell_runner));
+
+    // Same shell on USB serial device, for units deployed without ST-LINK
+    let (usb_runner, usb_port) = usb::new(p.usb, &USB);
+    runtime.medium().must_spawn(usb_task(usb_runner));
+    let usb_shell_context = shell::Context {
+        lm75: first_sensor,
+        dht22: second_sensor,
+        i2c: usb_shell_bus,
+        period: &MEASUREMENT_PERIOD_REQUEST,
+        telemetry,
+        config: config_store,
+        settings,
+        log,
+        hourly: hourly_log,
+    };
+    let usb_shell_runner = shell::new(usb_port, &MEASUREMENTS, usb_shell_context);
     runtime
-        .medium()
-        .must_spawn(dht22_temp_task(second_sensor_runner));
+        .lowest()
+        .must_spawn(usb_shell_task(usb_shell_runner));
 
-    let mut temps: [f32; 2] = [0.0; 2];
+    let modbus_context = modbus::Context {
+        lm75: first_sensor,
+        dht22: second_sensor,
+    };
+    let modbus_runner = modbus::new(
+        p.rs485,
+        settings.modbus_address,
+        &MEASUREMENTS,
+        modbus_context,
+    );
+    runtime.lowest().must_spawn(modbus_task(modbus_runner));
 
-    loop {
-        temps[0] = first_sensor.get_temperature().await;
-        temps[1] = second_sensor.get_temperature().await;
-        let avg_temp = (temps[0] + temps[1]) / 2.0;
+    // Network is given at build time, readings are not published without it
+    if let Some(ssid) = option_env!("WIFI_SSID") {
+        let mqtt_config = mqtt::Config {
+            ssid,
+            wifi_password: option_env!("WIFI_PASSWORD").unwrap_or(""),
+            host: option_env!("MQTT_HOST").unwrap_or("mqtt.local"),
+            port: option_env!("MQTT_PORT")
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(1883),
+            user: embassy_stm32_temp::drivers::esp_at::MqttUser {
+                client_id: option_env!("MQTT_CLIENT_ID").unwrap_or("thermometer"),
+                username: option_env!("MQTT_USERNAME").unwrap_or(""),
+                password: option_env!("MQTT_PASSWORD").unwrap_or(""),
+            },
+            topic_prefix: option_env!("MQTT_TOPIC_PREFIX").unwrap_or("thermometer"),
+            publish_interval: MQTT_PUBLISH_INTERVAL,
+        };
+        let mqtt_runner = mqtt::new(p.modem_uart, mqtt_config, &MEASUREMENTS);
+        runtime.lowest().must_spawn(mqtt_task(mqtt_runner));
+    }
+
+    let mut scheduler = Scheduler::new(rtc, MEASUREMENT_PERIOD);
+    let publisher = defmt::unwrap!(MEASUREMENTS.publisher());
+    let processing_watchdog = supervisor.register(TaskId::Processing);
 
-        defmt::info!("temp: 0={} 1={} avg={}", temps[0], temps[1], avg_temp);
+    loop {
+        processing_watchdog.check_in(scheduler.period().duration());
+        let temperatures = [
+            calibration::apply(SensorId::Lm75, first_sensor.get_temperature().await),
+            calibration::apply(SensorId::Dht22, second_sensor.get_temperature().await),
+        ];
+        let statuses = [
+            first_sensor.get_status().await,
+            second_sensor.get_status().await,
+        ];
+        let measurement = Measurement {
+            timestamp: clock.now().map(Timestamp),
+            temperatures,
+            statuses,
+            temperature: Measurement::fused_temperature(&temperatures, &statuses),
+            humidity: second_sensor.get_humidity().await,
+        };
 
-        let humidity = second_sensor.get_humidity().await;
-        defmt::info!("humidity: {}", humidity);
+        defmt::info!("{}", measurement);
+        publisher.publish_immediate(measurement);
+        defmt::debug!("rtc: temp={}", Temperature(rtc.get_temperature().await));
 
-        Timer::after(Duration::from_secs(1)).await;
+        // New period starts with measurement taken right away
+        if let Either::Second(period) =
+            select(scheduler.next(), MEASUREMENT_PERIOD_REQUEST.wait()).await
+        {
+            scheduler.set_period(period);
+        }
     }
 
     drop(p);
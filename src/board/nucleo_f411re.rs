@@ -1,28 +1,132 @@
+pub mod button;
 mod executor;
+mod flash;
 mod i2c;
+mod spi;
+mod usart;
+mod usb;
 mod work_indicator;
 
 use embassy_executor::{InterruptExecutor, SendSpawner, SpawnToken, Spawner};
-use embassy_stm32::gpio::{Flex, Pull};
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::gpio::{Flex, Input, Pull};
 use embassy_stm32::interrupt;
 use embassy_stm32::{
     gpio::{Level, Output, Speed},
     interrupt::{InterruptExt as _, Priority},
+    peripherals::IWDG,
+    rcc,
+    time::Hertz,
+    wdg::IndependentWatchdog,
     Config,
 };
 use static_cell::StaticCell;
 
 use executor::Executor;
 
+/// Error of transaction on shared i2c bus
+pub use i2c::I2cError;
 /// Handle used to shared i2c bus
 pub use i2c::I2cShared;
+/// Error of transaction on shared spi bus
+pub use spi::SpiError;
+/// Handle used to shared spi bus, chip select is included
+pub use spi::SpiShared;
+
+/// Serial port of ST-LINK virtual COM, USART2 on PA2/PA3
+pub type ShellUart = usart::UartHandle;
+
+/// Serial port of AT-command Wi-Fi modem, USART6 on PC6/PC7
+pub type ModemUart = usart::UartHandle;
+
+/// RS-485 transceiver on USART1 with driver enable pin
+pub use usart::Rs485;
+/// Error of serial port
+pub use usart::UartError;
+
+/// Flash sectors 1 and 2 kept for configuration
+pub type ConfigFlash = flash::ConfigPartition;
+
+/// Flash sectors 6 and 7 kept for measurement log
+pub type LogFlash = flash::LogPartition;
+
+/// Independent watchdog clocked from LSI, board resets unless it is fed within [WATCHDOG_TIMEOUT]
+pub type Watchdog = IndependentWatchdog<'static, IWDG>;
+
+/// Longest time between feeds of [Watchdog] once it is unleashed
+///
+/// Flash is not read while a sector is erased, which stalls supervisor up to 4s for 128K.
+pub const WATCHDOG_TIMEOUT: embassy_time::Duration = embassy_time::Duration::from_secs(8);
+
+/// Whether [Watchdog] reset the board, reset flags are cleared so it is told once
+pub fn take_watchdog_reset() -> bool {
+    let csr = embassy_stm32::pac::RCC.csr();
+    let reset = csr.read().wdgrstf();
+    csr.modify(|w| w.set_rmvf(true));
+    reset
+}
+
+/// Driver of USB OTG FS device on PA11/PA12, see [usb](crate::usb)
+pub type UsbDriver = usb::UsbDriver;
 
 pub type DhtSingleWirePin = Flex<'static>;
 
+/// Input connected to open-drain OS output of LM75
+pub type Lm75OsPin = ExtiInput<'static>;
+
+/// Input connected to open-drain INT/SQW output of DS3231
+pub type RtcIntPin = ExtiInput<'static>;
+
+/// Data/command select pin of SPI display
+pub type DisplayDcPin = Output<'static>;
+
+/// Reset pin of SPI display, active low
+pub type DisplayResetPin = Output<'static>;
+
+/// microSD card on SPI2, chip select is driven by card driver
+pub type SdCard = crate::drivers::sdcard::SdCard<spi::SpiHandle, Output<'static>>;
+
+/// Card detect switch of microSD socket, low while card is inserted
+pub type SdDetectPin = Input<'static>;
+
+/// Control pins of SPI display
+pub struct DisplayPins {
+    /// D8 on Arduino header
+    pub dc: DisplayDcPin,
+    /// D6 on Arduino header
+    pub reset: DisplayResetPin,
+}
+
 #[non_exhaustive]
 pub struct Peripherals {
     i2c1: &'static i2c::I2cProtected,
     pub dht_pin: DhtSingleWirePin,
+    /// D2 on Arduino header
+    pub lm75_os_pin: Lm75OsPin,
+    /// D5 on Arduino header
+    pub rtc_int_pin: RtcIntPin,
+    /// Blue user button B1
+    pub user_button: button::ButtonPin,
+    /// SPI display with chip select on D4, SCK is on D3 since D13 drives user LED
+    pub display_spi: SpiShared,
+    pub display_pins: DisplayPins,
+    /// microSD card, SCK on PB13, MISO on PB14 and MOSI on PB15 of morpho header, chip select
+    /// on A3
+    pub sd_card: SdCard,
+    /// A5 on Arduino header
+    pub sd_detect_pin: SdDetectPin,
+    /// Serial port connected to ST-LINK virtual COM port
+    pub shell_uart: ShellUart,
+    /// USB device port, D- on PA11 and D+ on PA12 of morpho header
+    pub usb: UsbDriver,
+    /// RS-485 transceiver, TX on D10, RX on PB7 of morpho header and driver enable on D7
+    pub rs485: Rs485,
+    /// Wi-Fi modem, TX on PC6 and RX on PC7 of morpho header
+    pub modem_uart: ModemUart,
+    pub config_flash: ConfigFlash,
+    pub log_flash: LogFlash,
+    /// Not started until unleashed
+    pub watchdog: Watchdog,
 }
 
 impl Peripherals {
@@ -91,8 +195,31 @@ macro_rules! mk_static {
     }};
 }
 
+/// Clocks from 8MHz ST-LINK MCO: 96MHz system clock and 48MHz needed by USB
+fn config() -> Config {
+    let mut config = Config::default();
+    config.rcc.hse = Some(rcc::Hse {
+        freq: Hertz(8_000_000),
+        mode: rcc::HseMode::Bypass,
+    });
+    config.rcc.pll_src = rcc::PllSource::HSE;
+    config.rcc.pll = Some(rcc::Pll {
+        prediv: rcc::PllPreDiv::DIV4,
+        mul: rcc::PllMul::MUL192,
+        divp: Some(rcc::PllPDiv::DIV4),
+        divq: Some(rcc::PllQDiv::DIV8),
+        divr: None,
+    });
+    config.rcc.sys = rcc::Sysclk::PLL1_P;
+    config.rcc.ahb_pre = rcc::AHBPrescaler::DIV1;
+    // APB1 is limited to 50MHz
+    config.rcc.apb1_pre = rcc::APBPrescaler::DIV2;
+    config.rcc.apb2_pre = rcc::APBPrescaler::DIV1;
+    config
+}
+
 fn init() -> Peripherals {
-    let p = embassy_stm32::init(Config::default());
+    let p = embassy_stm32::init(config());
     let indicator_led = Output::new(p.PA5, Level::Low, Speed::VeryHigh);
     let static_activity_led = mk_static!(Output<'static>, indicator_led);
     // We are only one calling this, `p` can passed here once only so we only one setting pin
@@ -103,12 +230,56 @@ fn init() -> Peripherals {
     let sda = p.PB9;
     let i2c1_ref = i2c::init_i2c1(i2c1, scl, sda);
 
+    let spi1 = p.SPI1;
+    let sck = p.PB3;
+    let mosi = p.PA7;
+    let miso = p.PA6;
+    let spi1_ref = spi::init_spi1(spi1, sck, mosi, miso);
+    let display_cs = Output::new(p.PB5, Level::High, Speed::VeryHigh);
+    let display_spi = SpiShared::new(spi1_ref, display_cs);
+    let display_pins = DisplayPins {
+        dc: Output::new(p.PA9, Level::Low, Speed::VeryHigh),
+        reset: Output::new(p.PB10, Level::High, Speed::Low),
+    };
+
+    let spi2 = spi::init_spi2(p.SPI2, p.PB13, p.PB15, p.PB14);
+    let sd_card_cs = Output::new(p.PB0, Level::High, Speed::VeryHigh);
+    let sd_card = SdCard::new(spi2, sd_card_cs);
+    let sd_detect_pin = Input::new(p.PC0, Pull::Up);
+
+    let shell_uart = usart::init_usart2(p.USART2, p.PA3, p.PA2);
+    let usb = usb::init_usb(p.USB_OTG_FS, p.PA12, p.PA11);
+    let rs485_de = Output::new(p.PA8, Level::Low, Speed::VeryHigh);
+    let rs485 = usart::init_usart1_rs485(p.USART1, p.PB7, p.PB6, rs485_de);
+    let modem_uart = usart::init_usart6(p.USART6, p.PC7, p.PC6);
+    let (log_flash, config_flash) = flash::init_flash(p.FLASH);
+    let watchdog = Watchdog::new(p.IWDG, WATCHDOG_TIMEOUT.as_micros() as u32);
+
     let mut dht_pin = Flex::new(p.PA15);
     dht_pin.set_as_input_output_pull(Speed::VeryHigh, Pull::Up);
 
+    let lm75_os_pin = ExtiInput::new(p.PA10, p.EXTI10, Pull::Up);
+    let rtc_int_pin = ExtiInput::new(p.PB4, p.EXTI4, Pull::Up);
+    // Board has external pull-up on button
+    let user_button = ExtiInput::new(p.PC13, p.EXTI13, Pull::None);
+
     Peripherals {
         i2c1: i2c1_ref,
         dht_pin,
+        lm75_os_pin,
+        rtc_int_pin,
+        user_button,
+        display_spi,
+        display_pins,
+        sd_card,
+        sd_detect_pin,
+        shell_uart,
+        usb,
+        rs485,
+        modem_uart,
+        config_flash,
+        log_flash,
+        watchdog,
     }
 }
 
@@ -124,7 +295,7 @@ where
 
     #[interrupt]
     #[allow(non_snake_case)]
-    unsafe fn USART6() {
+    unsafe fn SPI3() {
         work_indicator::set_working_enabled(true);
         EXECUTOR_HIGH.on_interrupt()
     }
@@ -136,8 +307,8 @@ where
         EXECUTOR_MEDIUM.on_interrupt()
     }
 
-    interrupt::USART6.set_priority(Priority::P6);
-    let spawner_high = EXECUTOR_HIGH.start(interrupt::USART6);
+    interrupt::SPI3.set_priority(Priority::P6);
+    let spawner_high = EXECUTOR_HIGH.start(interrupt::SPI3);
     interrupt::I2C3_EV.set_priority(Priority::P7);
     let spawner_med = EXECUTOR_MEDIUM.start(interrupt::I2C3_EV);
 
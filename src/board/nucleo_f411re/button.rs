@@ -0,0 +1,95 @@
+//!
+//! Debounced user button with short and long press detection
+//!
+
+use embassy_futures::select::{select, Either};
+use embassy_stm32::exti::ExtiInput;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::{Duration, Timer};
+
+/// Time for contacts to settle
+pub const DEBOUNCE_TIME: Duration = Duration::from_millis(20);
+
+/// Press held at least this long is reported as long one
+pub const LONG_PRESS_TIME: Duration = Duration::from_millis(800);
+
+/// How many events can wait to be handled
+const EVENT_QUEUE_SIZE: usize = 4;
+
+/// Button input, low when pressed
+pub type ButtonPin = ExtiInput<'static>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ButtonEvent {
+    ShortPress,
+    /// Reported as soon as [LONG_PRESS_TIME] passes, without waiting for release
+    LongPress,
+}
+
+pub struct Shared {
+    events: Channel<CriticalSectionRawMutex, ButtonEvent, EVENT_QUEUE_SIZE>,
+}
+
+impl Shared {
+    pub const fn new() -> Self {
+        Self {
+            events: Channel::new(),
+        }
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Button<'a> {
+    shared: &'a Shared,
+}
+
+impl Button<'_> {
+    /// Waits for next press of button
+    pub async fn wait_event(&self) -> ButtonEvent {
+        self.shared.events.receive().await
+    }
+}
+
+pub struct Runner<'a> {
+    pin: ButtonPin,
+    shared: &'a Shared,
+}
+
+impl Runner<'_> {
+    pub async fn run(mut self) -> ! {
+        loop {
+            self.pin.wait_for_falling_edge().await;
+            Timer::after(DEBOUNCE_TIME).await;
+            if self.pin.is_high() {
+                continue; // Bounce or noise
+            }
+
+            let release = select(
+                self.pin.wait_for_high(),
+                Timer::after(LONG_PRESS_TIME - DEBOUNCE_TIME),
+            );
+            let event = match release.await {
+                Either::First(_) => ButtonEvent::ShortPress,
+                Either::Second(_) => ButtonEvent::LongPress,
+            };
+
+            defmt::trace!("button: {}", event);
+            // Nobody is waiting if queue is full, so event can be dropped
+            self.shared.events.try_send(event).ok();
+
+            self.pin.wait_for_high().await;
+            Timer::after(DEBOUNCE_TIME).await;
+        }
+    }
+}
+
+pub fn new<'a>(pin: ButtonPin, data: &'a Shared) -> (Button<'a>, Runner<'a>) {
+    let runner = Runner { pin, shared: data };
+    (Button { shared: data }, runner)
+}
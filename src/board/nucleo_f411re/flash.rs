@@ -0,0 +1,38 @@
+use core::cell::RefCell;
+
+use embassy_embedded_hal::flash::partition::BlockingPartition;
+use embassy_stm32::{
+    flash::{Bank1Region1, Bank1Region3, Blocking, Flash},
+    peripherals::FLASH,
+    Peri,
+};
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, CriticalSectionMutex};
+use static_cell::StaticCell;
+
+/// Sectors 0 to 3, each 16K erased as a whole
+pub type SmallSectors = Bank1Region1<'static, Blocking>;
+/// Sectors 5 to 7, each 128K erased as a whole
+pub type LargeSectors = Bank1Region3<'static, Blocking>;
+pub type ConfigPartition = BlockingPartition<'static, CriticalSectionRawMutex, SmallSectors>;
+pub type LogPartition = BlockingPartition<'static, CriticalSectionRawMutex, LargeSectors>;
+
+const SMALL_SECTOR_SIZE: u32 = 16 * 1024;
+const LARGE_SECTOR_SIZE: u32 = 128 * 1024;
+
+static SMALL_SECTORS: StaticCell<CriticalSectionMutex<RefCell<SmallSectors>>> = StaticCell::new();
+static LARGE_SECTORS: StaticCell<CriticalSectionMutex<RefCell<LargeSectors>>> = StaticCell::new();
+
+/// Takes log in sectors 6 and 7 and configuration in sectors 1 and 2, which firmware skips
+pub fn init_flash(flash: Peri<'static, FLASH>) -> (LogPartition, ConfigPartition) {
+    let regions = Flash::new_blocking(flash).into_blocking_regions();
+    let small = SMALL_SECTORS.init(CriticalSectionMutex::new(RefCell::new(
+        regions.bank1_region1,
+    )));
+    let large = LARGE_SECTORS.init(CriticalSectionMutex::new(RefCell::new(
+        regions.bank1_region3,
+    )));
+
+    let log = LogPartition::new(large, LARGE_SECTOR_SIZE, 2 * LARGE_SECTOR_SIZE);
+    let config = ConfigPartition::new(small, SMALL_SECTOR_SIZE, 2 * SMALL_SECTOR_SIZE);
+    (log, config)
+}
@@ -1,8 +1,8 @@
 use core::cell::RefCell;
 
-use embassy_embedded_hal::shared_bus::blocking::i2c::I2cDevice;
+use embassy_embedded_hal::shared_bus::{blocking::i2c::I2cDevice, I2cDeviceError};
 use embassy_stm32::{
-    i2c::{I2c, Master, SclPin, SdaPin},
+    i2c::{self, I2c, Master, SclPin, SdaPin},
     mode::Blocking,
     peripherals::I2C1,
     Peri,
@@ -13,6 +13,7 @@ use static_cell::StaticCell;
 pub type I2cHandle = I2c<'static, Blocking, Master>;
 pub type I2cProtected = CriticalSectionMutex<RefCell<I2cHandle>>;
 pub type I2cShared = I2cDevice<'static, CriticalSectionRawMutex, I2cHandle>;
+pub type I2cError = I2cDeviceError<i2c::Error>;
 
 static I2C1_HANDLE: StaticCell<I2cProtected> = StaticCell::new();
 
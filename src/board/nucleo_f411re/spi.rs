@@ -0,0 +1,72 @@
+use core::cell::RefCell;
+
+use embassy_embedded_hal::shared_bus::{blocking::spi::SpiDevice, SpiDeviceError};
+use embassy_stm32::{
+    gpio::Output,
+    mode::Blocking,
+    peripherals::{SPI1, SPI2},
+    spi::{self, MisoPin, MosiPin, SckPin, Spi},
+    time::Hertz,
+    Peri,
+};
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, CriticalSectionMutex};
+use static_cell::StaticCell;
+
+use crate::drivers::sdcard::CardBus;
+
+/// Clock of the bus, fast enough to refresh colour display several times per second
+const SPI1_FREQUENCY: Hertz = Hertz(8_000_000);
+
+/// Clock of microSD card bus, cards take up to 25MHz once initialized
+const SPI2_FREQUENCY: Hertz = Hertz(12_000_000);
+
+/// Clock of microSD card bus until card is initialized
+const SPI2_INIT_FREQUENCY: Hertz = Hertz(400_000);
+
+pub type SpiHandle = Spi<'static, Blocking>;
+pub type SpiProtected = CriticalSectionMutex<RefCell<SpiHandle>>;
+pub type SpiShared = SpiDevice<'static, CriticalSectionRawMutex, SpiHandle, Output<'static>>;
+pub type SpiError = SpiDeviceError<spi::Error, core::convert::Infallible>;
+
+static SPI1_HANDLE: StaticCell<SpiProtected> = StaticCell::new();
+
+pub fn init_spi1(
+    spi1: Peri<'static, SPI1>,
+    sck: Peri<'static, impl SckPin<SPI1>>,
+    mosi: Peri<'static, impl MosiPin<SPI1>>,
+    miso: Peri<'static, impl MisoPin<SPI1>>,
+) -> &'static SpiProtected {
+    let mut config = spi::Config::default();
+    config.frequency = SPI1_FREQUENCY;
+
+    let handle = Spi::new_blocking(spi1, sck, mosi, miso, config);
+    let protected = CriticalSectionMutex::new(RefCell::new(handle));
+
+    SPI1_HANDLE.init(protected)
+}
+
+/// Bus microSD card is alone on, it starts at [SPI2_INIT_FREQUENCY]
+pub fn init_spi2(
+    spi2: Peri<'static, SPI2>,
+    sck: Peri<'static, impl SckPin<SPI2>>,
+    mosi: Peri<'static, impl MosiPin<SPI2>>,
+    miso: Peri<'static, impl MisoPin<SPI2>>,
+) -> SpiHandle {
+    let mut config = spi::Config::default();
+    config.frequency = SPI2_INIT_FREQUENCY;
+
+    Spi::new_blocking(spi2, sck, mosi, miso, config)
+}
+
+impl CardBus for SpiHandle {
+    fn set_init_clock(&mut self, init: bool) {
+        let mut config = spi::Config::default();
+        config.frequency = if init {
+            SPI2_INIT_FREQUENCY
+        } else {
+            SPI2_FREQUENCY
+        };
+        // Only fails for word sizes bus does not support
+        self.set_config(&config).ok();
+    }
+}
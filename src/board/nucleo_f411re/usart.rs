@@ -0,0 +1,120 @@
+use embassy_stm32::{
+    bind_interrupts,
+    gpio::Output,
+    peripherals::{USART1, USART2, USART6},
+    usart::{self, BufferedUart, Parity, RxPin, TxPin},
+    Peri,
+};
+use embedded_io_async::{Read as _, Write as _};
+use static_cell::StaticCell;
+
+/// Speed of ST-LINK virtual COM port
+const USART2_BAUDRATE: u32 = 115_200;
+
+/// Default speed of ESP AT firmware
+const USART6_BAUDRATE: u32 = 115_200;
+
+/// Default speed of Modbus RTU, with even parity
+const USART1_BAUDRATE: u32 = 19_200;
+
+const TX_BUFFER_SIZE: usize = 256;
+const RX_BUFFER_SIZE: usize = 64;
+
+/// Fits longest AT command and response line
+const MODEM_BUFFER_SIZE: usize = 256;
+
+/// Fits largest Modbus RTU frame
+const RS485_BUFFER_SIZE: usize = 256;
+
+pub type UartHandle = BufferedUart<'static>;
+pub type UartError = usart::Error;
+
+bind_interrupts!(struct Irqs {
+    USART1 => usart::BufferedInterruptHandler<USART1>;
+    USART2 => usart::BufferedInterruptHandler<USART2>;
+    USART6 => usart::BufferedInterruptHandler<USART6>;
+});
+
+static TX_BUFFER: StaticCell<[u8; TX_BUFFER_SIZE]> = StaticCell::new();
+static RX_BUFFER: StaticCell<[u8; RX_BUFFER_SIZE]> = StaticCell::new();
+static MODEM_TX_BUFFER: StaticCell<[u8; MODEM_BUFFER_SIZE]> = StaticCell::new();
+static MODEM_RX_BUFFER: StaticCell<[u8; MODEM_BUFFER_SIZE]> = StaticCell::new();
+static RS485_TX_BUFFER: StaticCell<[u8; RS485_BUFFER_SIZE]> = StaticCell::new();
+static RS485_RX_BUFFER: StaticCell<[u8; RS485_BUFFER_SIZE]> = StaticCell::new();
+
+pub fn init_usart2(
+    usart2: Peri<'static, USART2>,
+    rx: Peri<'static, impl RxPin<USART2>>,
+    tx: Peri<'static, impl TxPin<USART2>>,
+) -> UartHandle {
+    let mut config = usart::Config::default();
+    config.baudrate = USART2_BAUDRATE;
+
+    let tx_buffer = TX_BUFFER.init([0; TX_BUFFER_SIZE]);
+    let rx_buffer = RX_BUFFER.init([0; RX_BUFFER_SIZE]);
+    defmt::unwrap!(BufferedUart::new(
+        usart2, rx, tx, tx_buffer, rx_buffer, Irqs, config
+    ))
+}
+
+pub fn init_usart6(
+    usart6: Peri<'static, USART6>,
+    rx: Peri<'static, impl RxPin<USART6>>,
+    tx: Peri<'static, impl TxPin<USART6>>,
+) -> UartHandle {
+    let mut config = usart::Config::default();
+    config.baudrate = USART6_BAUDRATE;
+
+    let tx_buffer = MODEM_TX_BUFFER.init([0; MODEM_BUFFER_SIZE]);
+    let rx_buffer = MODEM_RX_BUFFER.init([0; MODEM_BUFFER_SIZE]);
+    defmt::unwrap!(BufferedUart::new(
+        usart6, rx, tx, tx_buffer, rx_buffer, Irqs, config
+    ))
+}
+
+/// Half-duplex port, transceiver drives the bus only while driver enable pin is high
+pub struct Rs485 {
+    uart: UartHandle,
+    driver_enable: Output<'static>,
+}
+
+impl Rs485 {
+    /// Reads bytes received so far, waits if there are none
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, UartError> {
+        self.uart.read(buffer).await
+    }
+
+    /// Sends bytes and releases the bus once the last bit is out
+    pub async fn send(&mut self, bytes: &[u8]) -> Result<(), UartError> {
+        self.driver_enable.set_high();
+        let result = match self.uart.write_all(bytes).await {
+            // Flush waits for transmission complete, not just empty buffer
+            Ok(()) => self.uart.flush().await,
+            Err(err) => Err(err),
+        };
+        self.driver_enable.set_low();
+        result
+    }
+}
+
+pub fn init_usart1_rs485(
+    usart1: Peri<'static, USART1>,
+    rx: Peri<'static, impl RxPin<USART1>>,
+    tx: Peri<'static, impl TxPin<USART1>>,
+    driver_enable: Output<'static>,
+) -> Rs485 {
+    let mut config = usart::Config::default();
+    config.baudrate = USART1_BAUDRATE;
+    config.parity = Parity::ParityEven;
+
+    let tx_buffer = RS485_TX_BUFFER.init([0; RS485_BUFFER_SIZE]);
+    let rx_buffer = RS485_RX_BUFFER.init([0; RS485_BUFFER_SIZE]);
+    let uart = defmt::unwrap!(BufferedUart::new(
+        usart1, rx, tx, tx_buffer, rx_buffer, Irqs, config
+    ));
+
+    Rs485 {
+        uart,
+        driver_enable,
+    }
+}
@@ -0,0 +1,31 @@
+use embassy_stm32::{
+    bind_interrupts,
+    peripherals::USB_OTG_FS,
+    usb::{self, DmPin, DpPin, Driver},
+    Peri,
+};
+use static_cell::StaticCell;
+
+/// Buffer shared by all OUT endpoints, fits full-speed packets of a few endpoints
+const EP_OUT_BUFFER_SIZE: usize = 256;
+
+pub type UsbDriver = Driver<'static, USB_OTG_FS>;
+
+bind_interrupts!(struct Irqs {
+    OTG_FS => usb::InterruptHandler<USB_OTG_FS>;
+});
+
+static EP_OUT_BUFFER: StaticCell<[u8; EP_OUT_BUFFER_SIZE]> = StaticCell::new();
+
+pub fn init_usb(
+    usb: Peri<'static, USB_OTG_FS>,
+    dp: Peri<'static, impl DpPin<USB_OTG_FS>>,
+    dm: Peri<'static, impl DmPin<USB_OTG_FS>>,
+) -> UsbDriver {
+    // PA9 is used by display, so VBUS is not sensed and device is always considered powered
+    let mut config = usb::Config::default();
+    config.vbus_detection = false;
+
+    let ep_out_buffer = EP_OUT_BUFFER.init([0; EP_OUT_BUFFER_SIZE]);
+    Driver::new_fs(usb, Irqs, dp, dm, ep_out_buffer, config)
+}
@@ -0,0 +1,28 @@
+//!
+//! Calibration offsets of sensors
+//!
+//! Offset is added to every reading of a sensor before readings are combined, so a sensor
+//! warmed by nearby parts can be corrected at runtime.
+//!
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::measurement::{SensorId, SENSOR_COUNT};
+
+/// Offsets in Celsius stored as bits of `f32`, zero bits are `0.0`
+static OFFSETS: [AtomicU32; SENSOR_COUNT] = [const { AtomicU32::new(0) }; SENSOR_COUNT];
+
+/// Offset of sensor, in Celsius
+pub fn offset(id: SensorId) -> f32 {
+    f32::from_bits(OFFSETS[id as usize].load(Ordering::Relaxed))
+}
+
+pub fn set_offset(id: SensorId, celsius: f32) {
+    OFFSETS[id as usize].store(celsius.to_bits(), Ordering::Relaxed);
+    defmt::info!("calibration: {=str} offset {=f32}C", id.name(), celsius);
+}
+
+/// Corrects reading of sensor given in Celsius
+pub fn apply(id: SensorId, celsius: f32) -> f32 {
+    celsius + offset(id)
+}
@@ -0,0 +1,118 @@
+//!
+//! Wall-clock time service
+//!
+//! Reads DS3231 once and then keeps UTC time by adding [Instant] elapsed since the read,
+//! so time can be taken without touching the bus. The clock is resynced with RTC periodically
+//! to compensate drift of the MCU oscillator.
+//!
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::drivers::ds3231::{self, Ds3231, NaiveDateTime};
+
+/// How often clock is synchronized with RTC
+pub const RESYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How soon to retry after failed synchronization
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+struct SyncPoint {
+    /// Milliseconds since Unix epoch at `instant`
+    unix_millis: i64,
+    instant: Instant,
+}
+
+pub struct Shared {
+    sync: CriticalSectionMutex<Cell<Option<SyncPoint>>>,
+}
+
+impl Shared {
+    pub const fn new() -> Self {
+        Self {
+            sync: CriticalSectionMutex::new(Cell::new(None)),
+        }
+    }
+
+    /// Milliseconds since Unix epoch, `None` until first synchronization with RTC
+    ///
+    /// Does not block, so can be used from any context including log timestamps
+    pub fn unix_millis(&self) -> Option<i64> {
+        let point = self.sync.lock(|sync| sync.get())?;
+        let elapsed = point.instant.elapsed().as_millis() as i64;
+        Some(point.unix_millis + elapsed)
+    }
+
+    fn sync_to(&self, datetime: &NaiveDateTime) {
+        let point = SyncPoint {
+            unix_millis: datetime.timestamp_millis(),
+            instant: Instant::now(),
+        };
+        self.sync.lock(|sync| sync.set(Some(point)));
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle to get current UTC time
+#[derive(Clone, Copy)]
+pub struct Clock<'a> {
+    rtc: Ds3231<'a>,
+    shared: &'a Shared,
+}
+
+impl Clock<'_> {
+    /// Milliseconds since Unix epoch, `None` until first synchronization with RTC
+    pub fn unix_millis(&self) -> Option<i64> {
+        self.shared.unix_millis()
+    }
+
+    /// Current UTC date-time, `None` until first synchronization with RTC
+    pub fn now(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::from_timestamp_millis(self.unix_millis()?)
+    }
+
+    /// Sets RTC to UTC date-time and synchronizes with it
+    pub async fn set(&self, datetime: &NaiveDateTime) -> Result<(), ds3231::Error> {
+        self.rtc.set_datetime(datetime).await?;
+        self.shared.sync_to(datetime);
+        Ok(())
+    }
+}
+
+pub struct Runner<'a> {
+    rtc: Ds3231<'a>,
+    shared: &'a Shared,
+}
+
+impl Runner<'_> {
+    pub async fn run(self) -> ! {
+        loop {
+            let next_sync = match self.rtc.datetime().await {
+                Ok(datetime) => {
+                    self.shared.sync_to(&datetime);
+                    defmt::debug!("clock: synced");
+                    RESYNC_INTERVAL
+                }
+                Err(err) => {
+                    defmt::error!("clock: failed to sync: {:?}", err);
+                    RETRY_INTERVAL
+                }
+            };
+
+            Timer::after(next_sync).await;
+        }
+    }
+}
+
+pub fn new<'a>(rtc: Ds3231<'a>, data: &'a Shared) -> (Clock<'a>, Runner<'a>) {
+    let runner = Runner { rtc, shared: data };
+    (Clock { rtc, shared: data }, runner)
+}
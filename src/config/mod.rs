@@ -0,0 +1,348 @@
+//!
+//! Settings kept in internal flash across restarts
+//!
+//! Settings are serialized with [postcard] into a versioned record of [store::Store]. Records
+//! of older versions are migrated when loaded, so saved configuration survives firmware
+//! updates. Without valid record, e.g. after factory reset, defaults of drivers are used.
+//!
+//! Settings are loaded once at start and saved on request from [crate::shell]. Unit and
+//! language changed with the button of the display are saved right away.
+//!
+
+pub mod store;
+
+#[cfg(feature = "board")]
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::Duration;
+use embedded_storage::nor_flash::NorFlash;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calibration,
+    display::locale::{self, Language},
+    drivers::sensors::{
+        dht22::{self, Dht22},
+        lm75::{self, Lm75},
+    },
+    measurement::{SensorId, SENSOR_COUNT},
+    modbus::rtu::SLAVE_ADDRESSES,
+    units::{self, TemperatureUnit},
+};
+use store::{Store, MAX_PAYLOAD_SIZE};
+
+/// Version of [Settings] layout, incremented on every change of it
+pub const VERSION: u16 = 1;
+
+/// Flash holding configuration, two erase blocks
+#[cfg(feature = "board")]
+pub type ConfigFlash = crate::bsp::ConfigFlash;
+
+/// Store shared by tasks saving configuration
+#[cfg(feature = "board")]
+pub type SharedStore = Mutex<CriticalSectionRawMutex, Store<ConfigFlash>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum Error {
+    #[error("Flash")]
+    Flash,
+    /// Written record does not read back
+    #[error("Verify")]
+    Verify,
+    #[error("Too large")]
+    TooLarge,
+    /// Record was written by newer firmware
+    #[error("Unsupported version")]
+    UnsupportedVersion(u16),
+    #[error("Malformed")]
+    Malformed,
+}
+
+/// Everything configurable at runtime
+///
+/// Enumerations are kept as their codes, so the layout does not depend on declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Code of [TemperatureUnit]
+    pub temperature_unit: u8,
+    /// Code of [Language]
+    pub language: u8,
+    /// I2C address of LM75
+    pub lm75_address: u8,
+    /// Over-temperature threshold of LM75, in Celsius
+    pub os_temperature: f32,
+    /// Hysteresis of LM75, in Celsius
+    pub hysteresis_temperature: f32,
+    /// Time between readings of each sensor in order of [SensorId::ALL], in ms
+    pub intervals: [u32; SENSOR_COUNT],
+    /// Calibration offset of each sensor in order of [SensorId::ALL], in Celsius
+    pub offsets: [f32; SENSOR_COUNT],
+    pub modbus_address: u8,
+}
+
+impl Settings {
+    /// Address of Modbus slave unless configured
+    pub const DEFAULT_MODBUS_ADDRESS: u8 = 1;
+
+    /// Applies unit, language and calibration, which are global preferences
+    pub fn apply_preferences(&self) {
+        if let Some(unit) = TemperatureUnit::from_code(self.temperature_unit) {
+            units::set_temperature_unit(unit);
+        }
+        if let Some(language) = Language::from_code(self.language) {
+            locale::set_language(language);
+        }
+        for id in SensorId::ALL {
+            calibration::set_offset(id, self.offsets[id as usize]);
+        }
+    }
+
+    /// Configuration of LM75 with stored address, thresholds and interval
+    pub fn lm75_config(&self, config: lm75::Config) -> lm75::Config {
+        lm75::Config {
+            address: self.lm75_address,
+            measurement_interval: self.interval(SensorId::Lm75),
+            os_temperature: self.os_temperature,
+            hysteresis_temperature: self.hysteresis_temperature,
+            ..config
+        }
+    }
+
+    pub fn interval(&self, id: SensorId) -> Duration {
+        Duration::from_millis(self.intervals[id as usize].into())
+    }
+
+    /// Takes current configuration of sensors and preferences, other settings are kept
+    pub async fn capture(&mut self, lm75: &Lm75<'_>, dht22: &Dht22<'_>) {
+        let config = lm75.config().await;
+        let millis = |interval: Duration| interval.as_millis().try_into().unwrap_or(u32::MAX);
+
+        self.temperature_unit = units::temperature_unit().code();
+        self.language = locale::language().code();
+        self.os_temperature = config.os_temperature;
+        self.hysteresis_temperature = config.hysteresis_temperature;
+        self.intervals = [
+            millis(config.measurement_interval),
+            millis(dht22.measurement_interval().await),
+        ];
+        self.offsets = SensorId::ALL.map(calibration::offset);
+    }
+
+    /// Replaces values drivers can't use with defaults, e.g. of hand-written record
+    ///
+    /// Rules are the same as for settings written over [Modbus](crate::modbus).
+    fn validated(self) -> Self {
+        let defaults = Self::default();
+        let mut settings = self;
+
+        if TemperatureUnit::from_code(settings.temperature_unit).is_none() {
+            settings.temperature_unit = defaults.temperature_unit;
+        }
+        if Language::from_code(settings.language).is_none() {
+            settings.language = defaults.language;
+        }
+        if !lm75::ADDRESSES.contains(&settings.lm75_address) {
+            settings.lm75_address = defaults.lm75_address;
+        }
+        if !lm75::valid_thresholds(settings.os_temperature, settings.hysteresis_temperature) {
+            settings.os_temperature = defaults.os_temperature;
+            settings.hysteresis_temperature = defaults.hysteresis_temperature;
+        }
+        for id in SensorId::ALL {
+            if settings.interval(id) < id.fastest_interval() {
+                settings.intervals[id as usize] = defaults.intervals[id as usize];
+            }
+            if !settings.offsets[id as usize].is_finite() {
+                settings.offsets[id as usize] = defaults.offsets[id as usize];
+            }
+        }
+        if !SLAVE_ADDRESSES.contains(&settings.modbus_address) {
+            settings.modbus_address = defaults.modbus_address;
+        }
+
+        if settings != self {
+            defmt::warn!("config: invalid values replaced by defaults");
+        }
+        settings
+    }
+
+    fn decode(version: u16, payload: &[u8]) -> Result<Self, Error> {
+        match version {
+            VERSION => postcard::from_bytes(payload).map_err(|_| Error::Malformed),
+            // Older layouts are decoded into their own structs and converted here
+            _ => Err(Error::UnsupportedVersion(version)),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let lm75 = lm75::Config::default();
+        let millis = |interval: Duration| interval.as_millis() as u32;
+        Self {
+            temperature_unit: TemperatureUnit::Celsius.code(),
+            language: Language::English.code(),
+            lm75_address: lm75.address,
+            os_temperature: lm75.os_temperature,
+            hysteresis_temperature: lm75.hysteresis_temperature,
+            intervals: [
+                millis(lm75.measurement_interval),
+                millis(dht22::MEASUREMENT_INTERVAL),
+            ],
+            offsets: [0.0; SENSOR_COUNT],
+            modbus_address: Self::DEFAULT_MODBUS_ADDRESS,
+        }
+    }
+}
+
+/// Loads newest settings, defaults are used if there are none or they can't be read
+pub fn load<F: NorFlash>(store: &mut Store<F>) -> Settings {
+    match load_saved(store) {
+        Ok(Some(settings)) => {
+            defmt::info!("config: loaded");
+            settings
+        }
+        Ok(None) => {
+            defmt::info!("config: not saved yet, using defaults");
+            Settings::default()
+        }
+        Err(err) => {
+            defmt::error!("config: not loaded: {}, using defaults", err);
+            Settings::default()
+        }
+    }
+}
+
+pub fn save<F: NorFlash>(store: &mut Store<F>, settings: &Settings) -> Result<(), Error> {
+    let mut payload = [0; MAX_PAYLOAD_SIZE];
+    let payload = postcard::to_slice(settings, &mut payload).map_err(|_| Error::TooLarge)?;
+    store.save(VERSION, payload)?;
+    defmt::info!("config: saved");
+    Ok(())
+}
+
+/// Saves unit and language, other settings are kept as saved
+///
+/// Nothing is saved if saved settings can't be read, so they are not replaced by defaults.
+pub fn save_preferences<F: NorFlash>(
+    store: &mut Store<F>,
+    unit: TemperatureUnit,
+    language: Language,
+) -> Result<(), Error> {
+    let mut settings = load_saved(store)?.unwrap_or_default();
+    settings.temperature_unit = unit.code();
+    settings.language = language.code();
+    save(store, &settings)
+}
+
+/// Erases saved settings, defaults are used after restart
+pub fn factory_reset<F: NorFlash>(store: &mut Store<F>) -> Result<(), Error> {
+    store.erase()?;
+    defmt::warn!("config: factory reset");
+    Ok(())
+}
+
+fn load_saved<F: NorFlash>(store: &mut Store<F>) -> Result<Option<Settings>, Error> {
+    match store.load()? {
+        Some(record) => Settings::decode(record.version, record.payload())
+            .map(|settings| Some(settings.validated())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::RamFlash;
+
+    type Flash = RamFlash<{ 2 * 1024 }, 1024>;
+
+    #[test]
+    fn loads_saved_settings() {
+        let mut flash = Flash::new();
+        let settings = Settings {
+            offsets: [0.5, -1.25],
+            modbus_address: 17,
+            ..Settings::default()
+        };
+        save(&mut Store::new(&mut flash, 0), &settings).unwrap();
+
+        assert_eq!(load(&mut Store::new(&mut flash, 0)), settings);
+    }
+
+    #[test]
+    fn replaces_invalid_values_with_defaults() {
+        let mut flash = Flash::new();
+        let settings = Settings {
+            temperature_unit: 7,
+            lm75_address: 0x20,
+            os_temperature: f32::NAN,
+            intervals: [0, 5000],
+            offsets: [f32::INFINITY, 0.5],
+            modbus_address: 0,
+            ..Settings::default()
+        };
+        save(&mut Store::new(&mut flash, 0), &settings).unwrap();
+
+        assert_eq!(
+            load(&mut Store::new(&mut flash, 0)),
+            Settings {
+                intervals: [Settings::default().intervals[0], 5000],
+                offsets: [0.0, 0.5],
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn uses_defaults_for_unsupported_version() {
+        let mut flash = Flash::new();
+        Store::new(&mut flash, 0)
+            .save(VERSION + 1, &[0; 32])
+            .unwrap();
+        let mut store = Store::new(&mut flash, 0);
+
+        assert_eq!(
+            load_saved(&mut store),
+            Err(Error::UnsupportedVersion(VERSION + 1))
+        );
+        assert_eq!(load(&mut store), Settings::default());
+        // Record of newer firmware is not overwritten
+        assert_eq!(
+            save_preferences(&mut store, TemperatureUnit::Kelvin, Language::English),
+            Err(Error::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn saves_preferences_keeping_other_settings() {
+        let mut flash = Flash::new();
+        let settings = Settings {
+            modbus_address: 17,
+            ..Settings::default()
+        };
+        save(&mut Store::new(&mut flash, 0), &settings).unwrap();
+
+        let mut store = Store::new(&mut flash, 0);
+        save_preferences(&mut store, TemperatureUnit::Kelvin, Language::Russian).unwrap();
+
+        let loaded = load(&mut Store::new(&mut flash, 0));
+        assert_eq!(loaded.temperature_unit, TemperatureUnit::Kelvin.code());
+        assert_eq!(loaded.language, Language::Russian.code());
+        assert_eq!(loaded.modbus_address, 17);
+    }
+
+    #[test]
+    fn factory_reset_restores_defaults() {
+        let mut flash = Flash::new();
+        let mut store = Store::new(&mut flash, 0);
+        let settings = Settings {
+            lm75_address: 0x4f,
+            ..Settings::default()
+        };
+        save(&mut store, &settings).unwrap();
+
+        factory_reset(&mut store).unwrap();
+
+        assert_eq!(load(&mut Store::new(&mut flash, 0)), Settings::default());
+    }
+}
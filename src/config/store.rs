@@ -0,0 +1,342 @@
+//!
+//! Versioned records in two erase blocks of NOR flash
+//!
+//! Records are appended to fixed-size slots of one bank until it is full, then the other bank
+//! is erased and used, so a bank is erased once per many saves and the newest record of the
+//! previous bank survives power loss during erase or write. Newest valid record is found by
+//! its sequence number.
+//!
+//! Slot layout, little-endian:
+//!
+//! | Offset | Size | Value |
+//! |---|---|---|
+//! | 0 | 4 | [MAGIC] |
+//! | 4 | 4 | sequence number |
+//! | 8 | 2 | version of payload |
+//! | 10 | 2 | length of payload |
+//! | 12 | length | payload |
+//! | 12 + length | 4 | CRC-32 of all above |
+//!
+
+use embedded_storage::nor_flash::NorFlash;
+
+use super::Error;
+
+pub const SLOT_SIZE: usize = 128;
+
+/// Payload of record fits slot together with header and CRC
+pub const MAX_PAYLOAD_SIZE: usize = SLOT_SIZE - HEADER_SIZE - CRC_SIZE;
+
+/// Marks slot holding a record
+pub const MAGIC: u32 = 0x4746_4e43;
+
+const HEADER_SIZE: usize = 12;
+const CRC_SIZE: usize = 4;
+const BANK_COUNT: u32 = 2;
+
+/// Reversed polynomial 0x04c11db7
+const CRC_POLYNOMIAL: u32 = 0xedb8_8320;
+
+/// CRC-32 of bytes, as used by zlib
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ CRC_POLYNOMIAL
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Record found in flash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub version: u16,
+    length: usize,
+    sequence: u32,
+    slot: [u8; SLOT_SIZE],
+}
+
+impl Record {
+    pub fn payload(&self) -> &[u8] {
+        &self.slot[HEADER_SIZE..HEADER_SIZE + self.length]
+    }
+
+    fn parse(slot: &[u8; SLOT_SIZE]) -> Option<Self> {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                slot[offset],
+                slot[offset + 1],
+                slot[offset + 2],
+                slot[offset + 3],
+            ])
+        };
+        if word(0) != MAGIC {
+            return None;
+        }
+        let version = u16::from_le_bytes([slot[8], slot[9]]);
+        let length = usize::from(u16::from_le_bytes([slot[10], slot[11]]));
+        if length > MAX_PAYLOAD_SIZE
+            || word(HEADER_SIZE + length) != crc32(&slot[..HEADER_SIZE + length])
+        {
+            return None;
+        }
+
+        Some(Self {
+            version,
+            length,
+            sequence: word(4),
+            slot: *slot,
+        })
+    }
+}
+
+/// Position of next write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    bank: u32,
+    slot: u32,
+    sequence: u32,
+}
+
+pub struct Store<F> {
+    flash: F,
+    /// Offset of first bank in flash
+    base: u32,
+    cursor: Option<Cursor>,
+}
+
+impl<F: NorFlash> Store<F> {
+    /// Each bank is one erase block
+    const BANK_SIZE: u32 = F::ERASE_SIZE as u32;
+    const SLOTS_PER_BANK: u32 = Self::BANK_SIZE / SLOT_SIZE as u32;
+
+    /// Uses two erase blocks starting at offset of flash
+    pub fn new(flash: F, base: u32) -> Self {
+        const {
+            assert!(
+                SLOT_SIZE.is_multiple_of(F::WRITE_SIZE) && F::ERASE_SIZE.is_multiple_of(SLOT_SIZE)
+            );
+        }
+        Self {
+            flash,
+            base,
+            cursor: None,
+        }
+    }
+
+    /// Finds newest valid record
+    pub fn load(&mut self) -> Result<Option<Record>, Error> {
+        let mut newest: Option<(u32, Record)> = None;
+        let mut ends = [0; BANK_COUNT as usize];
+
+        for bank in 0..BANK_COUNT {
+            for slot in 0..Self::SLOTS_PER_BANK {
+                let mut bytes = [0; SLOT_SIZE];
+                self.flash
+                    .read(self.slot_offset(bank, slot), &mut bytes)
+                    .map_err(|_| Error::Flash)?;
+                // Damaged slots are not written again before erase
+                if bytes.iter().any(|&byte| byte != 0xff) {
+                    ends[bank as usize] = slot + 1;
+                }
+
+                let Some(record) = Record::parse(&bytes) else {
+                    continue;
+                };
+                if newest.is_none_or(|(_, newest)| record.sequence > newest.sequence) {
+                    newest = Some((bank, record));
+                }
+            }
+        }
+
+        self.cursor = Some(match newest {
+            Some((bank, record)) => Cursor {
+                bank,
+                slot: ends[bank as usize],
+                sequence: record.sequence.wrapping_add(1),
+            },
+            None => Cursor {
+                bank: 0,
+                slot: ends[0],
+                sequence: 0,
+            },
+        });
+        Ok(newest.map(|(_, record)| record))
+    }
+
+    /// Appends record which becomes the newest one
+    pub fn save(&mut self, version: u16, payload: &[u8]) -> Result<(), Error> {
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(Error::TooLarge);
+        }
+        let mut cursor = match self.cursor {
+            Some(cursor) => cursor,
+            None => {
+                self.load()?;
+                defmt::unwrap!(self.cursor)
+            }
+        };
+
+        if cursor.slot >= Self::SLOTS_PER_BANK {
+            cursor.bank = (cursor.bank + 1) % BANK_COUNT;
+            cursor.slot = 0;
+            self.erase_bank(cursor.bank)?;
+        }
+
+        let mut slot = [0xff; SLOT_SIZE];
+        let end = HEADER_SIZE + payload.len();
+        slot[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        slot[4..8].copy_from_slice(&cursor.sequence.to_le_bytes());
+        slot[8..10].copy_from_slice(&version.to_le_bytes());
+        slot[10..12].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        slot[HEADER_SIZE..end].copy_from_slice(payload);
+        let crc = crc32(&slot[..end]);
+        slot[end..end + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let offset = self.slot_offset(cursor.bank, cursor.slot);
+        // Slot is used even if write fails, it is not erased anymore
+        self.cursor = Some(Cursor {
+            slot: cursor.slot + 1,
+            sequence: cursor.sequence.wrapping_add(1),
+            ..cursor
+        });
+        self.flash.write(offset, &slot).map_err(|_| Error::Flash)?;
+
+        let mut written = [0; SLOT_SIZE];
+        self.flash
+            .read(offset, &mut written)
+            .map_err(|_| Error::Flash)?;
+        if written != slot {
+            return Err(Error::Verify);
+        }
+        Ok(())
+    }
+
+    /// Erases both banks, so nothing is loaded anymore
+    pub fn erase(&mut self) -> Result<(), Error> {
+        for bank in 0..BANK_COUNT {
+            self.erase_bank(bank)?;
+        }
+        self.cursor = Some(Cursor {
+            bank: 0,
+            slot: 0,
+            sequence: 0,
+        });
+        Ok(())
+    }
+
+    fn erase_bank(&mut self, bank: u32) -> Result<(), Error> {
+        let from = self.base + bank * Self::BANK_SIZE;
+        self.flash
+            .erase(from, from + Self::BANK_SIZE)
+            .map_err(|_| Error::Flash)
+    }
+
+    fn slot_offset(&self, bank: u32, slot: u32) -> u32 {
+        self.base + bank * Self::BANK_SIZE + slot * SLOT_SIZE as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::RamFlash;
+
+    /// Banks of four slots
+    type Flash = RamFlash<{ 2 * 4 * SLOT_SIZE }, { 4 * SLOT_SIZE }>;
+
+    /// Newest record as found after restart
+    fn newest(flash: &mut Flash) -> Option<(u16, Vec<u8>)> {
+        let record = Store::new(flash, 0).load().unwrap();
+        record.map(|record| (record.version, record.payload().to_vec()))
+    }
+
+    #[test]
+    fn crc_matches_known_vector() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn loads_newest_record() {
+        let mut flash = Flash::new();
+        assert_eq!(newest(&mut flash), None);
+
+        let mut store = Store::new(&mut flash, 0);
+        store.save(1, b"first").unwrap();
+        store.save(2, b"second").unwrap();
+
+        assert_eq!(newest(&mut flash), Some((2, b"second".to_vec())));
+    }
+
+    #[test]
+    fn rolls_over_to_other_bank() {
+        let mut flash = Flash::new();
+
+        // Both banks are filled twice
+        for n in 0..17u8 {
+            Store::new(&mut flash, 0).save(1, &[n]).unwrap();
+            assert_eq!(newest(&mut flash), Some((1, vec![n])));
+        }
+        // Record 16 started first bank again, erasing records 0 to 3
+        assert_eq!(flash.bytes[SLOT_SIZE..4 * SLOT_SIZE], [0xff; 3 * SLOT_SIZE]);
+    }
+
+    #[test]
+    fn keeps_previous_record_when_write_is_torn() {
+        let mut flash = Flash::new();
+        Store::new(&mut flash, 0).save(1, b"kept").unwrap();
+
+        flash.power_budget = Some(HEADER_SIZE + 2);
+        assert_eq!(
+            Store::new(&mut flash, 0).save(1, b"torn"),
+            Err(Error::Flash)
+        );
+        flash.power_budget = None;
+        assert_eq!(newest(&mut flash), Some((1, b"kept".to_vec())));
+
+        // Torn slot is skipped, not written again
+        Store::new(&mut flash, 0).save(1, b"next").unwrap();
+        assert_eq!(newest(&mut flash), Some((1, b"next".to_vec())));
+        assert_eq!(flash.bytes[2 * SLOT_SIZE..][..4], MAGIC.to_le_bytes());
+    }
+
+    #[test]
+    fn skips_record_with_bad_crc() {
+        let mut flash = Flash::new();
+        let mut store = Store::new(&mut flash, 0);
+        store.save(1, b"good").unwrap();
+        store.save(1, b"flipped").unwrap();
+
+        flash.bytes[SLOT_SIZE + HEADER_SIZE] ^= 0x01;
+
+        assert_eq!(newest(&mut flash), Some((1, b"good".to_vec())));
+    }
+
+    #[test]
+    fn erase_leaves_nothing_to_load() {
+        let mut flash = Flash::new();
+        let mut store = Store::new(&mut flash, 0);
+        for n in 0..6u8 {
+            store.save(1, &[n]).unwrap();
+        }
+
+        store.erase().unwrap();
+        assert_eq!(store.load(), Ok(None));
+        store.save(1, b"fresh").unwrap();
+
+        assert_eq!(newest(&mut flash), Some((1, b"fresh".to_vec())));
+    }
+
+    #[test]
+    fn refuses_too_large_payload() {
+        let mut flash = Flash::new();
+
+        assert_eq!(
+            Store::new(&mut flash, 0).save(1, &[0; MAX_PAYLOAD_SIZE + 1]),
+            Err(Error::TooLarge)
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//!
+//! Log of measurements kept in flash
+//!
+//! Measurements are appended to a [ring::Ring] at a fixed interval, so readings taken while
+//! nobody listens are not lost. Log survives restarts and can be dumped as CSV from the
+//! [shell](crate::shell).
+//!
+//! Record layout, little-endian:
+//!
+//! | Offset | Size | Value |
+//! |---|---|---|
+//! | 0 | 4 | UTC unix time in seconds, 0 if clock was not synchronized |
+//! | 4 | 2 per sensor | temperature of sensor in hundredths of Celsius |
+//! | 8 | 2 | relative humidity in tenths of % |
+//! | 10 | 1 | status of sensors, 2 bits each, see [Entry::statuses] |
+//! | 11 | 1 | reserved |
+//!
+
+pub mod ring;
+#[cfg(feature = "board")]
+mod runner;
+
+#[cfg(feature = "board")]
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+#[cfg_attr(not(target_os = "none"), allow(unused_imports))]
+use num_traits::float::FloatCore;
+
+use crate::{
+    drivers::sensors::status::SensorStatus,
+    measurement::{Measurement, SensorId, SENSOR_COUNT},
+};
+#[cfg(feature = "board")]
+use ring::Ring;
+use ring::RECORD_SIZE;
+#[cfg(feature = "board")]
+pub use runner::{new, Runner};
+
+/// Flash holding log, see [bsp::LogFlash](crate::bsp::LogFlash)
+#[cfg(feature = "board")]
+pub type LogFlash = crate::bsp::LogFlash;
+
+/// Log shared by logger and readers
+#[cfg(feature = "board")]
+pub type SharedLog = Mutex<CriticalSectionRawMutex, Ring<LogFlash>>;
+
+const TEMPERATURES: usize = 4;
+const HUMIDITY: usize = TEMPERATURES + 2 * SENSOR_COUNT;
+const STATUSES: usize = HUMIDITY + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum Error {
+    #[error("Flash")]
+    Flash,
+}
+
+/// Measurement as kept in log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    /// UTC unix time in seconds, `None` if clock was not synchronized
+    pub timestamp: Option<u32>,
+    /// Temperature of each sensor in order of [SensorId::ALL], in Celsius
+    pub temperatures: [f32; SENSOR_COUNT],
+    /// Status of each sensor in order of [SensorId::ALL]
+    pub statuses: [SensorStatus; SENSOR_COUNT],
+    /// Relative humidity in %
+    pub humidity: f32,
+}
+
+impl Entry {
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0; RECORD_SIZE];
+        record[0..4].copy_from_slice(&self.timestamp.unwrap_or(0).to_le_bytes());
+        for id in SensorId::ALL {
+            let offset = TEMPERATURES + 2 * id as usize;
+            let centi = (self.temperatures[id as usize] * 100.0).round() as i16;
+            record[offset..offset + 2].copy_from_slice(&centi.to_le_bytes());
+
+            let status = match self.statuses[id as usize] {
+                SensorStatus::Unknown => 0,
+                SensorStatus::Ok => 1,
+                SensorStatus::Error => 2,
+            };
+            record[STATUSES] |= status << (2 * id as usize);
+        }
+        let humidity = (self.humidity * 10.0).round() as u16;
+        record[HUMIDITY..HUMIDITY + 2].copy_from_slice(&humidity.to_le_bytes());
+        record
+    }
+
+    pub fn decode(record: &[u8; RECORD_SIZE]) -> Self {
+        let timestamp = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let temperature = |id: SensorId| {
+            let offset = TEMPERATURES + 2 * id as usize;
+            f32::from(i16::from_le_bytes([record[offset], record[offset + 1]])) / 100.0
+        };
+        let status = |id: SensorId| match (record[STATUSES] >> (2 * id as usize)) & 0b11 {
+            1 => SensorStatus::Ok,
+            2 => SensorStatus::Error,
+            _ => SensorStatus::Unknown,
+        };
+
+        Self {
+            timestamp: (timestamp != 0).then_some(timestamp),
+            temperatures: SensorId::ALL.map(temperature),
+            statuses: SensorId::ALL.map(status),
+            humidity: f32::from(u16::from_le_bytes([record[HUMIDITY], record[HUMIDITY + 1]]))
+                / 10.0,
+        }
+    }
+}
+
+impl From<&Measurement> for Entry {
+    fn from(measurement: &Measurement) -> Self {
+        Self {
+            timestamp: measurement
+                .timestamp
+                .and_then(|timestamp| timestamp.0.timestamp().try_into().ok()),
+            temperatures: measurement.temperatures,
+            statuses: measurement.statuses,
+            humidity: measurement.humidity,
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//!
+//! Circular log of fixed-size records in NOR flash
+//!
+//! Flash is split into erase blocks, each starting with a header slot that holds the sequence
+//! number of the block. Records are appended to the newest block; when it is full, the oldest
+//! block is erased and becomes the newest one. Every slot carries CRC-32, so records torn by
+//! power loss are skipped when read.
+//!
+//! With a single erase block the whole log is erased when it is full.
+//!
+
+use embedded_storage::nor_flash::NorFlash;
+
+use super::Error;
+use crate::config::store::crc32;
+
+pub const SLOT_SIZE: usize = 16;
+
+/// Record fits slot together with CRC
+pub const RECORD_SIZE: usize = SLOT_SIZE - CRC_SIZE;
+
+/// Marks header of block
+const MAGIC: u32 = 0x474f_4c44;
+
+const CRC_SIZE: usize = 4;
+
+/// Where next record is appended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Head {
+    block: u32,
+    slot: u32,
+    sequence: u32,
+}
+
+/// Position of reader, from oldest block to newest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    age: u32,
+    slot: u32,
+}
+
+pub struct Ring<F> {
+    flash: F,
+    blocks: u32,
+    /// `None` until mounted or if nothing was written yet
+    head: Option<Head>,
+}
+
+impl<F: NorFlash> Ring<F> {
+    const BLOCK_SIZE: u32 = F::ERASE_SIZE as u32;
+    const SLOTS_PER_BLOCK: u32 = Self::BLOCK_SIZE / SLOT_SIZE as u32;
+
+    /// Uses whole flash
+    pub fn new(flash: F) -> Self {
+        const {
+            assert!(
+                SLOT_SIZE.is_multiple_of(F::WRITE_SIZE) && F::ERASE_SIZE.is_multiple_of(SLOT_SIZE)
+            );
+        }
+        let blocks = (flash.capacity() / F::ERASE_SIZE) as u32;
+        Self {
+            flash,
+            blocks,
+            head: None,
+        }
+    }
+
+    /// Finds newest block and end of log in it
+    pub fn mount(&mut self) -> Result<(), Error> {
+        self.head = None;
+        for block in 0..self.blocks {
+            let Some(sequence) = self.block_sequence(block)? else {
+                continue;
+            };
+            if self.head.is_some_and(|head| head.sequence >= sequence) {
+                continue;
+            }
+            self.head = Some(Head {
+                block,
+                slot: 0,
+                sequence,
+            });
+        }
+
+        if let Some(mut head) = self.head {
+            // Torn slots are not written again before erase
+            for slot in 1..Self::SLOTS_PER_BLOCK {
+                if !self
+                    .read_slot(head.block, slot)?
+                    .iter()
+                    .all(|&byte| byte == 0xff)
+                {
+                    head.slot = slot;
+                }
+            }
+            head.slot += 1;
+            self.head = Some(head);
+        }
+        Ok(())
+    }
+
+    /// Most records kept before oldest ones are erased
+    pub fn capacity(&self) -> u32 {
+        self.blocks * (Self::SLOTS_PER_BLOCK - 1)
+    }
+
+    pub fn append(&mut self, record: &[u8; RECORD_SIZE]) -> Result<(), Error> {
+        let mut head = match self.head {
+            Some(head) if head.slot < Self::SLOTS_PER_BLOCK => head,
+            Some(head) => self.start_block((head.block + 1) % self.blocks, head.sequence + 1)?,
+            None => self.start_block(0, 0)?,
+        };
+
+        let mut slot = [0; SLOT_SIZE];
+        slot[..RECORD_SIZE].copy_from_slice(record);
+        slot[RECORD_SIZE..].copy_from_slice(&crc32(record).to_le_bytes());
+
+        let offset = self.slot_offset(head.block, head.slot);
+        head.slot += 1;
+        self.head = Some(head);
+        self.flash.write(offset, &slot).map_err(|_| Error::Flash)
+    }
+
+    /// Reads next valid record at or after position, oldest first
+    pub fn read(&mut self, position: &mut Position) -> Result<Option<[u8; RECORD_SIZE]>, Error> {
+        let Some(head) = self.head else {
+            return Ok(None);
+        };
+
+        while position.age < self.blocks {
+            // Newest block is the last one
+            let block = (head.block + 1 + position.age) % self.blocks;
+            let end = if block == head.block {
+                head.slot
+            } else {
+                Self::SLOTS_PER_BLOCK
+            };
+            if position.slot == 0 {
+                position.slot = 1;
+                // Blocks never written are skipped
+                if self.block_sequence(block)?.is_none() {
+                    position.slot = end;
+                }
+            }
+
+            while position.slot < end {
+                let slot = self.read_slot(block, position.slot)?;
+                position.slot += 1;
+                if let Some(record) = Self::parse(&slot) {
+                    return Ok(Some(record));
+                }
+            }
+            position.age += 1;
+            position.slot = 0;
+        }
+        Ok(None)
+    }
+
+    /// Erases all records
+    pub fn clear(&mut self) -> Result<(), Error> {
+        for block in 0..self.blocks {
+            self.erase_block(block)?;
+        }
+        self.head = None;
+        Ok(())
+    }
+
+    fn start_block(&mut self, block: u32, sequence: u32) -> Result<Head, Error> {
+        self.erase_block(block)?;
+
+        let mut header = [0xff; RECORD_SIZE];
+        header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&sequence.to_le_bytes());
+        let mut slot = [0; SLOT_SIZE];
+        slot[..RECORD_SIZE].copy_from_slice(&header);
+        slot[RECORD_SIZE..].copy_from_slice(&crc32(&header).to_le_bytes());
+        self.flash
+            .write(self.slot_offset(block, 0), &slot)
+            .map_err(|_| Error::Flash)?;
+
+        Ok(Head {
+            block,
+            slot: 1,
+            sequence,
+        })
+    }
+
+    /// Sequence number of block with valid header
+    fn block_sequence(&mut self, block: u32) -> Result<Option<u32>, Error> {
+        let Some(header) = Self::parse(&self.read_slot(block, 0)?) else {
+            return Ok(None);
+        };
+        if header[0..4] != MAGIC.to_le_bytes() {
+            return Ok(None);
+        }
+        Ok(Some(u32::from_le_bytes([
+            header[4], header[5], header[6], header[7],
+        ])))
+    }
+
+    fn parse(slot: &[u8; SLOT_SIZE]) -> Option<[u8; RECORD_SIZE]> {
+        let (record, crc) = slot.split_at(RECORD_SIZE);
+        let record: [u8; RECORD_SIZE] = record.try_into().ok()?;
+        (crc == crc32(&record).to_le_bytes()).then_some(record)
+    }
+
+    fn read_slot(&mut self, block: u32, slot: u32) -> Result<[u8; SLOT_SIZE], Error> {
+        let mut bytes = [0; SLOT_SIZE];
+        self.flash
+            .read(self.slot_offset(block, slot), &mut bytes)
+            .map_err(|_| Error::Flash)?;
+        Ok(bytes)
+    }
+
+    fn erase_block(&mut self, block: u32) -> Result<(), Error> {
+        let from = block * Self::BLOCK_SIZE;
+        self.flash
+            .erase(from, from + Self::BLOCK_SIZE)
+            .map_err(|_| Error::Flash)
+    }
+
+    fn slot_offset(&self, block: u32, slot: u32) -> u32 {
+        block * Self::BLOCK_SIZE + slot * SLOT_SIZE as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::RamFlash;
+
+    /// Two blocks of header and three records
+    type Flash = RamFlash<{ 2 * 4 * SLOT_SIZE }, { 4 * SLOT_SIZE }>;
+
+    fn mounted(flash: &mut Flash) -> Ring<&mut Flash> {
+        let mut ring = Ring::new(flash);
+        ring.mount().unwrap();
+        ring
+    }
+
+    fn append(ring: &mut Ring<&mut Flash>, records: core::ops::Range<u8>) {
+        for n in records {
+            ring.append(&[n; RECORD_SIZE]).unwrap();
+        }
+    }
+
+    /// First byte of every record, oldest first
+    fn records(ring: &mut Ring<&mut Flash>) -> Vec<u8> {
+        let mut position = Position::default();
+        core::iter::from_fn(|| ring.read(&mut position).unwrap())
+            .map(|record| record[0])
+            .collect()
+    }
+
+    #[test]
+    fn reads_records_in_order_of_appending() {
+        let mut flash = Flash::new();
+        let mut ring = mounted(&mut flash);
+        assert_eq!(ring.capacity(), 6);
+        assert_eq!(records(&mut ring), []);
+
+        append(&mut ring, 0..5);
+
+        assert_eq!(records(&mut ring), [0, 1, 2, 3, 4]);
+        assert_eq!(records(&mut mounted(&mut flash)), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn wraps_erasing_oldest_block() {
+        let mut flash = Flash::new();
+        let mut ring = mounted(&mut flash);
+
+        append(&mut ring, 0..10);
+        assert_eq!(records(&mut ring), [6, 7, 8, 9]);
+
+        // Newest block is found by sequence, not by position
+        let mut ring = mounted(&mut flash);
+        assert_eq!(records(&mut ring), [6, 7, 8, 9]);
+        append(&mut ring, 10..13);
+        assert_eq!(records(&mut ring), [9, 10, 11, 12]);
+    }
+
+    #[test]
+    fn skips_torn_record_after_mount() {
+        let mut flash = Flash::new();
+        append(&mut mounted(&mut flash), 0..2);
+
+        flash.power_budget = Some(4);
+        assert_eq!(
+            mounted(&mut flash).append(&[2; RECORD_SIZE]),
+            Err(Error::Flash)
+        );
+        flash.power_budget = None;
+
+        let mut ring = mounted(&mut flash);
+        assert_eq!(records(&mut ring), [0, 1]);
+        // Torn slot is not written again
+        append(&mut ring, 3..5);
+        assert_eq!(records(&mut mounted(&mut flash)), [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn clear_erases_all_records() {
+        let mut flash = Flash::new();
+        let mut ring = mounted(&mut flash);
+        append(&mut ring, 0..5);
+
+        ring.clear().unwrap();
+        assert_eq!(records(&mut ring), []);
+        append(&mut ring, 5..6);
+
+        assert_eq!(records(&mut mounted(&mut flash)), [5]);
+    }
+}
@@ -0,0 +1,56 @@
+//!
+//! Task appending measurements to log
+//!
+
+use embassy_time::{Duration, Instant};
+
+use super::{Entry, SharedLog};
+use crate::measurement::MeasurementChannel;
+
+/// Appends measurements to log
+pub struct Runner<'a> {
+    measurements: &'a MeasurementChannel,
+    log: &'a SharedLog,
+    interval: Duration,
+}
+
+impl Runner<'_> {
+    pub async fn run(self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+
+        {
+            let mut log = self.log.lock().await;
+            match log.mount() {
+                Ok(()) => defmt::info!("datalog: mounted, {=u32} records fit", log.capacity()),
+                Err(err) => defmt::error!("datalog: not mounted: {}", err),
+            }
+        }
+
+        let mut log_time = Instant::now();
+        loop {
+            let measurement = subscriber.next_message_pure().await;
+            if Instant::now() < log_time {
+                continue;
+            }
+            log_time = Instant::now() + self.interval;
+
+            let record = Entry::from(&measurement).encode();
+            if let Err(err) = self.log.lock().await.append(&record) {
+                defmt::warn!("datalog: not appended: {}", err);
+            }
+        }
+    }
+}
+
+/// Creates logger appending one measurement per interval
+pub fn new<'a>(
+    measurements: &'a MeasurementChannel,
+    log: &'a SharedLog,
+    interval: Duration,
+) -> Runner<'a> {
+    Runner {
+        measurements,
+        log,
+        interval,
+    }
+}
@@ -0,0 +1,118 @@
+//!
+//! Display controllers screens can be drawn on
+//!
+//! Screens are laid out for 128x64 monochrome panel, colour displays show the same layout
+//! scaled and painted with a [Theme](st77xx::Theme).
+//!
+//! Screens are drawn into a [FrameBuffer] and backends send only the part of it changed since
+//! previous update, so the shared bus is not held for a whole frame on every measurement.
+//!
+
+use core::future::Future;
+
+use display_interface::DisplayError;
+
+use super::{framebuffer::FrameBuffer, power::Brightness};
+use crate::bsp::{DisplayPins, I2cShared, SpiShared};
+
+pub mod sh1106;
+pub mod ssd1306;
+pub mod st77xx;
+
+pub trait DisplayBackend {
+    /// Resets and configures controller and clears panel, must be called before updates
+    fn init(&mut self) -> impl Future<Output = Result<(), DisplayError>>;
+
+    /// Sends part of frame changed since previous update, returns number of bytes sent
+    fn update(&mut self, frame: &FrameBuffer) -> Result<usize, DisplayError>;
+
+    /// Turns panel on or off, drawn frame is kept while panel is off
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError>;
+
+    fn set_brightness(&mut self, brightness: Brightness) -> Result<(), DisplayError>;
+
+    /// Shows alarm state, monochrome panels have nothing to show it with
+    fn set_alarm(&mut self, alarm: bool) -> Result<(), DisplayError> {
+        let _ = alarm;
+        Ok(())
+    }
+}
+
+/// Controller of connected display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Kind {
+    /// 0.96" OLED on i2c bus
+    Ssd1306,
+    /// 1.3" OLED on i2c bus
+    Sh1106,
+    /// 1.8" 160x128 colour TFT on spi bus
+    St7735,
+    /// 2" 320x240 colour TFT on spi bus
+    St7789,
+}
+
+/// Any of supported displays, so display task does not depend on controller
+// Only one instance lives in display task, so size difference costs nothing
+#[allow(clippy::large_enum_variant)]
+pub enum Backend {
+    Ssd1306(ssd1306::Ssd1306Backend),
+    Sh1106(sh1106::Sh1106<ssd1306::I2cInterface>),
+    St77xx(st77xx::St77xx<st77xx::SpiInterface<SpiShared>>),
+}
+
+impl Backend {
+    /// Creates driver of given display, unused bus and pins are dropped
+    pub fn new(kind: Kind, i2c: I2cShared, spi: SpiShared, pins: DisplayPins) -> Self {
+        let DisplayPins { dc, reset } = pins;
+        let spi = st77xx::SpiInterface::new(spi, dc);
+
+        match kind {
+            Kind::Ssd1306 => Backend::Ssd1306(ssd1306::Ssd1306Backend::new(i2c)),
+            Kind::Sh1106 => Backend::Sh1106(sh1106::Sh1106::new(ssd1306::interface(i2c))),
+            Kind::St7735 => Backend::St77xx(st77xx::St77xx::new(spi, reset, st77xx::Model::St7735)),
+            Kind::St7789 => Backend::St77xx(st77xx::St77xx::new(spi, reset, st77xx::Model::St7789)),
+        }
+    }
+}
+
+impl DisplayBackend for Backend {
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        match self {
+            Backend::Ssd1306(display) => display.init().await,
+            Backend::Sh1106(display) => display.init().await,
+            Backend::St77xx(display) => display.init().await,
+        }
+    }
+
+    fn update(&mut self, frame: &FrameBuffer) -> Result<usize, DisplayError> {
+        match self {
+            Backend::Ssd1306(display) => display.update(frame),
+            Backend::Sh1106(display) => display.update(frame),
+            Backend::St77xx(display) => display.update(frame),
+        }
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        match self {
+            Backend::Ssd1306(display) => display.set_display_on(on),
+            Backend::Sh1106(display) => display.set_display_on(on),
+            Backend::St77xx(display) => display.set_display_on(on),
+        }
+    }
+
+    fn set_brightness(&mut self, brightness: Brightness) -> Result<(), DisplayError> {
+        match self {
+            Backend::Ssd1306(display) => display.set_brightness(brightness),
+            Backend::Sh1106(display) => display.set_brightness(brightness),
+            Backend::St77xx(display) => display.set_brightness(brightness),
+        }
+    }
+
+    fn set_alarm(&mut self, alarm: bool) -> Result<(), DisplayError> {
+        match self {
+            Backend::Ssd1306(display) => display.set_alarm(alarm),
+            Backend::Sh1106(display) => display.set_alarm(alarm),
+            Backend::St77xx(display) => display.set_alarm(alarm),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//!
+//! SH1106 132x64 OLED controller, used in 1.3" modules
+//!
+//! Commands are mostly those of SSD1306, but the controller has no horizontal addressing mode,
+//! so frame is sent page by page, and only changed columns of each page. Panel is 128 columns
+//! wide and centered in controller RAM.
+//!
+
+use core::ops::RangeInclusive;
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+use super::DisplayBackend;
+use crate::display::{framebuffer::FrameBuffer, power::Brightness};
+
+/// First RAM column shown on 128 columns wide panel
+const COLUMN_OFFSET: u8 = 2;
+const WIDTH: usize = 128;
+const PAGES: usize = 8;
+
+const DISPLAY_OFF: u8 = 0xAE;
+const DISPLAY_ON: u8 = 0xAF;
+const SET_CONTRAST: u8 = 0x81;
+const SET_PAGE: u8 = 0xB0;
+const SET_COLUMN_LOW: u8 = 0x00;
+const SET_COLUMN_HIGH: u8 = 0x10;
+
+/// Commands with parameters sent once after power up
+const INIT_COMMANDS: &[&[u8]] = &[
+    &[DISPLAY_OFF],
+    // Clock divide ratio and oscillator frequency
+    &[0xD5, 0x80],
+    // Multiplex ratio of 64 rows
+    &[0xA8, 0x3F],
+    // No display offset, start line 0
+    &[0xD3, 0x00],
+    &[0x40],
+    // Internal DC-DC converter on
+    &[0xAD, 0x8B],
+    // Column 0 on the left and row 0 on top
+    &[0xA1],
+    &[0xC8],
+    // Alternative COM pins configuration
+    &[0xDA, 0x12],
+    // Precharge period and VCOM deselect level
+    &[0xD9, 0x22],
+    &[0xDB, 0x35],
+    // Show RAM content, not inverted
+    &[0xA4],
+    &[0xA6],
+];
+
+pub struct Sh1106<DI> {
+    interface: DI,
+    shown: FrameBuffer,
+}
+
+impl<DI: WriteOnlyDataCommand> Sh1106<DI> {
+    pub fn new(interface: DI) -> Self {
+        Self {
+            interface,
+            shown: FrameBuffer::new(),
+        }
+    }
+
+    /// Byte of page column, lowest bit is top row of page
+    fn page_column(frame: &FrameBuffer, page: usize, x: usize) -> u8 {
+        (0..8).fold(0, |byte, bit| {
+            let point = Point::new(x as i32, (page * 8 + bit) as i32);
+            match frame.pixel(point) {
+                BinaryColor::On => byte | (1 << bit),
+                BinaryColor::Off => byte,
+            }
+        })
+    }
+
+    /// Sends columns of page, returns number of bytes sent
+    fn send_columns(
+        &mut self,
+        frame: &FrameBuffer,
+        page: usize,
+        columns: RangeInclusive<usize>,
+    ) -> Result<usize, DisplayError> {
+        let column = COLUMN_OFFSET + *columns.start() as u8;
+        self.interface.send_commands(DataFormat::U8(&[
+            SET_PAGE | page as u8,
+            SET_COLUMN_LOW | (column & 0x0F),
+            SET_COLUMN_HIGH | (column >> 4),
+        ]))?;
+
+        let mut data = [0; WIDTH];
+        let data = &mut data[..columns.clone().count()];
+        for (byte, x) in data.iter_mut().zip(columns) {
+            *byte = Self::page_column(frame, page, x);
+        }
+        self.interface.send_data(DataFormat::U8(data))?;
+        Ok(data.len())
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> DisplayBackend for Sh1106<DI> {
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        for command in INIT_COMMANDS {
+            self.interface.send_commands(DataFormat::U8(command))?;
+        }
+        self.set_brightness(Brightness::NORMAL)?;
+
+        // RAM content is random after power up
+        self.shown = FrameBuffer::new();
+        let blank = FrameBuffer::new();
+        for page in 0..PAGES {
+            self.send_columns(&blank, page, 0..=WIDTH - 1)?;
+        }
+
+        self.set_display_on(true)
+    }
+
+    fn update(&mut self, frame: &FrameBuffer) -> Result<usize, DisplayError> {
+        let mut sent = 0;
+        for page in 0..PAGES {
+            let rows = page * 8..(page + 1) * 8;
+            if let Some(columns) = frame.changed_columns(&self.shown, rows) {
+                sent += self.send_columns(frame, page, columns)?;
+            }
+        }
+        self.shown = frame.clone();
+        Ok(sent)
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        let command = if on { DISPLAY_ON } else { DISPLAY_OFF };
+        self.interface.send_commands(DataFormat::U8(&[command]))
+    }
+
+    fn set_brightness(&mut self, brightness: Brightness) -> Result<(), DisplayError> {
+        self.interface
+            .send_commands(DataFormat::U8(&[SET_CONTRAST, brightness.0]))
+    }
+}
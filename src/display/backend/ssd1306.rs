@@ -0,0 +1,87 @@
+//!
+//! SSD1306 128x64 OLED on i2c bus
+//!
+
+use display_interface::DisplayError;
+use embedded_graphics::prelude::*;
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, Ssd1306};
+
+use super::DisplayBackend;
+use crate::{
+    bsp::I2cShared,
+    display::{framebuffer::FrameBuffer, power::Brightness},
+};
+
+pub type I2cInterface = ssd1306::prelude::I2CInterface<I2cShared>;
+
+pub type Ssd1306Display =
+    Ssd1306<I2cInterface, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>;
+
+/// Interface of OLED controller at default 0x3C address
+pub fn interface(i2c: I2cShared) -> I2cInterface {
+    ssd1306::I2CDisplayInterface::new(i2c)
+}
+
+/// Driver keeps its own buffer and flushes only the area of pixels set since last flush
+pub struct Ssd1306Backend {
+    display: Ssd1306Display,
+    shown: FrameBuffer,
+}
+
+impl Ssd1306Backend {
+    pub fn new(i2c: I2cShared) -> Self {
+        let display = Ssd1306::new(interface(i2c), DisplaySize128x64, DisplayRotation::Rotate0)
+            .into_buffered_graphics_mode();
+
+        Self {
+            display,
+            shown: FrameBuffer::new(),
+        }
+    }
+}
+
+impl DisplayBackend for Ssd1306Backend {
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        self.display.init()?;
+        self.display.clear_buffer();
+        self.display.flush()?;
+        self.shown = FrameBuffer::new();
+        Ok(())
+    }
+
+    fn update(&mut self, frame: &FrameBuffer) -> Result<usize, DisplayError> {
+        let Some(area) = frame.changed_area(&self.shown) else {
+            return Ok(0);
+        };
+
+        for point in area.points() {
+            let on = frame.pixel(point).is_on();
+            self.display.set_pixel(point.x as u32, point.y as u32, on);
+        }
+        self.display.flush()?;
+        self.shown = frame.clone();
+
+        // Columns of area are sent for every page it touches
+        let bottom = area.top_left.y + area.size.height as i32 - 1;
+        let pages = (bottom / 8 - area.top_left.y / 8 + 1) as usize;
+        Ok(area.size.width as usize * pages)
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.display.set_display_on(on)
+    }
+
+    fn set_brightness(&mut self, brightness: Brightness) -> Result<(), DisplayError> {
+        // Lowest precharge makes the dimmest level darker than contrast alone
+        let precharge = if brightness == Brightness::DIMMEST {
+            0x1
+        } else {
+            0x2
+        };
+        self.display
+            .set_brightness(ssd1306::prelude::Brightness::custom(
+                precharge,
+                brightness.0,
+            ))
+    }
+}
@@ -0,0 +1,309 @@
+//!
+//! ST7735 and ST7789 colour TFT controllers on spi bus
+//!
+//! Monochrome layout is scaled by whole pixels and centered on the panel. Lit pixels are drawn
+//! with foreground colour of current [Theme] and the rest with background colour, the theme is
+//! switched when alarm is shown. Only the scaled bounding box of changed pixels is sent.
+//!
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embassy_time::{Duration, Timer};
+use embedded_graphics::{
+    pixelcolor::{raw::ToBytes, Rgb565, RgbColor},
+    prelude::*,
+    primitives::Rectangle,
+};
+use embedded_hal::{digital::OutputPin, spi::SpiDevice};
+
+use super::DisplayBackend;
+use crate::display::{
+    framebuffer::FrameBuffer,
+    power::Brightness,
+    screens::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+};
+
+/// Layout is not scaled more, so line buffer stays small
+const MAX_SCALE: u32 = 2;
+
+/// Widest supported panel
+const MAX_PANEL_WIDTH: usize = 320;
+
+const RESET_PULSE: Duration = Duration::from_millis(10);
+/// Time after reset or sleep out before next command
+const WAKE_UP_TIME: Duration = Duration::from_millis(150);
+
+const SWRESET: u8 = 0x01;
+const SLPOUT: u8 = 0x11;
+const NORON: u8 = 0x13;
+const INVON: u8 = 0x21;
+const DISPOFF: u8 = 0x28;
+const DISPON: u8 = 0x29;
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+const MADCTL: u8 = 0x36;
+const COLMOD: u8 = 0x3A;
+
+/// Row and column exchange with mirrored columns turns panel to landscape
+const MADCTL_LANDSCAPE: u8 = 0x60;
+const MADCTL_BGR: u8 = 0x08;
+/// 16 bits per pixel
+const COLMOD_RGB565: u8 = 0x55;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Model {
+    /// 160x128 panel with BGR pixel order
+    St7735,
+    /// 320x240 panel with inverted colours
+    St7789,
+}
+
+impl Model {
+    /// Size of panel in landscape orientation
+    pub fn panel_size(&self) -> Size {
+        match self {
+            Model::St7735 => Size::new(160, 128),
+            Model::St7789 => Size::new(320, 240),
+        }
+    }
+
+    fn madctl(&self) -> u8 {
+        match self {
+            Model::St7735 => MADCTL_LANDSCAPE | MADCTL_BGR,
+            Model::St7789 => MADCTL_LANDSCAPE,
+        }
+    }
+}
+
+/// Colours of lit and dark pixels of layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub foreground: Rgb565,
+    pub background: Rgb565,
+}
+
+impl Theme {
+    pub const NORMAL: Theme = Theme {
+        foreground: Rgb565::WHITE,
+        background: Rgb565::BLACK,
+    };
+    pub const ALARM: Theme = Theme {
+        foreground: Rgb565::WHITE,
+        background: Rgb565::RED,
+    };
+}
+
+/// Data/command interface over spi device with separate data/command pin
+pub struct SpiInterface<SPI, DC = crate::bsp::DisplayDcPin> {
+    spi: SPI,
+    dc: DC,
+}
+
+impl<SPI, DC> SpiInterface<SPI, DC> {
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        Self { spi, dc }
+    }
+}
+
+impl<SPI: SpiDevice, DC: OutputPin> SpiInterface<SPI, DC> {
+    fn write(&mut self, is_data: bool, format: DataFormat<'_>) -> Result<(), DisplayError> {
+        let DataFormat::U8(bytes) = format else {
+            return Err(DisplayError::DataFormatNotImplemented);
+        };
+        self.dc
+            .set_state(is_data.into())
+            .map_err(|_| DisplayError::DCError)?;
+        self.spi
+            .write(bytes)
+            .map_err(|_| DisplayError::BusWriteError)
+    }
+}
+
+impl<SPI: SpiDevice, DC: OutputPin> WriteOnlyDataCommand for SpiInterface<SPI, DC> {
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.write(false, cmd)
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.write(true, buf)
+    }
+}
+
+pub struct St77xx<DI, RST = crate::bsp::DisplayResetPin> {
+    interface: DI,
+    reset: RST,
+    model: Model,
+    theme: Theme,
+    alarm_theme: Theme,
+    alarm: bool,
+    shown: FrameBuffer,
+}
+
+impl<DI, RST> St77xx<DI, RST>
+where
+    DI: WriteOnlyDataCommand,
+    RST: OutputPin,
+{
+    pub fn new(interface: DI, reset: RST, model: Model) -> Self {
+        Self {
+            interface,
+            reset,
+            model,
+            theme: Theme::NORMAL,
+            alarm_theme: Theme::ALARM,
+            alarm: false,
+            shown: FrameBuffer::new(),
+        }
+    }
+
+    pub fn with_themes(self, theme: Theme, alarm_theme: Theme) -> Self {
+        Self {
+            theme,
+            alarm_theme,
+            ..self
+        }
+    }
+
+    fn current_theme(&self) -> Theme {
+        if self.alarm {
+            self.alarm_theme
+        } else {
+            self.theme
+        }
+    }
+
+    fn command(&mut self, command: u8, params: &[u8]) -> Result<(), DisplayError> {
+        self.interface.send_commands(DataFormat::U8(&[command]))?;
+        if !params.is_empty() {
+            self.interface.send_data(DataFormat::U8(params))?;
+        }
+        Ok(())
+    }
+
+    /// Limits following pixel data to rectangle
+    fn set_window(&mut self, area: Rectangle) -> Result<(), DisplayError> {
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let [x0, x1, y0, y1] = [
+            area.top_left.x,
+            bottom_right.x,
+            area.top_left.y,
+            bottom_right.y,
+        ]
+        .map(|coordinate| (coordinate as u16).to_be_bytes());
+
+        self.command(CASET, &[x0[0], x0[1], x1[0], x1[1]])?;
+        self.command(RASET, &[y0[0], y0[1], y1[0], y1[1]])?;
+        self.command(RAMWR, &[])
+    }
+
+    /// Scale of layout and area it takes on panel
+    fn layout_area(&self) -> (u32, Rectangle) {
+        let panel = self.model.panel_size();
+        let scale = (panel.width / DISPLAY_WIDTH)
+            .min(panel.height / DISPLAY_HEIGHT)
+            .clamp(1, MAX_SCALE);
+        let size = Size::new(DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+        let top_left = Point::new(
+            (panel.width - size.width) as i32 / 2,
+            (panel.height - size.height) as i32 / 2,
+        );
+        (scale, Rectangle::new(top_left, size))
+    }
+
+    /// Paints whole panel with background colour, so nothing of layout is shown
+    fn fill_background(&mut self) -> Result<(), DisplayError> {
+        let panel = self.model.panel_size();
+        let color = self.current_theme().background.to_be_bytes();
+        let mut line = [0; MAX_PANEL_WIDTH * 2];
+        let line = &mut line[..panel.width as usize * 2];
+        for pixel in line.as_chunks_mut::<2>().0 {
+            *pixel = color;
+        }
+
+        self.set_window(Rectangle::new(Point::zero(), panel))?;
+        for _ in 0..panel.height {
+            self.interface.send_data(DataFormat::U8(line))?;
+        }
+        self.shown = FrameBuffer::new();
+        Ok(())
+    }
+}
+
+impl<DI, RST> DisplayBackend for St77xx<DI, RST>
+where
+    DI: WriteOnlyDataCommand,
+    RST: OutputPin,
+{
+    async fn init(&mut self) -> Result<(), DisplayError> {
+        self.reset.set_low().map_err(|_| DisplayError::RSError)?;
+        Timer::after(RESET_PULSE).await;
+        self.reset.set_high().map_err(|_| DisplayError::RSError)?;
+        Timer::after(WAKE_UP_TIME).await;
+
+        self.command(SWRESET, &[])?;
+        Timer::after(WAKE_UP_TIME).await;
+        self.command(SLPOUT, &[])?;
+        Timer::after(WAKE_UP_TIME).await;
+
+        self.command(COLMOD, &[COLMOD_RGB565])?;
+        self.command(MADCTL, &[self.model.madctl()])?;
+        if self.model == Model::St7789 {
+            self.command(INVON, &[])?;
+        }
+        self.command(NORON, &[])?;
+
+        self.fill_background()?;
+        self.set_display_on(true)
+    }
+
+    fn update(&mut self, frame: &FrameBuffer) -> Result<usize, DisplayError> {
+        let Some(changed) = frame.changed_area(&self.shown) else {
+            return Ok(0);
+        };
+
+        let (scale, layout) = self.layout_area();
+        let theme = self.current_theme();
+        let [on, off] = [theme.foreground, theme.background].map(|color| color.to_be_bytes());
+
+        let window = Rectangle::new(
+            layout.top_left + changed.top_left * scale as i32,
+            changed.size * scale,
+        );
+        self.set_window(window)?;
+
+        let mut line = [0; DISPLAY_WIDTH as usize * MAX_SCALE as usize * 2];
+        let line = &mut line[..window.size.width as usize * 2];
+        for y in changed.rows() {
+            for (x, pixel) in line.as_chunks_mut::<2>().0.iter_mut().enumerate() {
+                let point = Point::new(changed.top_left.x + x as i32 / scale as i32, y);
+                *pixel = if frame.pixel(point).is_on() { on } else { off };
+            }
+            for _ in 0..scale {
+                self.interface.send_data(DataFormat::U8(line))?;
+            }
+        }
+        self.shown = frame.clone();
+
+        Ok(line.len() * window.size.height as usize)
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.command(if on { DISPON } else { DISPOFF }, &[])
+    }
+
+    /// Backlight is wired to supply on common modules, so brightness cannot be changed
+    fn set_brightness(&mut self, _brightness: Brightness) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    fn set_alarm(&mut self, alarm: bool) -> Result<(), DisplayError> {
+        if self.alarm == alarm {
+            return Ok(());
+        }
+        self.alarm = alarm;
+        // Border around layout changes colour too, layout is sent again on next update
+        self.fill_background()
+    }
+}
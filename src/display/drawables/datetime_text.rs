@@ -0,0 +1,89 @@
+use embedded_graphics::{
+    prelude::*,
+    text::{renderer::TextRenderer, Text},
+    Drawable,
+};
+use heapless::String;
+use ufmt::uwrite;
+
+use ds323x::{Datelike, NaiveDateTime, Timelike};
+
+const DATETIME_STRING_SIZE: usize = 19; // YYYY-MM-DD hh:mm:ss
+
+/// Shown when time is unknown
+const PLACEHOLDER: &str = "---------- --:--:--";
+
+/// Date and time of day
+pub struct DateTimeText<Style> {
+    datetime_string: String<DATETIME_STRING_SIZE>,
+    style: Style,
+    position: Point,
+}
+
+impl<Style> DateTimeText<Style> {
+    pub fn new(position: Point, style: Style) -> Self {
+        Self {
+            datetime_string: String::new(),
+            style,
+            position,
+        }
+    }
+
+    pub fn with_datetime(self, datetime: Option<NaiveDateTime>) -> Self {
+        let mut datetime_string = String::new();
+        if let Some(datetime) = datetime {
+            Self::write_datetime(&datetime, &mut datetime_string);
+        }
+
+        Self {
+            datetime_string,
+            ..self
+        }
+    }
+
+    fn write_datetime<const N: usize>(datetime: &NaiveDateTime, str: &mut String<N>) {
+        str.clear();
+
+        uwrite!(str, "{}-", datetime.year()).ok();
+        Self::write_two_digits(datetime.month(), str);
+        str.push('-').ok();
+        Self::write_two_digits(datetime.day(), str);
+        str.push(' ').ok();
+        Self::write_two_digits(datetime.hour(), str);
+        str.push(':').ok();
+        Self::write_two_digits(datetime.minute(), str);
+        str.push(':').ok();
+        Self::write_two_digits(datetime.second(), str);
+    }
+
+    fn write_two_digits<const N: usize>(value: u32, str: &mut String<N>) {
+        if value < 10 {
+            str.push('0').ok();
+        }
+        uwrite!(str, "{}", value).ok();
+    }
+}
+
+impl<Color, Style> Drawable for DateTimeText<Style>
+where
+    Color: PixelColor,
+    Style: TextRenderer<Color = Color> + Clone,
+{
+    type Color = Color;
+    /// Position right after the text
+    type Output = Point;
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let string = if self.datetime_string.is_empty() {
+            PLACEHOLDER
+        } else {
+            &self.datetime_string
+        };
+
+        let text = Text::new(string, self.position, self.style.clone());
+        text.draw(target)
+    }
+}
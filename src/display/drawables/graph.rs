@@ -0,0 +1,217 @@
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+    Drawable,
+};
+use heapless::{HistoryBuffer, String};
+#[cfg_attr(not(target_os = "none"), allow(unused_imports))]
+use num_traits::float::FloatCore;
+use ufmt::uwrite;
+
+const LABEL_STRING_SIZE: usize = 6; // -\d{1,3}\.\d
+
+/// Width of labels column in characters
+const LABEL_CHARACTERS: u32 = 5;
+
+/// Length of time axis ticks in pixels
+const TICK_LENGTH: u32 = 2;
+
+/// Smallest range of Y axis, so noise of constant value is not magnified
+const MIN_RANGE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphStyle {
+    Line,
+    Bar,
+}
+
+/// Chart of samples with auto-scaled Y axis, newest sample is at right edge
+pub struct Graph<'a, 'font, Color, const N: usize> {
+    samples: &'a HistoryBuffer<f32, N>,
+    area: Rectangle,
+    graph_style: GraphStyle,
+    label_style: MonoTextStyle<'font, Color>,
+    color: Color,
+    /// Distance between time axis ticks in samples, no ticks if zero
+    tick_every: usize,
+    /// Applied to samples before drawing, e.g. to convert units
+    transform: fn(f32) -> f32,
+}
+
+impl<'a, 'font, Color, const N: usize> Graph<'a, 'font, Color, N>
+where
+    Color: PixelColor,
+{
+    pub fn new(
+        samples: &'a HistoryBuffer<f32, N>,
+        area: Rectangle,
+        label_style: MonoTextStyle<'font, Color>,
+        color: Color,
+    ) -> Self {
+        Self {
+            samples,
+            area,
+            graph_style: GraphStyle::Line,
+            label_style,
+            color,
+            tick_every: 0,
+            transform: |value| value,
+        }
+    }
+
+    pub fn with_graph_style(self, graph_style: GraphStyle) -> Self {
+        Self {
+            graph_style,
+            ..self
+        }
+    }
+
+    pub fn with_ticks(self, tick_every: usize) -> Self {
+        Self { tick_every, ..self }
+    }
+
+    pub fn with_transform(self, transform: fn(f32) -> f32) -> Self {
+        Self { transform, ..self }
+    }
+
+    /// Range of Y axis covering all samples
+    fn range(&self) -> (f32, f32) {
+        let (min, max) = self
+            .samples
+            .iter()
+            .map(|&value| (self.transform)(value))
+            .fold((f32::MAX, f32::MIN), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+
+        let missing = MIN_RANGE - (max - min);
+        if missing > 0.0 {
+            (min - missing / 2.0, max + missing / 2.0)
+        } else {
+            (min, max)
+        }
+    }
+
+    fn write_label<const S: usize>(value: f32, str: &mut String<S>) {
+        let rounded = (value * 10.0).round() as i32;
+
+        str.clear();
+        if rounded < 0 {
+            str.push('-').ok();
+        }
+        let rounded = rounded.unsigned_abs();
+        uwrite!(str, "{}.{}", rounded / 10, rounded % 10).ok();
+    }
+}
+
+impl<Color, const N: usize> Drawable for Graph<'_, '_, Color, N>
+where
+    Color: PixelColor,
+{
+    type Color = Color;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let stroke = PrimitiveStyle::with_stroke(self.color, 1);
+        let label_width = LABEL_CHARACTERS * self.label_style.font.character_size.width + 1;
+
+        let plot = Rectangle::new(
+            self.area.top_left + Point::new(label_width as i32, 0),
+            Size::new(
+                self.area.size.width.saturating_sub(label_width),
+                self.area.size.height.saturating_sub(TICK_LENGTH),
+            ),
+        );
+        let Some(bottom_right) = plot.bottom_right() else {
+            return Ok(());
+        };
+
+        // Axes
+        Line::new(plot.top_left, Point::new(plot.top_left.x, bottom_right.y))
+            .into_styled(stroke)
+            .draw(target)?;
+        Line::new(Point::new(plot.top_left.x, bottom_right.y), bottom_right)
+            .into_styled(stroke)
+            .draw(target)?;
+
+        // Samples are aligned to the right, so position of slot does not change when buffer fills
+        let slot_x = |slot: usize| {
+            let width = plot.size.width as i32 - 2;
+            plot.top_left.x + 1 + (slot as i32 * width) / (N as i32 - 1).max(1)
+        };
+        let first_slot = N - self.samples.len();
+
+        if self.tick_every > 0 {
+            // Ticks are counted from newest sample, so they stay on the same samples when
+            // buffer shifts
+            for slot in (0..N).rev().step_by(self.tick_every) {
+                let x = slot_x(slot);
+                Line::new(
+                    Point::new(x, bottom_right.y + 1),
+                    Point::new(x, bottom_right.y + TICK_LENGTH as i32),
+                )
+                .into_styled(stroke)
+                .draw(target)?;
+            }
+        }
+
+        if self.samples.is_empty() {
+            return Ok(());
+        }
+
+        let (min, max) = self.range();
+
+        let mut label: String<LABEL_STRING_SIZE> = String::new();
+        Self::write_label(max, &mut label);
+        Text::with_baseline(&label, self.area.top_left, self.label_style, Baseline::Top)
+            .draw(target)?;
+        Self::write_label(min, &mut label);
+        Text::with_baseline(
+            &label,
+            Point::new(self.area.top_left.x, bottom_right.y),
+            self.label_style,
+            Baseline::Bottom,
+        )
+        .draw(target)?;
+
+        let height = plot.size.height as f32 - 2.0;
+        let value_y = |value: f32| {
+            let fraction = (value - min) / (max - min);
+            bottom_right.y - 1 - (fraction * height).round() as i32
+        };
+
+        let points = self
+            .samples
+            .oldest_ordered()
+            .enumerate()
+            .map(|(n, &value)| {
+                let value = (self.transform)(value);
+                Point::new(slot_x(first_slot + n), value_y(value))
+            });
+
+        match self.graph_style {
+            GraphStyle::Line => {
+                let mut previous: Option<Point> = None;
+                for point in points {
+                    let start = previous.unwrap_or(point);
+                    Line::new(start, point).into_styled(stroke).draw(target)?;
+                    previous = Some(point);
+                }
+            }
+            GraphStyle::Bar => {
+                for point in points {
+                    Line::new(point, Point::new(point.x, bottom_right.y - 1))
+                        .into_styled(stroke)
+                        .draw(target)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
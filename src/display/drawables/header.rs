@@ -0,0 +1,84 @@
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle},
+    text::{Alignment, Text},
+    Drawable,
+};
+use heapless::String;
+use ufmt::uwrite;
+
+const PAGE_NUMBER_STRING_SIZE: usize = 5; // \d{1,2}/\d{1,2}
+
+/// Distance from baseline of text to the line below it
+const LINE_OFFSET: i32 = 2;
+
+/// Page title with page number at right and line below
+pub struct Header<'a, 'font, Color> {
+    title: &'a str,
+    page_number_string: String<PAGE_NUMBER_STRING_SIZE>,
+    style: MonoTextStyle<'font, Color>,
+    /// Baseline of text
+    y: i32,
+    width: u32,
+}
+
+impl<'a, 'font, Color> Header<'a, 'font, Color> {
+    pub fn new(y: i32, width: u32, style: MonoTextStyle<'font, Color>) -> Self {
+        Self {
+            title: "",
+            page_number_string: String::new(),
+            style,
+            y,
+            width,
+        }
+    }
+
+    pub fn with_title(self, title: &'a str) -> Self {
+        Self { title, ..self }
+    }
+
+    /// Sets page number, starting from one
+    pub fn with_page_number(self, number: usize, count: usize) -> Self {
+        let mut page_number_string = String::new();
+        uwrite!(page_number_string, "{}/{}", number, count).ok();
+
+        Self {
+            page_number_string,
+            ..self
+        }
+    }
+}
+
+impl<Color> Drawable for Header<'_, '_, Color>
+where
+    Color: PixelColor,
+{
+    type Color = Color;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let right = self.width as i32 - 1;
+
+        Text::new(self.title, Point::new(0, self.y), self.style).draw(target)?;
+        Text::with_alignment(
+            &self.page_number_string,
+            Point::new(right, self.y),
+            self.style,
+            Alignment::Right,
+        )
+        .draw(target)?;
+
+        if let Some(color) = self.style.text_color {
+            let y = self.y + LINE_OFFSET;
+            Line::new(Point::new(0, y), Point::new(right, y))
+                .into_styled(PrimitiveStyle::with_stroke(color, 1))
+                .draw(target)?;
+        }
+
+        Ok(())
+    }
+}
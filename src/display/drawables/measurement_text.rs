@@ -0,0 +1,314 @@
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle},
+    text::{renderer::TextRenderer, Alignment, Baseline, Text},
+    Drawable,
+};
+use heapless::String;
+#[cfg_attr(not(target_os = "none"), allow(unused_imports))]
+use num_traits::float::FloatCore;
+use ufmt::uwrite;
+
+use crate::units::TemperatureUnit;
+
+/// Maximum number of decimals, more would not fit into `u32` fraction
+pub const MAX_DECIMALS: u8 = 6;
+
+const VALUE_STRING_SIZE: usize = 12; // -?\d{1,4}\.\d{0,6}
+
+/// Unit printed after value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Unit {
+    None,
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    RelativeHumidity,
+}
+
+impl Unit {
+    /// Suffix drawn with font, degree sign is drawn separately
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Unit::None => "",
+            Unit::Celsius => "C",
+            Unit::Fahrenheit => "F",
+            Unit::Kelvin => "K",
+            Unit::RelativeHumidity => "%",
+        }
+    }
+
+    fn has_degree_sign(&self) -> bool {
+        matches!(self, Unit::Celsius | Unit::Fahrenheit)
+    }
+}
+
+impl From<TemperatureUnit> for Unit {
+    fn from(unit: TemperatureUnit) -> Self {
+        match unit {
+            TemperatureUnit::Celsius => Unit::Celsius,
+            TemperatureUnit::Fahrenheit => Unit::Fahrenheit,
+            TemperatureUnit::Kelvin => Unit::Kelvin,
+        }
+    }
+}
+
+/// Writes value rounded to `decimals` or placeholder like `--.-` when value is missing
+///
+/// Values rounding to zero are printed without sign, so `-0.04` with one decimal is `0.0`.
+/// Placeholder is written too when value does not fit into string.
+pub fn write_value<const N: usize>(value: Option<f32>, decimals: u8, str: &mut String<N>) {
+    str.clear();
+
+    let written = match value.filter(|value| value.is_finite()) {
+        Some(value) => write_number(value, decimals.min(MAX_DECIMALS), str),
+        None => Err(()),
+    };
+
+    if written.is_err() {
+        str.clear();
+        write_placeholder(decimals.min(MAX_DECIMALS), str).ok();
+    }
+}
+
+fn write_number<const N: usize>(value: f32, decimals: u8, str: &mut String<N>) -> Result<(), ()> {
+    let coff = 10_u32.pow(decimals as u32);
+    let scaled = (value.abs() * coff as f32).round();
+    if scaled >= u32::MAX as f32 {
+        return Err(());
+    }
+
+    // Sign is taken from rounded value, so it is kept for -0.5 and dropped for -0.0
+    let rounded = scaled as u32;
+    if value < 0.0 && rounded != 0 {
+        str.push_str("-")?;
+    }
+
+    uwrite!(str, "{}", rounded / coff)?;
+    if decimals > 0 {
+        let fract = rounded % coff;
+        str.push_str(".")?;
+        // ufmt has no padding, so leading zeros of fraction are written by hand
+        for digit in (0..decimals as u32)
+            .rev()
+            .map(|power| fract / 10_u32.pow(power) % 10)
+        {
+            uwrite!(str, "{}", digit)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_placeholder<const N: usize>(decimals: u8, str: &mut String<N>) -> Result<(), ()> {
+    str.push_str("--")?;
+    if decimals > 0 {
+        str.push_str(".")?;
+        for _ in 0..decimals {
+            str.push_str("-")?;
+        }
+    }
+    Ok(())
+}
+
+/// Measured value with unit, e.g. `-0.5°C`, `45.2%` or `--.-°C` when value is missing
+///
+/// Position is at the baseline, horizontal alignment tells which side of the text it anchors.
+pub struct MeasurementText<'font, Color> {
+    value_string: String<VALUE_STRING_SIZE>,
+    value: Option<f32>,
+    decimals: u8,
+    unit: Unit,
+    alignment: Alignment,
+    style: MonoTextStyle<'font, Color>,
+    position: Point,
+}
+
+impl<'font, Color> MeasurementText<'font, Color> {
+    pub fn new(position: Point, style: MonoTextStyle<'font, Color>) -> Self {
+        let decimals = 1;
+        let mut value_string = String::new();
+        write_value(None, decimals, &mut value_string);
+
+        Self {
+            value_string,
+            value: None,
+            decimals,
+            unit: Unit::None,
+            alignment: Alignment::Left,
+            style,
+            position,
+        }
+    }
+
+    pub fn with_value(self, value: impl Into<Option<f32>>) -> Self {
+        let value = value.into();
+        Self { value, ..self }.formatted()
+    }
+
+    pub fn with_decimals(self, decimals: u8) -> Self {
+        Self { decimals, ..self }.formatted()
+    }
+
+    pub fn with_unit(self, unit: Unit) -> Self {
+        Self { unit, ..self }
+    }
+
+    pub fn with_alignment(self, alignment: Alignment) -> Self {
+        Self { alignment, ..self }
+    }
+
+    fn formatted(mut self) -> Self {
+        write_value(self.value, self.decimals, &mut self.value_string);
+        self
+    }
+
+    fn degree_sign_diameter(&self) -> u32 {
+        (self.style.font.character_size.width * 2 / 5).max(3)
+    }
+}
+
+impl<Color> Drawable for MeasurementText<'_, Color>
+where
+    Color: PixelColor,
+{
+    type Color = Color;
+    /// Position right after the text
+    type Output = Point;
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let advance = |text: &str| {
+            self.style
+                .measure_string(text, Point::zero(), Baseline::Alphabetic)
+                .next_position
+                .x
+        };
+
+        let diameter = self.degree_sign_diameter();
+        let degree_width = match self.unit.has_degree_sign() {
+            true => diameter as i32 + 2,
+            false => 0,
+        };
+        let width = advance(&self.value_string) + degree_width + advance(self.unit.suffix());
+
+        let start = match self.alignment {
+            Alignment::Left => self.position,
+            Alignment::Center => self.position - Point::new(width / 2, 0),
+            Alignment::Right => self.position - Point::new(width, 0),
+        };
+
+        let after_value = Text::new(&self.value_string, start, self.style).draw(target)?;
+
+        if let (Some(color), true) = (self.style.text_color, self.unit.has_degree_sign()) {
+            // Fonts have no degree sign, so it is drawn near the top of digits
+            let top = after_value.y - self.style.font.baseline as i32 + 1;
+            Circle::new(Point::new(after_value.x + 1, top), diameter)
+                .into_styled(PrimitiveStyle::with_stroke(color, 1))
+                .draw(target)?;
+        }
+
+        let suffix_position = after_value + Point::new(degree_width, 0);
+        Text::new(self.unit.suffix(), suffix_position, self.style).draw(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{mono_font::ascii::FONT_6X10, pixelcolor::BinaryColor};
+
+    use super::*;
+    use crate::display::framebuffer::FrameBuffer;
+
+    fn formatted(value: Option<f32>, decimals: u8) -> String<VALUE_STRING_SIZE> {
+        let mut str = String::new();
+        write_value(value, decimals, &mut str);
+        str
+    }
+
+    #[test]
+    fn values_are_rounded_to_decimals() {
+        assert_eq!(formatted(Some(21.46), 1), "21.5");
+        assert_eq!(formatted(Some(21.44), 1), "21.4");
+        assert_eq!(formatted(Some(7.0), 2), "7.00");
+        assert_eq!(formatted(Some(45.6), 0), "46");
+        assert_eq!(formatted(Some(1234.5), 0), "1235");
+    }
+
+    #[test]
+    fn fraction_keeps_leading_zeros() {
+        assert_eq!(formatted(Some(0.05), 2), "0.05");
+        assert_eq!(formatted(Some(3.004), 3), "3.004");
+        assert_eq!(formatted(Some(-1.06), 2), "-1.06");
+    }
+
+    #[test]
+    fn sign_is_taken_from_rounded_value() {
+        assert_eq!(formatted(Some(-0.04), 1), "0.0");
+        assert_eq!(formatted(Some(-0.0), 1), "0.0");
+        assert_eq!(formatted(Some(-0.05), 1), "-0.1");
+        assert_eq!(formatted(Some(-12.5), 1), "-12.5");
+    }
+
+    #[test]
+    fn decimals_are_limited() {
+        assert_eq!(formatted(Some(1.5), 9), "1.500000");
+        assert_eq!(formatted(None, 9), "--.------");
+    }
+
+    #[test]
+    fn missing_values_are_placeholders() {
+        assert_eq!(formatted(None, 1), "--.-");
+        assert_eq!(formatted(None, 0), "--");
+        assert_eq!(formatted(Some(f32::NAN), 2), "--.--");
+        assert_eq!(formatted(Some(f32::INFINITY), 1), "--.-");
+        assert_eq!(formatted(Some(f32::NEG_INFINITY), 1), "--.-");
+    }
+
+    #[test]
+    fn values_not_fitting_are_placeholders() {
+        // Scaled value exceeds u32
+        assert_eq!(formatted(Some(5_000.0), 6), "--.------");
+        // Formatted value exceeds string
+        let mut short: String<4> = String::new();
+        write_value(Some(-123.4), 1, &mut short);
+        assert_eq!(short, "--.-");
+    }
+
+    #[test]
+    fn alignment_anchors_text() {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let position = Point::new(64, 20);
+        // `-1.5%` is 5 characters
+        let width = 5 * 6;
+        let draw = |alignment| {
+            MeasurementText::new(position, style)
+                .with_value(-1.5)
+                .with_unit(Unit::RelativeHumidity)
+                .with_alignment(alignment)
+                .draw(&mut FrameBuffer::new())
+                .unwrap()
+        };
+
+        assert_eq!(draw(Alignment::Left), position + Point::new(width, 0));
+        assert_eq!(draw(Alignment::Center), position + Point::new(width / 2, 0));
+        assert_eq!(draw(Alignment::Right), position);
+    }
+
+    #[test]
+    fn degree_sign_is_drawn_between_value_and_unit() {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let text = MeasurementText::new(Point::new(0, 10), style)
+            .with_value(20.0)
+            .with_unit(Unit::Celsius);
+        let frame = FrameBuffer::render(&text);
+
+        // `20.0` takes 24 pixels, circle of 3 pixels follows after 1 pixel gap
+        let degree_sign = Point::new(25, 10 - FONT_6X10.baseline as i32 + 1);
+        assert!(frame.pixel(degree_sign + Point::new(1, 0)).is_on());
+        assert!(frame.pixel(degree_sign + Point::new(1, 1)).is_off());
+    }
+}
@@ -1,2 +1,10 @@
-pub mod temperature_text;
-pub use temperature_text::TemperatureText;
+pub mod datetime_text;
+pub mod graph;
+pub mod header;
+pub mod measurement_text;
+pub mod status_icon;
+pub use datetime_text::DateTimeText;
+pub use graph::{Graph, GraphStyle};
+pub use header::Header;
+pub use measurement_text::{MeasurementText, Unit};
+pub use status_icon::StatusIcon;
@@ -0,0 +1,68 @@
+use embedded_graphics::{
+    prelude::*,
+    primitives::{Circle, Line, PrimitiveStyle},
+    Drawable,
+};
+
+use crate::drivers::sensors::status::SensorStatus;
+
+/// Width and height of icon in pixels
+pub const ICON_SIZE: u32 = 7;
+
+/// Icon of sensor state: filled circle when ok, cross on error and empty circle when unknown
+pub struct StatusIcon<Color> {
+    status: SensorStatus,
+    color: Color,
+    /// Top-left corner
+    position: Point,
+}
+
+impl<Color> StatusIcon<Color> {
+    pub fn new(position: Point, color: Color) -> Self {
+        Self {
+            status: SensorStatus::Unknown,
+            color,
+            position,
+        }
+    }
+
+    pub fn with_status(self, status: SensorStatus) -> Self {
+        Self { status, ..self }
+    }
+}
+
+impl<Color> Drawable for StatusIcon<Color>
+where
+    Color: PixelColor,
+{
+    type Color = Color;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let circle = Circle::new(self.position, ICON_SIZE);
+        match self.status {
+            SensorStatus::Ok => circle
+                .into_styled(PrimitiveStyle::with_fill(self.color))
+                .draw(target),
+            SensorStatus::Unknown => circle
+                .into_styled(PrimitiveStyle::with_stroke(self.color, 1))
+                .draw(target),
+            SensorStatus::Error => {
+                let last = ICON_SIZE as i32 - 1;
+                let style = PrimitiveStyle::with_stroke(self.color, 1);
+                Line::new(self.position, self.position + Point::new(last, last))
+                    .into_styled(style)
+                    .draw(target)?;
+                Line::new(
+                    self.position + Point::new(0, last),
+                    self.position + Point::new(last, 0),
+                )
+                .into_styled(style)
+                .draw(target)
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//!
+//! In-memory monochrome frame buffer
+//!
+//! Screens are drawn into it without display hardware, so layouts can be rendered on host and
+//! compared with stored images in plain PBM or ASCII form.
+//!
+
+use core::{
+    convert::Infallible,
+    ops::{Range, RangeInclusive},
+};
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle};
+use ufmt::{uWrite, uwrite};
+
+use super::screens::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+const WIDTH: usize = DISPLAY_WIDTH as usize;
+const HEIGHT: usize = DISPLAY_HEIGHT as usize;
+
+/// Character of lit pixel in ASCII image
+pub const ASCII_ON: char = '#';
+/// Character of dark pixel in ASCII image
+pub const ASCII_OFF: char = '.';
+
+/// Frame of display size, one bit per pixel
+#[derive(Clone, PartialEq, Eq)]
+pub struct FrameBuffer {
+    rows: [[u8; WIDTH / 8]; HEIGHT],
+}
+
+impl FrameBuffer {
+    pub const fn new() -> Self {
+        Self {
+            rows: [[0; WIDTH / 8]; HEIGHT],
+        }
+    }
+
+    /// Renders drawable into empty frame
+    pub fn render(drawable: &impl Drawable<Color = BinaryColor>) -> Self {
+        let mut frame = Self::new();
+        // Drawing into memory cannot fail
+        drawable.draw(&mut frame).ok();
+        frame
+    }
+
+    /// Color of pixel, pixels outside of frame are off
+    pub fn pixel(&self, point: Point) -> BinaryColor {
+        let Some((x, y)) = Self::index(point) else {
+            return BinaryColor::Off;
+        };
+        BinaryColor::from(self.rows[y][x / 8] & Self::mask(x) != 0)
+    }
+
+    /// Smallest rectangle containing all pixels that differ from other frame
+    pub fn changed_area(&self, other: &Self) -> Option<Rectangle> {
+        let first_row = (0..HEIGHT).find(|y| self.rows[*y] != other.rows[*y])?;
+        let last_row = (0..HEIGHT).rfind(|y| self.rows[*y] != other.rows[*y])?;
+        let columns = self.changed_columns(other, first_row..last_row + 1)?;
+
+        Some(Rectangle::with_corners(
+            Point::new(*columns.start() as i32, first_row as i32),
+            Point::new(*columns.end() as i32, last_row as i32),
+        ))
+    }
+
+    /// First and last columns where given rows differ from other frame
+    pub fn changed_columns(
+        &self,
+        other: &Self,
+        rows: Range<usize>,
+    ) -> Option<RangeInclusive<usize>> {
+        let rows = rows.start.min(HEIGHT)..rows.end.min(HEIGHT);
+
+        let mut diff = [0; WIDTH / 8];
+        for y in rows {
+            let changed = self.rows[y].iter().zip(&other.rows[y]);
+            for (diff, (a, b)) in diff.iter_mut().zip(changed) {
+                *diff |= a ^ b;
+            }
+        }
+
+        let first = diff.iter().position(|byte| *byte != 0)?;
+        let last = diff.iter().rposition(|byte| *byte != 0)?;
+        // Leftmost pixel is the highest bit
+        let start = first * 8 + diff[first].leading_zeros() as usize;
+        let end = last * 8 + 7 - diff[last].trailing_zeros() as usize;
+        Some(start..=end)
+    }
+
+    /// Writes plain PBM (P1) image, lit pixels are black
+    pub fn write_pbm<W: uWrite + ?Sized>(&self, w: &mut W) -> Result<(), W::Error> {
+        uwrite!(w, "P1\n{} {}\n", WIDTH, HEIGHT)?;
+        self.write_rows(w, '1', '0')
+    }
+
+    /// Writes one line of [ASCII_ON] and [ASCII_OFF] characters per row
+    pub fn write_ascii<W: uWrite + ?Sized>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.write_rows(w, ASCII_ON, ASCII_OFF)
+    }
+
+    /// Compares frame with image written by [FrameBuffer::write_ascii]
+    ///
+    /// Whitespace around rows is ignored, so images can be stored indented or with trailing newline.
+    pub fn matches_ascii(&self, image: &str) -> bool {
+        let mut rows = image.lines().map(str::trim).filter(|row| !row.is_empty());
+
+        let frame_matches = (0..HEIGHT).all(|y| {
+            rows.next().is_some_and(|row| {
+                row.chars().count() == WIDTH
+                    && row.chars().enumerate().all(|(x, c)| {
+                        let expected = c == ASCII_ON;
+                        let actual = self.pixel(Point::new(x as i32, y as i32)).is_on();
+                        expected == actual
+                    })
+            })
+        });
+
+        frame_matches && rows.next().is_none()
+    }
+
+    fn write_rows<W: uWrite + ?Sized>(
+        &self,
+        w: &mut W,
+        on: char,
+        off: char,
+    ) -> Result<(), W::Error> {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let pixel = self.pixel(Point::new(x as i32, y as i32));
+                w.write_char(if pixel.is_on() { on } else { off })?;
+            }
+            w.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    fn index(point: Point) -> Option<(usize, usize)> {
+        let x = usize::try_from(point.x).ok().filter(|x| *x < WIDTH)?;
+        let y = usize::try_from(point.y).ok().filter(|y| *y < HEIGHT)?;
+        Some((x, y))
+    }
+
+    fn mask(x: usize) -> u8 {
+        0x80 >> (x % 8)
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(DISPLAY_WIDTH, DISPLAY_HEIGHT)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let Some((x, y)) = Self::index(point) else {
+                continue;
+            };
+            match color {
+                BinaryColor::On => self.rows[y][x / 8] |= Self::mask(x),
+                BinaryColor::Off => self.rows[y][x / 8] &= !Self::mask(x),
+            }
+        }
+        Ok(())
+    }
+}
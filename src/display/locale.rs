@@ -0,0 +1,109 @@
+//!
+//! Translations of display labels
+//!
+//! Language can be switched at any time, screens look labels up on every redraw.
+//! Fonts of [iso_8859_5](embedded_graphics::mono_font::iso_8859_5) have both Latin and Cyrillic
+//! glyphs, so every language is drawn with the same fonts.
+//!
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English.code());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Language {
+    English,
+    Russian,
+}
+
+impl Language {
+    pub const ALL: [Language; LANGUAGE_COUNT] = [Language::English, Language::Russian];
+
+    /// Value stored in configuration, never changes for existing languages
+    pub const fn code(&self) -> u8 {
+        match self {
+            Language::English => 0,
+            Language::Russian => 1,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code() == code)
+    }
+
+    /// Language chosen after this one when user cycles through languages
+    pub fn next(&self) -> Self {
+        Self::ALL[(*self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+const LANGUAGE_COUNT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Label {
+    StatusTitle,
+    SensorsTitle,
+    HistoryTitle,
+    StatisticsTitle,
+    SystemTitle,
+    TemperatureHistory,
+    HumidityHistory,
+    Temperature,
+    Humidity,
+    Min,
+    Max,
+    SensorError,
+    Version,
+    Uptime,
+    Clock,
+    ClockSynced,
+    ClockNotSet,
+    Display,
+    /// Suffix of days in uptime
+    Days,
+    BytesPerSecond,
+}
+
+/// Texts of labels in order of [Label] variants, one column per language in order of
+/// [Language::ALL]
+const TABLE: [[&str; LANGUAGE_COUNT]; 20] = [
+    ["Status", "Статус"],
+    ["Sensors", "Датчики"],
+    ["History", "История"],
+    ["Min/Max", "Мин/Макс"],
+    ["System", "Система"],
+    ["Temp. history", "История темп."],
+    ["Hum. history", "История влажн."],
+    ["Temperature", "Температура"],
+    ["Humidity", "Влажность"],
+    ["min", "мин"],
+    ["max", "макс"],
+    ["error", "ошибка"],
+    ["Version", "Версия"],
+    ["Uptime", "Работа"],
+    ["Clock", "Часы"],
+    ["synced", "синхр."],
+    ["not set", "не уст."],
+    ["Display", "Дисплей"],
+    ["d", "д"],
+    ["B/s", "Б/с"],
+];
+
+impl Label {
+    pub fn text(self, language: Language) -> &'static str {
+        TABLE[self as usize][language as usize]
+    }
+}
+
+/// Language of display labels
+pub fn language() -> Language {
+    let code = LANGUAGE.load(Ordering::Relaxed);
+    Language::from_code(code).unwrap_or(Language::English)
+}
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language.code(), Ordering::Relaxed);
+    defmt::info!("locale: {}", language);
+}
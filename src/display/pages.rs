@@ -0,0 +1,297 @@
+//!
+//! Pages of the display switched with button
+//!
+
+use embassy_time::Duration;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+use super::{
+    locale::{self, Label, Language},
+    screens::{HistoryScreen, SensorsScreen, StatisticsScreen, StatusScreen, SystemScreen},
+    statistics::Statistics,
+};
+use crate::{
+    history::HistoryData,
+    measurement::Measurement,
+    units::{self, TemperatureUnit},
+};
+
+/// Display returns to main page if button was not used for this long
+pub const INACTIVITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Page {
+    /// Main page
+    Status,
+    Sensors,
+    History,
+    Statistics,
+    System,
+}
+
+/// Series shown on history page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum HistorySeries {
+    Temperature,
+    Humidity,
+}
+
+impl HistorySeries {
+    pub fn toggled(self) -> Self {
+        match self {
+            HistorySeries::Temperature => HistorySeries::Humidity,
+            HistorySeries::Humidity => HistorySeries::Temperature,
+        }
+    }
+}
+
+/// What long press does on page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum LongPressAction {
+    ResetStatistics,
+    ToggleHistorySeries,
+    NextTemperatureUnit,
+    NextLanguage,
+    GoToMain,
+}
+
+impl Page {
+    pub const ALL: [Page; 5] = [
+        Page::Status,
+        Page::Sensors,
+        Page::History,
+        Page::Statistics,
+        Page::System,
+    ];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn title(self) -> Label {
+        match self {
+            Page::Status => Label::StatusTitle,
+            Page::Sensors => Label::SensorsTitle,
+            Page::History => Label::HistoryTitle,
+            Page::Statistics => Label::StatisticsTitle,
+            Page::System => Label::SystemTitle,
+        }
+    }
+
+    pub fn long_press_action(self) -> LongPressAction {
+        match self {
+            Page::Statistics => LongPressAction::ResetStatistics,
+            Page::History => LongPressAction::ToggleHistorySeries,
+            Page::Sensors => LongPressAction::NextTemperatureUnit,
+            Page::System => LongPressAction::NextLanguage,
+            _ => LongPressAction::GoToMain,
+        }
+    }
+}
+
+/// User preferences of how values and labels are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewSettings {
+    pub temperature_unit: TemperatureUnit,
+    pub language: Language,
+}
+
+impl ViewSettings {
+    /// Settings currently chosen by user
+    pub fn current() -> Self {
+        Self {
+            temperature_unit: units::temperature_unit(),
+            language: locale::language(),
+        }
+    }
+
+    pub fn text(&self, label: Label) -> &'static str {
+        label.text(self.language)
+    }
+}
+
+/// Information about firmware shown on system page
+#[derive(Debug, Clone, Copy)]
+pub struct SystemInfo {
+    pub uptime: Duration,
+    pub clock_synced: bool,
+    /// Average traffic of display updates
+    pub display_bytes_per_second: u32,
+}
+
+/// Data needed to draw any page
+pub struct PageView<'a> {
+    pub page: Page,
+    pub measurement: &'a Measurement,
+    pub statistics: &'a Statistics,
+    pub history: &'a HistoryData,
+    pub history_series: HistorySeries,
+    pub system: &'a SystemInfo,
+    pub settings: ViewSettings,
+}
+
+impl Drawable for PageView<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        match self.page {
+            Page::Status => StatusScreen::new(self.measurement, self.settings).draw(target),
+            Page::Sensors => {
+                SensorsScreen::new(self.page, self.measurement, self.settings).draw(target)
+            }
+            Page::History => {
+                HistoryScreen::new(self.page, self.history_series, self.history, self.settings)
+                    .draw(target)
+            }
+            Page::Statistics => {
+                StatisticsScreen::new(self.page, self.statistics, self.settings).draw(target)
+            }
+            Page::System => SystemScreen::new(self.page, self.system, self.settings).draw(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use ds323x::NaiveDate;
+    use heapless::String;
+
+    use super::*;
+    use crate::{
+        display::framebuffer::FrameBuffer, drivers::sensors::status::SensorStatus,
+        measurement::Timestamp,
+    };
+
+    /// Size of ASCII image, one line per row
+    const ASCII_SIZE: usize = 129 * 64;
+
+    fn measurement(statuses: [SensorStatus; 2]) -> Measurement {
+        let datetime = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .and_then(|date| date.and_hms_opt(12, 34, 56))
+            .unwrap();
+        Measurement {
+            timestamp: Some(Timestamp(datetime)),
+            temperatures: [21.5, 22.25],
+            statuses,
+            temperature: 21.875,
+            humidity: 45.2,
+        }
+    }
+
+    fn history() -> HistoryData {
+        let mut history = HistoryData::new();
+        for n in 0..48 {
+            history.temperature.write(20.0 + (n % 12) as f32 * 0.25);
+            history.humidity.write(40.0 + (n % 8) as f32);
+        }
+        history
+    }
+
+    fn statistics(measurement: &Measurement) -> Statistics {
+        let mut statistics = Statistics::new();
+        statistics.update(measurement);
+        statistics.update(&Measurement {
+            temperature: 18.5,
+            humidity: 52.0,
+            ..*measurement
+        });
+        statistics
+    }
+
+    /// Compares page with stored image, `UPDATE_SNAPSHOTS=1` stores current rendering instead
+    fn assert_snapshot(name: &str, page: Page, measurement: &Measurement, language: Language) {
+        let history = history();
+        let view = PageView {
+            page,
+            measurement,
+            statistics: &statistics(measurement),
+            history: &history,
+            history_series: HistorySeries::Temperature,
+            system: &SystemInfo {
+                uptime: Duration::from_secs(93_784),
+                clock_synced: true,
+                display_bytes_per_second: 512,
+            },
+            settings: ViewSettings {
+                temperature_unit: TemperatureUnit::Celsius,
+                language,
+            },
+        };
+        let frame = FrameBuffer::render(&view);
+
+        let mut actual: String<ASCII_SIZE> = String::new();
+        frame.write_ascii(&mut actual).unwrap();
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/display/snapshots")
+            .join(format!("{name}.txt"));
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, actual.as_str()).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            frame.matches_ascii(&expected),
+            "{name} differs from {}:\n{}",
+            path.display(),
+            actual
+        );
+    }
+
+    #[test]
+    fn status_page() {
+        let measurement = measurement([SensorStatus::Ok, SensorStatus::Ok]);
+        assert_snapshot("status", Page::Status, &measurement, Language::English);
+    }
+
+    #[test]
+    fn sensors_page() {
+        let measurement = measurement([SensorStatus::Ok, SensorStatus::Ok]);
+        assert_snapshot("sensors", Page::Sensors, &measurement, Language::English);
+    }
+
+    #[test]
+    fn sensors_page_with_failed_sensors() {
+        let measurement = measurement([SensorStatus::Unknown, SensorStatus::Error]);
+        assert_snapshot(
+            "sensors_failed",
+            Page::Sensors,
+            &measurement,
+            Language::English,
+        );
+    }
+
+    #[test]
+    fn history_page() {
+        let measurement = measurement([SensorStatus::Ok, SensorStatus::Ok]);
+        assert_snapshot("history", Page::History, &measurement, Language::English);
+    }
+
+    #[test]
+    fn statistics_page() {
+        let measurement = measurement([SensorStatus::Ok, SensorStatus::Ok]);
+        assert_snapshot(
+            "statistics",
+            Page::Statistics,
+            &measurement,
+            Language::English,
+        );
+    }
+
+    #[test]
+    fn system_page_in_russian() {
+        let measurement = measurement([SensorStatus::Ok, SensorStatus::Ok]);
+        assert_snapshot("system_ru", Page::System, &measurement, Language::Russian);
+    }
+}
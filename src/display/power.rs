@@ -0,0 +1,103 @@
+//!
+//! Burn-in protection of OLED panel
+//!
+//! Display is dimmed at night, turned off after inactivity and the whole layout is moved by
+//! a pixel from time to time, so the same pixels are not lit for months.
+//!
+
+use embassy_time::{Duration, Instant};
+use embedded_graphics::prelude::Point;
+
+/// Contrast level of panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Brightness(pub u8);
+
+impl Brightness {
+    pub const DIMMEST: Brightness = Brightness(0x00);
+    pub const DIM: Brightness = Brightness(0x2F);
+    pub const NORMAL: Brightness = Brightness(0x5F);
+    pub const BRIGHT: Brightness = Brightness(0x9F);
+    pub const BRIGHTEST: Brightness = Brightness(0xFF);
+}
+
+/// Offsets of layout cycled by pixel shifting, layout is never moved more than by one pixel
+const SHIFT_OFFSETS: [Point; 4] = [
+    Point::new(0, 0),
+    Point::new(1, 0),
+    Point::new(1, 1),
+    Point::new(0, 1),
+];
+
+/// Hours of UTC day when display is dimmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct NightSchedule {
+    /// First hour of night
+    pub start_hour: u8,
+    /// First hour of day
+    pub end_hour: u8,
+}
+
+impl NightSchedule {
+    pub fn is_night(&self, hour: u8) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            // Night goes over midnight
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub brightness: Brightness,
+    pub night_brightness: Brightness,
+    /// Night is not detected until clock is synchronized
+    pub night: Option<NightSchedule>,
+    /// Display is turned off after this time without button presses, `None` to keep it on
+    pub sleep_timeout: Option<Duration>,
+    /// How often layout is moved
+    pub pixel_shift_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            brightness: Brightness::NORMAL,
+            night_brightness: Brightness::DIMMEST,
+            night: Some(NightSchedule {
+                start_hour: 22,
+                end_hour: 6,
+            }),
+            sleep_timeout: Some(Duration::from_secs(5 * 60)),
+            pixel_shift_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Config {
+    /// Brightness for hour of UTC day, day brightness is used when time is unknown
+    pub fn brightness_at(&self, hour: Option<u8>) -> Brightness {
+        let night = self
+            .night
+            .zip(hour)
+            .is_some_and(|(night, hour)| night.is_night(hour));
+
+        match night {
+            true => self.night_brightness,
+            false => self.brightness,
+        }
+    }
+
+    /// Offset of layout at given time since boot
+    pub fn pixel_shift_at(&self, instant: Instant) -> Point {
+        let interval = self.pixel_shift_interval.as_ticks().max(1);
+        let step = instant.as_ticks() / interval;
+        SHIFT_OFFSETS[(step % SHIFT_OFFSETS.len() as u64) as usize]
+    }
+
+    /// Time when display should go to sleep after last activity
+    pub fn sleep_deadline(&self, last_activity: Instant) -> Option<Instant> {
+        self.sleep_timeout.map(|timeout| last_activity + timeout)
+    }
+}
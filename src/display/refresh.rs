@@ -0,0 +1,51 @@
+//!
+//! Limits of display refreshes and traffic they cause
+//!
+
+use embassy_time::{Duration, Instant};
+
+/// Display is not updated more often, changes made meanwhile are sent with next update
+pub const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time over which sent bytes are averaged
+pub const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Average number of bytes sent to display per second
+pub struct Throughput {
+    window_start: Instant,
+    bytes: usize,
+    bytes_per_second: u32,
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            bytes: 0,
+            bytes_per_second: 0,
+        }
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.bytes += bytes;
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= THROUGHPUT_WINDOW {
+            self.bytes_per_second = (self.bytes as u64 * 1000 / elapsed.as_millis()) as u32;
+            defmt::debug!("display: {} B/s", self.bytes_per_second);
+            self.bytes = 0;
+            self.window_start = Instant::now();
+        }
+    }
+
+    /// Average of last complete window
+    pub fn bytes_per_second(&self) -> u32 {
+        self.bytes_per_second
+    }
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,76 @@
+//!
+//! Graph of fused temperature or humidity over last hours
+//!
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle};
+
+use super::{header_with_title, CONTENT_TOP, DISPLAY_HEIGHT, DISPLAY_WIDTH, TINY_STYLE};
+use crate::{
+    display::{
+        drawables::{Graph, GraphStyle},
+        locale::Label,
+        pages::{HistorySeries, Page, ViewSettings},
+    },
+    history::{HistoryData, POINTS_PER_HOUR},
+    units::TemperatureUnit,
+};
+
+pub struct HistoryScreen<'a> {
+    page: Page,
+    series: HistorySeries,
+    data: &'a HistoryData,
+    settings: ViewSettings,
+}
+
+impl<'a> HistoryScreen<'a> {
+    pub fn new(
+        page: Page,
+        series: HistorySeries,
+        data: &'a HistoryData,
+        settings: ViewSettings,
+    ) -> Self {
+        Self {
+            page,
+            series,
+            data,
+            settings,
+        }
+    }
+}
+
+impl Drawable for HistoryScreen<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let (title, samples, graph_style, transform) = match self.series {
+            HistorySeries::Temperature => (
+                Label::TemperatureHistory,
+                &self.data.temperature,
+                GraphStyle::Line,
+                self.settings.temperature_unit.converter(),
+            ),
+            HistorySeries::Humidity => (
+                Label::HumidityHistory,
+                &self.data.humidity,
+                GraphStyle::Bar,
+                TemperatureUnit::Celsius.converter(),
+            ),
+        };
+
+        header_with_title(self.page, self.settings.text(title)).draw(target)?;
+
+        let area = Rectangle::new(
+            Point::new(0, CONTENT_TOP),
+            Size::new(DISPLAY_WIDTH, DISPLAY_HEIGHT - CONTENT_TOP as u32),
+        );
+        Graph::new(samples, area, TINY_STYLE, BinaryColor::On)
+            .with_graph_style(graph_style)
+            .with_ticks(POINTS_PER_HOUR)
+            .with_transform(transform)
+            .draw(target)
+    }
+}
@@ -0,0 +1,61 @@
+//!
+//! Layouts of display pages
+//!
+
+use embedded_graphics::{
+    mono_font::{
+        iso_8859_5::{FONT_10X20, FONT_4X6, FONT_6X10},
+        MonoTextStyle,
+    },
+    pixelcolor::BinaryColor,
+};
+
+use super::{
+    drawables::Header,
+    pages::{Page, ViewSettings},
+};
+
+mod history;
+mod sensors;
+mod statistics;
+mod status;
+mod system;
+
+pub use history::HistoryScreen;
+pub use sensors::SensorsScreen;
+pub use statistics::StatisticsScreen;
+pub use status::StatusScreen;
+pub use system::SystemScreen;
+
+pub const DISPLAY_WIDTH: u32 = 128;
+pub const DISPLAY_HEIGHT: u32 = 64;
+
+/// Baseline of header text
+const HEADER_Y: i32 = 8;
+
+/// First row of pixels below header
+const CONTENT_TOP: i32 = HEADER_Y + 4;
+
+/// Baseline of first line below header
+const CONTENT_Y: i32 = 24;
+
+/// Distance between baselines of lines in small font
+const LINE_HEIGHT: i32 = 11;
+
+// Fonts with Cyrillic glyphs, so labels of any language can be drawn
+const TINY_STYLE: MonoTextStyle<'static, BinaryColor> =
+    MonoTextStyle::new(&FONT_4X6, BinaryColor::On);
+const SMALL_STYLE: MonoTextStyle<'static, BinaryColor> =
+    MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+const LARGE_STYLE: MonoTextStyle<'static, BinaryColor> =
+    MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+
+fn header(page: Page, settings: ViewSettings) -> Header<'static, 'static, BinaryColor> {
+    header_with_title(page, settings.text(page.title()))
+}
+
+fn header_with_title(page: Page, title: &str) -> Header<'_, 'static, BinaryColor> {
+    Header::new(HEADER_Y, DISPLAY_WIDTH, SMALL_STYLE)
+        .with_title(title)
+        .with_page_number(page.index() + 1, Page::ALL.len())
+}
@@ -0,0 +1,77 @@
+//!
+//! Temperature and state of each sensor
+//!
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, text::Text};
+
+use super::{header, CONTENT_Y, LINE_HEIGHT, SMALL_STYLE};
+use crate::{
+    display::{
+        drawables::{status_icon::ICON_SIZE, MeasurementText, StatusIcon},
+        locale::Label,
+        pages::{Page, ViewSettings},
+    },
+    drivers::sensors::status::SensorStatus,
+    measurement::{Measurement, SensorId},
+};
+
+const TEMPERATURE_X: i32 = 48;
+const ICON_X: i32 = 112;
+
+pub struct SensorsScreen<'a> {
+    page: Page,
+    measurement: &'a Measurement,
+    settings: ViewSettings,
+}
+
+impl<'a> SensorsScreen<'a> {
+    pub fn new(page: Page, measurement: &'a Measurement, settings: ViewSettings) -> Self {
+        Self {
+            page,
+            measurement,
+            settings,
+        }
+    }
+}
+
+impl Drawable for SensorsScreen<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        header(self.page, self.settings).draw(target)?;
+        let unit = self.settings.temperature_unit;
+
+        for (row, id) in SensorId::ALL.into_iter().enumerate() {
+            let y = CONTENT_Y + row as i32 * LINE_HEIGHT;
+
+            Text::new(id.name(), Point::new(0, y), SMALL_STYLE).draw(target)?;
+            let status = self.measurement.sensor_status(id);
+            let value_position = Point::new(TEMPERATURE_X, y);
+            if status == SensorStatus::Error {
+                let error = self.settings.text(Label::SensorError);
+                Text::new(error, value_position, SMALL_STYLE).draw(target)?;
+            } else {
+                // Temperature is not read yet while status is unknown
+                let temperature = (status == SensorStatus::Ok)
+                    .then(|| unit.from_celsius(self.measurement.sensor_temperature(id)));
+                MeasurementText::new(value_position, SMALL_STYLE)
+                    .with_value(temperature)
+                    .with_decimals(2)
+                    .with_unit(unit.into())
+                    .draw(target)?;
+            }
+            StatusIcon::new(
+                Point::new(ICON_X, y - ICON_SIZE as i32 + 1),
+                BinaryColor::On,
+            )
+            .with_status(status)
+            .draw(target)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//!
+//! Minimum and maximum of fused temperature and humidity
+//!
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, text::Text};
+
+use super::{header, CONTENT_Y, LINE_HEIGHT, SMALL_STYLE};
+use crate::display::{
+    drawables::{MeasurementText, Unit},
+    locale::Label,
+    pages::{Page, ViewSettings},
+    statistics::{MinMax, Statistics},
+};
+
+const MIN_X: i32 = 0;
+const MAX_X: i32 = 64;
+/// Offset of value from "min"/"max" label
+const VALUE_OFFSET: i32 = 24;
+
+pub struct StatisticsScreen<'a> {
+    page: Page,
+    statistics: &'a Statistics,
+    settings: ViewSettings,
+}
+
+impl<'a> StatisticsScreen<'a> {
+    pub fn new(page: Page, statistics: &'a Statistics, settings: ViewSettings) -> Self {
+        Self {
+            page,
+            statistics,
+            settings,
+        }
+    }
+
+    /// Draws "min" and "max" labels at line and returns positions of values
+    fn draw_labels<D>(&self, y: i32, target: &mut D) -> Result<[Point; 2], D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let labels = [(Label::Min, MIN_X), (Label::Max, MAX_X)];
+        let mut positions = [Point::zero(); 2];
+        for ((label, x), position) in labels.into_iter().zip(positions.iter_mut()) {
+            let label = self.settings.text(label);
+            Text::new(label, Point::new(x, y), SMALL_STYLE).draw(target)?;
+            *position = Point::new(x + VALUE_OFFSET, y);
+        }
+        Ok(positions)
+    }
+
+    /// Draws minimum and maximum, or placeholders when nothing was measured since reset
+    fn draw_values<D>(
+        positions: [Point; 2],
+        min_max: Option<MinMax>,
+        unit: Unit,
+        target: &mut D,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let values = match min_max {
+            Some(MinMax { min, max }) => [Some(min), Some(max)],
+            None => [None, None],
+        };
+        for (position, value) in positions.into_iter().zip(values) {
+            MeasurementText::new(position, SMALL_STYLE)
+                .with_value(value)
+                .with_unit(unit)
+                .draw(target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drawable for StatisticsScreen<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        header(self.page, self.settings).draw(target)?;
+        let unit = self.settings.temperature_unit;
+
+        let temperature_y = CONTENT_Y;
+        let title = self.settings.text(Label::Temperature);
+        Text::new(title, Point::new(0, temperature_y), SMALL_STYLE).draw(target)?;
+        let positions = self.draw_labels(temperature_y + LINE_HEIGHT, target)?;
+        let temperature = self
+            .statistics
+            .temperature
+            .map(|MinMax { min, max }| MinMax {
+                min: unit.from_celsius(min),
+                max: unit.from_celsius(max),
+            });
+        Self::draw_values(positions, temperature, unit.into(), target)?;
+
+        let humidity_y = temperature_y + 2 * LINE_HEIGHT;
+        let title = self.settings.text(Label::Humidity);
+        Text::new(title, Point::new(0, humidity_y), SMALL_STYLE).draw(target)?;
+        let positions = self.draw_labels(humidity_y + LINE_HEIGHT, target)?;
+        Self::draw_values(
+            positions,
+            self.statistics.humidity,
+            Unit::RelativeHumidity,
+            target,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+//!
+//! Main screen with time, fused temperature, humidity and sensor states
+//!
+
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Alignment, Text},
+};
+
+use super::{LARGE_STYLE, SMALL_STYLE};
+use crate::display::{
+    drawables::{status_icon::ICON_SIZE, DateTimeText, MeasurementText, StatusIcon, Unit},
+    pages::ViewSettings,
+};
+use crate::{
+    drivers::sensors::status::SensorStatus,
+    measurement::{Measurement, SensorId, SENSOR_COUNT},
+};
+
+const DATETIME_POSITION: Point = Point::new(0, 8);
+/// Temperature is centered at this point
+const TEMPERATURE_POSITION: Point = Point::new(64, 38);
+const HUMIDITY_POSITION: Point = Point::new(0, 62);
+
+/// Width of label and icon of one sensor
+const SENSOR_SLOT_WIDTH: i32 = 16;
+const SENSOR_SLOTS_POSITION: Point = Point::new(128 - SENSOR_SLOT_WIDTH * SENSOR_COUNT as i32, 62);
+
+pub struct StatusScreen<'a> {
+    measurement: &'a Measurement,
+    settings: ViewSettings,
+}
+
+impl<'a> StatusScreen<'a> {
+    pub fn new(measurement: &'a Measurement, settings: ViewSettings) -> Self {
+        Self {
+            measurement,
+            settings,
+        }
+    }
+}
+
+impl Drawable for StatusScreen<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let unit = self.settings.temperature_unit;
+
+        DateTimeText::new(DATETIME_POSITION, SMALL_STYLE)
+            .with_datetime(self.measurement.timestamp.map(|timestamp| timestamp.0))
+            .draw(target)?;
+
+        MeasurementText::new(TEMPERATURE_POSITION, LARGE_STYLE)
+            .with_value(unit.from_celsius(self.measurement.temperature))
+            .with_unit(unit.into())
+            .with_alignment(Alignment::Center)
+            .draw(target)?;
+
+        let humidity = (self.measurement.sensor_status(SensorId::Dht22) == SensorStatus::Ok)
+            .then_some(self.measurement.humidity);
+        MeasurementText::new(HUMIDITY_POSITION, SMALL_STYLE)
+            .with_value(humidity)
+            .with_unit(Unit::RelativeHumidity)
+            .draw(target)?;
+
+        for (slot, id) in SensorId::ALL.into_iter().enumerate() {
+            let position = SENSOR_SLOTS_POSITION + Point::new(slot as i32 * SENSOR_SLOT_WIDTH, 0);
+            let after_label = Text::new(&id.name()[..1], position, SMALL_STYLE).draw(target)?;
+
+            let icon_position = Point::new(after_label.x + 1, position.y - ICON_SIZE as i32 + 1);
+            StatusIcon::new(icon_position, BinaryColor::On)
+                .with_status(self.measurement.sensor_status(id))
+                .draw(target)?;
+        }
+
+        Ok(())
+    }
+}
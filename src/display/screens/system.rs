@@ -0,0 +1,101 @@
+//!
+//! Firmware version, uptime, clock state and display traffic
+//!
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, text::Text};
+use heapless::String;
+use ufmt::uwrite;
+
+use super::{header, CONTENT_Y, LINE_HEIGHT, SMALL_STYLE};
+use crate::display::{
+    locale::Label,
+    pages::{Page, SystemInfo, ViewSettings},
+};
+
+const VALUE_X: i32 = 48;
+
+// Sizes in bytes, Cyrillic letters take two bytes each
+const UPTIME_STRING_SIZE: usize = 18; // \d{1,5}д \d\d:\d\d:\d\d
+const TRAFFIC_STRING_SIZE: usize = 16; // \d{1,10} Б/с
+
+pub struct SystemScreen<'a> {
+    page: Page,
+    info: &'a SystemInfo,
+    settings: ViewSettings,
+}
+
+impl<'a> SystemScreen<'a> {
+    pub fn new(page: Page, info: &'a SystemInfo, settings: ViewSettings) -> Self {
+        Self {
+            page,
+            info,
+            settings,
+        }
+    }
+
+    fn write_uptime<const N: usize>(seconds: u64, days_suffix: &str, str: &mut String<N>) {
+        let days = seconds / (24 * 60 * 60);
+        let hours = seconds / (60 * 60) % 24;
+        let minutes = seconds / 60 % 60;
+        let seconds = seconds % 60;
+
+        str.clear();
+        uwrite!(str, "{}{} ", days, days_suffix).ok();
+        for (n, value) in [hours, minutes, seconds].into_iter().enumerate() {
+            if n > 0 {
+                str.push(':').ok();
+            }
+            if value < 10 {
+                str.push('0').ok();
+            }
+            uwrite!(str, "{}", value).ok();
+        }
+    }
+}
+
+impl Drawable for SystemScreen<'_> {
+    type Color = BinaryColor;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<Self::Output, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        header(self.page, self.settings).draw(target)?;
+        let text = |label| self.settings.text(label);
+
+        let mut uptime: String<UPTIME_STRING_SIZE> = String::new();
+        Self::write_uptime(self.info.uptime.as_secs(), text(Label::Days), &mut uptime);
+
+        let clock = if self.info.clock_synced {
+            text(Label::ClockSynced)
+        } else {
+            text(Label::ClockNotSet)
+        };
+
+        let mut traffic: String<TRAFFIC_STRING_SIZE> = String::new();
+        let bytes_per_second = self.info.display_bytes_per_second;
+        uwrite!(
+            traffic,
+            "{} {}",
+            bytes_per_second,
+            text(Label::BytesPerSecond)
+        )
+        .ok();
+
+        let rows = [
+            (Label::Version, env!("CARGO_PKG_VERSION")),
+            (Label::Uptime, uptime.as_str()),
+            (Label::Clock, clock),
+            (Label::Display, traffic.as_str()),
+        ];
+
+        for (row, (label, value)) in rows.into_iter().enumerate() {
+            let y = CONTENT_Y + row as i32 * LINE_HEIGHT;
+            Text::new(text(label), Point::new(0, y), SMALL_STYLE).draw(target)?;
+            Text::new(value, Point::new(VALUE_X, y), SMALL_STYLE).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//!
+//! Minimum and maximum of readings since start or last reset
+//!
+
+use crate::{
+    drivers::sensors::status::SensorStatus,
+    measurement::{Measurement, SensorId, Timestamp},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinMax {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl MinMax {
+    fn new(value: f32) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+
+    fn update(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Statistics {
+    pub temperature: Option<MinMax>,
+    pub humidity: Option<MinMax>,
+    /// Time of first measurement taken into account
+    pub since: Option<Timestamp>,
+}
+
+impl Statistics {
+    pub const fn new() -> Self {
+        Self {
+            temperature: None,
+            humidity: None,
+            since: None,
+        }
+    }
+
+    /// Takes values of sensors read successfully into account
+    pub fn update(&mut self, measurement: &Measurement) {
+        // Combined temperature is NaN when no sensor was read
+        let temperature = Some(measurement.temperature).filter(|value| value.is_finite());
+        let humidity = (measurement.sensor_status(SensorId::Dht22) == SensorStatus::Ok)
+            .then_some(measurement.humidity);
+        if temperature.is_none() && humidity.is_none() {
+            return;
+        }
+
+        if let Some(temperature) = temperature {
+            Self::update_value(&mut self.temperature, temperature);
+        }
+        if let Some(humidity) = humidity {
+            Self::update_value(&mut self.humidity, humidity);
+        }
+        self.since = self.since.or(measurement.timestamp);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn update_value(min_max: &mut Option<MinMax>, value: f32) {
+        match min_max {
+            Some(min_max) => min_max.update(value),
+            None => *min_max = Some(MinMax::new(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(temperature: f32, humidity: f32, dht22: SensorStatus) -> Measurement {
+        Measurement {
+            timestamp: None,
+            temperatures: [temperature; 2],
+            statuses: [SensorStatus::Ok, dht22],
+            temperature,
+            humidity,
+        }
+    }
+
+    #[test]
+    fn failed_readings_are_ignored() {
+        let mut statistics = Statistics::new();
+        statistics.update(&measurement(21.0, 40.0, SensorStatus::Ok));
+        statistics.update(&measurement(25.0, 0.0, SensorStatus::Error));
+        statistics.update(&measurement(f32::NAN, 0.0, SensorStatus::Unknown));
+        statistics.update(&measurement(19.0, 45.0, SensorStatus::Ok));
+
+        let range = |min, max| Some(MinMax { min, max });
+        assert_eq!(statistics.temperature, range(19.0, 25.0));
+        assert_eq!(statistics.humidity, range(40.0, 45.0));
+    }
+
+    #[test]
+    fn nothing_is_collected_before_first_reading() {
+        let mut statistics = Statistics::new();
+        statistics.update(&measurement(f32::NAN, 0.0, SensorStatus::Error));
+
+        assert_eq!(statistics.temperature, None);
+        assert_eq!(statistics.humidity, None);
+    }
+}
@@ -0,0 +1,233 @@
+//!
+//! Display task drawing pages on measurements, button presses and timeouts
+//!
+
+// Task gets every resource it uses as argument, also through its spawner
+#![allow(clippy::too_many_arguments)]
+
+use embassy_executor::Spawner;
+use embassy_futures::select::{select4, Either4};
+use embassy_time::{Duration, Instant, Timer};
+
+use core::sync::atomic::Ordering;
+
+use super::{
+    backend::{Backend, DisplayBackend},
+    framebuffer::FrameBuffer,
+    locale,
+    pages::{
+        HistorySeries, LongPressAction, Page, PageView, SystemInfo, ViewSettings,
+        INACTIVITY_TIMEOUT,
+    },
+    power,
+    refresh::{Throughput, MIN_REFRESH_INTERVAL},
+    statistics::Statistics,
+    Display, Shared,
+};
+use crate::bsp::{button::Button, button::ButtonEvent};
+use crate::config::{self, SharedStore};
+use crate::history::History;
+use crate::measurement::{Measurement, MeasurementChannel};
+use crate::units;
+use crate::watchdog::CheckIn;
+
+/// Display task checks in with watchdog at least this often, also while asleep
+const WATCHDOG_PERIOD: Duration = Duration::from_secs(10);
+
+pub fn spawn_display_tasks(
+    measurements: &'static MeasurementChannel,
+    display: Backend,
+    button: Button<'static>,
+    history: History<'static>,
+    config: power::Config,
+    shared: &'static Shared,
+    config_store: &'static SharedStore,
+    watchdog: CheckIn<'static>,
+    spawner: &Spawner,
+) -> Display<'static> {
+    spawner.must_spawn(draw_current_temperature(
+        measurements,
+        display,
+        button,
+        history,
+        config,
+        shared,
+        config_store,
+        watchdog,
+    ));
+    Display { shared }
+}
+
+#[embassy_executor::task]
+async fn draw_current_temperature(
+    measurements: &'static MeasurementChannel,
+    mut display: Backend,
+    button: Button<'static>,
+    history: History<'static>,
+    config: power::Config,
+    shared: &'static Shared,
+    config_store: &'static SharedStore,
+    watchdog: CheckIn<'static>,
+) {
+    use ds323x::Timelike;
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::*;
+
+    let mut subscriber = measurements
+        .subscriber()
+        .expect("failed to create subscriber");
+
+    display.init().await.expect("failed to init display");
+
+    let mut page = Page::Status;
+    let mut statistics = Statistics::new();
+    let mut history_series = HistorySeries::Temperature;
+    let mut measurement: Option<Measurement> = None;
+    let mut last_activity = Instant::now();
+    let mut asleep = false;
+    let mut brightness = None;
+    let mut alarm = false;
+    let mut frame = FrameBuffer::new();
+    let mut last_refresh = Instant::now();
+    let mut throughput = Throughput::new();
+
+    loop {
+        watchdog.check_in(WATCHDOG_PERIOD);
+        let check_in_deadline = Instant::now() + WATCHDOG_PERIOD;
+        let return_deadline = (page != Page::Status).then(|| last_activity + INACTIVITY_TIMEOUT);
+        let sleep_deadline = config.sleep_deadline(last_activity).filter(|_| !asleep);
+        let deadline = [return_deadline, sleep_deadline]
+            .into_iter()
+            .flatten()
+            .fold(check_in_deadline, Instant::min);
+
+        let event = select4(
+            subscriber.next_message_pure(),
+            button.wait_event(),
+            Timer::at(deadline),
+            shared.wake.wait(),
+        );
+
+        let woken = match event.await {
+            Either4::First(new_measurement) => {
+                statistics.update(&new_measurement);
+                measurement = Some(new_measurement);
+                false
+            }
+            // Press that wakes display is not used for navigation
+            Either4::Second(_) if asleep => true,
+            Either4::Second(ButtonEvent::ShortPress) => {
+                page = page.next();
+                last_activity = Instant::now();
+                false
+            }
+            Either4::Second(ButtonEvent::LongPress) => {
+                match page.long_press_action() {
+                    LongPressAction::ResetStatistics => statistics.reset(),
+                    LongPressAction::ToggleHistorySeries => {
+                        history_series = history_series.toggled()
+                    }
+                    LongPressAction::NextTemperatureUnit => {
+                        units::set_temperature_unit(units::temperature_unit().next());
+                        save_preferences(config_store).await;
+                    }
+                    LongPressAction::NextLanguage => {
+                        locale::set_language(locale::language().next());
+                        save_preferences(config_store).await;
+                    }
+                    LongPressAction::GoToMain => page = Page::Status,
+                }
+                last_activity = Instant::now();
+                false
+            }
+            Either4::Third(_) => {
+                let now = Instant::now();
+                if return_deadline.is_some_and(|deadline| now >= deadline) {
+                    page = Page::Status;
+                }
+                if sleep_deadline.is_some_and(|deadline| now >= deadline) {
+                    defmt::debug!("display: sleep");
+                    display.set_display_on(false).ok();
+                    asleep = true;
+                }
+                false
+            }
+            Either4::Fourth(_) => true,
+        };
+
+        if woken {
+            last_activity = Instant::now();
+            if asleep {
+                defmt::debug!("display: wake up");
+                page = Page::Status;
+                display.set_display_on(true).ok();
+                asleep = false;
+            }
+        }
+
+        if asleep {
+            continue;
+        }
+
+        let Some(measurement) = measurement.as_ref() else {
+            continue;
+        };
+
+        let hour = measurement
+            .timestamp
+            .map(|timestamp| timestamp.0.hour() as u8);
+        let new_brightness = config.brightness_at(hour);
+        if brightness != Some(new_brightness) {
+            display.set_brightness(new_brightness).ok();
+            brightness = Some(new_brightness);
+        }
+
+        let new_alarm = shared.alarm.load(Ordering::Relaxed);
+        if alarm != new_alarm {
+            display.set_alarm(new_alarm).ok();
+            alarm = new_alarm;
+        }
+
+        // Changes coming meanwhile are drawn with this refresh
+        Timer::at(last_refresh + MIN_REFRESH_INTERVAL).await;
+
+        let system = SystemInfo {
+            uptime: Instant::now().duration_since(Instant::from_ticks(0)),
+            clock_synced: measurement.timestamp.is_some(),
+            display_bytes_per_second: throughput.bytes_per_second(),
+        };
+
+        frame.clear(BinaryColor::Off).ok();
+        let offset = config.pixel_shift_at(Instant::now());
+        history
+            .with_data(|history| {
+                let view = PageView {
+                    page,
+                    measurement,
+                    statistics: &statistics,
+                    history,
+                    history_series,
+                    system: &system,
+                    settings: ViewSettings::current(),
+                };
+                view.draw(&mut frame.translated(offset)).ok();
+            })
+            .await;
+
+        match display.update(&frame) {
+            Ok(sent) => throughput.add(sent),
+            Err(_) => defmt::error!("display: failed to update"),
+        }
+        last_refresh = Instant::now();
+    }
+}
+
+/// Keeps unit and language chosen with button across restarts
+async fn save_preferences(config_store: &SharedStore) {
+    let mut store = config_store.lock().await;
+    let result =
+        config::save_preferences(&mut store, units::temperature_unit(), locale::language());
+    if let Err(err) = result {
+        defmt::warn!("display: preferences not saved: {}", err);
+    }
+}
@@ -1 +1,6 @@
+pub mod at24cxx;
+#[cfg(feature = "board")]
+pub mod ds3231;
+pub mod esp_at;
+pub mod sdcard;
 pub mod sensors;
@@ -0,0 +1,135 @@
+//!
+//! AT24Cxx I2C EEPROM driver
+//!
+//! Covers chips with two address bytes, AT24C32 to AT24C256. Writes are split at page
+//! boundaries, since a page write wraps around within its page, and every page write is
+//! followed by the internal write cycle of the chip.
+//!
+
+use core::future::Future;
+
+#[cfg(feature = "board")]
+use embassy_time::{Duration, Timer};
+#[cfg(feature = "board")]
+use embedded_hal::i2c::I2c as _;
+
+#[cfg(feature = "board")]
+use crate::bsp::I2cShared;
+
+/// Address of AT24C32 on ZS-042 DS3231 modules, A0..A2 are pulled up
+pub const ZS042_ADDRESS: u8 = 0x57;
+
+#[cfg(feature = "board")]
+/// Longest internal write cycle, chip does not answer meanwhile
+const WRITE_CYCLE_TIME: Duration = Duration::from_millis(10);
+
+/// Largest page of supported chips
+#[cfg(feature = "board")]
+const MAX_PAGE_SIZE: usize = 64;
+
+/// Size and page size of chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Geometry {
+    pub capacity: u16,
+    pub page_size: u16,
+}
+
+impl Geometry {
+    pub const AT24C32: Self = Self {
+        capacity: 4 * 1024,
+        page_size: 32,
+    };
+    pub const AT24C64: Self = Self {
+        capacity: 8 * 1024,
+        page_size: 32,
+    };
+    pub const AT24C128: Self = Self {
+        capacity: 16 * 1024,
+        page_size: 64,
+    };
+    pub const AT24C256: Self = Self {
+        capacity: 32 * 1024,
+        page_size: 64,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum Error {
+    #[error("Bus")]
+    Bus,
+    /// Access goes past the end of memory
+    #[error("Out of range")]
+    OutOfRange,
+}
+
+/// Byte-addressed non-volatile memory
+pub trait Eeprom {
+    fn capacity(&self) -> u16;
+
+    fn read(&mut self, offset: u16, buffer: &mut [u8]) -> Result<(), Error>;
+
+    fn write(&mut self, offset: u16, data: &[u8]) -> impl Future<Output = Result<(), Error>>;
+}
+
+#[cfg(feature = "board")]
+pub struct At24cxx {
+    bus: I2cShared,
+    address: u8,
+    geometry: Geometry,
+}
+
+#[cfg(feature = "board")]
+impl At24cxx {
+    pub fn new(bus: I2cShared, address: u8, geometry: Geometry) -> Self {
+        Self {
+            bus,
+            address,
+            geometry,
+        }
+    }
+
+    fn check_range(&self, offset: u16, len: usize) -> Result<(), Error> {
+        if usize::from(offset) + len > usize::from(self.geometry.capacity) {
+            return Err(Error::OutOfRange);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "board")]
+impl Eeprom for At24cxx {
+    fn capacity(&self) -> u16 {
+        self.geometry.capacity
+    }
+
+    fn read(&mut self, offset: u16, buffer: &mut [u8]) -> Result<(), Error> {
+        self.check_range(offset, buffer.len())?;
+        self.bus
+            .write_read(self.address, &offset.to_be_bytes(), buffer)
+            .map_err(|_| Error::Bus)
+    }
+
+    async fn write(&mut self, offset: u16, data: &[u8]) -> Result<(), Error> {
+        self.check_range(offset, data.len())?;
+
+        let page_size = usize::from(self.geometry.page_size);
+        let mut offset = usize::from(offset);
+        let mut data = data;
+        while !data.is_empty() {
+            let room = page_size - offset % page_size;
+            let (page, rest) = data.split_at(room.min(data.len()));
+
+            let mut frame = [0; 2 + MAX_PAGE_SIZE];
+            frame[..2].copy_from_slice(&(offset as u16).to_be_bytes());
+            frame[2..2 + page.len()].copy_from_slice(page);
+            self.bus
+                .write(self.address, &frame[..2 + page.len()])
+                .map_err(|_| Error::Bus)?;
+            Timer::after(WRITE_CYCLE_TIME).await;
+
+            offset += page.len();
+            data = rest;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+//!
+//! DS3231 real-time clock driver
+//!
+//! The clock is shared between runner and handles, so date-time can be read and set from any
+//! task while runner periodically forces temperature conversions.
+//!
+//! Alarms are reported with [Ds3231::wait_alarm] when INT/SQW output is connected to an EXTI pin
+//! with [Runner::with_int_pin].
+//!
+
+use ds323x::{ic::DS3231, interface::I2cInterface, DateTimeAccess, Ds323x};
+use embassy_futures::select::select;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, mutex::Mutex};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::{
+    bsp::{I2cError, I2cShared},
+    drivers::sensors::temperature::TemperatureSensor,
+    units::Temperature,
+};
+
+pub use ds323x::{Alarm1Matching, Alarm2Matching, DayAlarm1, DayAlarm2, Hours, NaiveDateTime};
+
+pub const MEASUREMENT_INTERVAL: Duration = Duration::from_millis(2000);
+
+/// Time needed to finish forced temperature conversion
+pub const CONVERSION_TIME: Duration = Duration::from_millis(200);
+
+/// How many alarm events can wait to be handled
+const ALARM_QUEUE_SIZE: usize = 2;
+
+/// Pin connected to INT/SQW output of the clock
+pub type IntPin = crate::bsp::RtcIntPin;
+
+type Device = Ds323x<I2cInterface<I2cShared>, DS3231>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Alarm {
+    Alarm1,
+    Alarm2,
+}
+
+#[derive(Debug, defmt::Format, thiserror::Error)]
+pub enum Error {
+    /// Runner has not started yet
+    #[error("Not running")]
+    NotRunning,
+    #[error("Bus: {0:?}")]
+    Bus(I2cError),
+    /// Clock was not set or date-time is out of supported range
+    #[error("Invalid date-time")]
+    InvalidDateTime,
+}
+
+impl From<ds323x::Error<I2cError, ()>> for Error {
+    fn from(value: ds323x::Error<I2cError, ()>) -> Self {
+        match value {
+            ds323x::Error::Comm(err) => Self::Bus(err),
+            // Pin errors come from SPI chip select only, which I2C interface does not have
+            ds323x::Error::Pin(())
+            | ds323x::Error::InvalidInputData
+            | ds323x::Error::InvalidDeviceState => Self::InvalidDateTime,
+        }
+    }
+}
+
+pub struct Shared {
+    temperature: Mutex<CriticalSectionRawMutex, f32>,
+    device: Mutex<CriticalSectionRawMutex, Option<Device>>,
+    alarms: Channel<CriticalSectionRawMutex, Alarm, ALARM_QUEUE_SIZE>,
+}
+
+impl Shared {
+    pub fn new() -> Self {
+        Self {
+            temperature: Mutex::new(0.0),
+            device: Mutex::new(None),
+            alarms: Channel::new(),
+        }
+    }
+
+    async fn with_device<T>(
+        &self,
+        f: impl FnOnce(&mut Device) -> Result<T, ds323x::Error<I2cError, ()>>,
+    ) -> Result<T, Error> {
+        let mut device = self.device.lock().await;
+        let device = device.as_mut().ok_or(Error::NotRunning)?;
+        Ok(f(device)?)
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Ds3231<'a> {
+    shared: &'a Shared,
+}
+
+impl Ds3231<'_> {
+    /// Reads current date-time of the clock
+    pub async fn datetime(&self) -> Result<NaiveDateTime, Error> {
+        self.shared.with_device(|device| device.datetime()).await
+    }
+
+    /// Sets date-time of the clock
+    pub async fn set_datetime(&self, datetime: &NaiveDateTime) -> Result<(), Error> {
+        self.shared
+            .with_device(|device| device.set_datetime(datetime))
+            .await
+    }
+
+    /// Sets alarm 1 and enables its interrupt on INT/SQW output
+    pub async fn set_alarm1(&self, when: DayAlarm1, matching: Alarm1Matching) -> Result<(), Error> {
+        self.shared
+            .with_device(|device| {
+                device.set_alarm1_day(when, matching)?;
+                device.clear_alarm1_matched_flag()?;
+                device.use_int_sqw_output_as_interrupt()?;
+                device.enable_alarm1_interrupts()
+            })
+            .await
+    }
+
+    /// Sets alarm 2 and enables its interrupt on INT/SQW output
+    pub async fn set_alarm2(&self, when: DayAlarm2, matching: Alarm2Matching) -> Result<(), Error> {
+        self.shared
+            .with_device(|device| {
+                device.set_alarm2_day(when, matching)?;
+                device.clear_alarm2_matched_flag()?;
+                device.use_int_sqw_output_as_interrupt()?;
+                device.enable_alarm2_interrupts()
+            })
+            .await
+    }
+
+    /// Disables interrupt of alarm
+    pub async fn disable_alarm(&self, alarm: Alarm) -> Result<(), Error> {
+        self.shared
+            .with_device(|device| match alarm {
+                Alarm::Alarm1 => device.disable_alarm1_interrupts(),
+                Alarm::Alarm2 => device.disable_alarm2_interrupts(),
+            })
+            .await
+    }
+
+    /// Waits for next alarm
+    ///
+    /// # Note
+    /// Never returns if runner has no INT pin
+    pub async fn wait_alarm(&self) -> Alarm {
+        self.shared.alarms.receive().await
+    }
+
+    /// Reads and clears alarm flags, reporting matched alarms to waiters
+    async fn dispatch_alarms(&self) -> Result<(), Error> {
+        let (alarm1, alarm2) = self
+            .shared
+            .with_device(|device| {
+                let alarm1 = device.has_alarm1_matched()?;
+                if alarm1 {
+                    device.clear_alarm1_matched_flag()?;
+                }
+                let alarm2 = device.has_alarm2_matched()?;
+                if alarm2 {
+                    device.clear_alarm2_matched_flag()?;
+                }
+                Ok((alarm1, alarm2))
+            })
+            .await?;
+
+        let matched = [(alarm1, Alarm::Alarm1), (alarm2, Alarm::Alarm2)];
+        for (_, alarm) in matched.into_iter().filter(|(matched, _)| *matched) {
+            defmt::trace!("ds3231: {}", alarm);
+            // Nobody is waiting if queue is full, so event can be dropped
+            self.shared.alarms.try_send(alarm).ok();
+        }
+
+        Ok(())
+    }
+
+    /// Forces temperature conversion and returns new temperature
+    ///
+    /// Without forced conversion the temperature is updated by device every 64 seconds only
+    pub async fn measure_temperature(&self) -> Result<f32, Error> {
+        let busy = self.shared.with_device(|device| device.busy()).await?;
+        if !busy {
+            self.shared
+                .with_device(|device| device.convert_temperature())
+                .await?;
+        }
+
+        Timer::after(CONVERSION_TIME).await;
+
+        let temp = self
+            .shared
+            .with_device(|device| device.temperature())
+            .await?;
+        *self.shared.temperature.lock().await = temp;
+
+        Ok(temp)
+    }
+}
+
+impl TemperatureSensor for Ds3231<'_> {
+    async fn get_temperature(&self) -> f32 {
+        *self.shared.temperature.lock().await
+    }
+}
+
+pub struct Runner<'a> {
+    bus: I2cShared,
+    int_pin: Option<IntPin>,
+    shared: &'a Shared,
+}
+
+impl Runner<'_> {
+    /// Use INT/SQW pin to report alarms
+    pub fn with_int_pin(self, int_pin: IntPin) -> Self {
+        Self {
+            int_pin: Some(int_pin),
+            ..self
+        }
+    }
+
+    pub async fn run(mut self) -> ! {
+        let mut device = Ds323x::new_ds3231(self.bus);
+
+        if device.enable().is_err() {
+            defmt::error!("Failed to enable DS3231");
+        }
+
+        // Flags left from before reset keep INT low, so no edge would come
+        if device.clear_alarm1_matched_flag().is_err()
+            || device.clear_alarm2_matched_flag().is_err()
+        {
+            defmt::error!("Failed to clear DS3231 alarms");
+        }
+
+        *self.shared.device.lock().await = Some(device);
+
+        let handle = Ds3231 {
+            shared: self.shared,
+        };
+
+        let mut next_measurement = Instant::now();
+
+        loop {
+            if Instant::now() >= next_measurement {
+                match handle.measure_temperature().await {
+                    Ok(temp) => defmt::trace!("ds3231: temperature is {}", Temperature(temp)),
+                    Err(err) => defmt::error!("DS3231 error: {:?}", err),
+                }
+                next_measurement = Instant::now() + MEASUREMENT_INTERVAL;
+            }
+
+            let Some(int_pin) = self.int_pin.as_mut() else {
+                Timer::at(next_measurement).await;
+                continue;
+            };
+
+            // INT stays low while flag is set, so alarm matched while measuring or dispatching
+            // brings no edge and level is waited for instead
+            select(Timer::at(next_measurement), int_pin.wait_for_low()).await;
+
+            // Flags are read on timer as well, should an alarm be missed anyway
+            if let Err(err) = handle.dispatch_alarms().await {
+                defmt::error!("DS3231 alarm error: {:?}", err);
+            }
+            if int_pin.is_low() {
+                // Flags were not cleared, they are read again with next measurement
+                Timer::at(next_measurement).await;
+            }
+        }
+    }
+}
+
+pub fn new<'a>(bus: I2cShared, data: &'a Shared) -> (Ds3231<'a>, Runner<'a>) {
+    let runner = Runner {
+        bus,
+        int_pin: None,
+        shared: data,
+    };
+    (Ds3231 { shared: data }, runner)
+}
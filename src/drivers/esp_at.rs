@@ -0,0 +1,363 @@
+//!
+//! Driver of ESP8266/ESP32 Wi-Fi modem with AT firmware
+//!
+//! Modem is driven over any byte stream, so the driver can be exercised against a scripted
+//! fake on host. Broker connection uses the MQTT command set of ESP-AT, which opens the TCP
+//! connection itself.
+//!
+//! Responses are read line by line: command ends at `OK`, `ERROR` or `FAIL`, other lines like
+//! `WIFI GOT IP` are logged and skipped. Anything received before a command, like a late
+//! result of a command that timed out, is dropped, so it is not taken for the new result.
+//!
+
+use core::fmt::Write as _;
+
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::{Read, ReadReady, Write};
+use heapless::{String, Vec};
+
+/// Longest command, including quoted and escaped parameters
+const COMMAND_SIZE: usize = 256;
+
+/// Longer response lines are truncated
+const LINE_SIZE: usize = 128;
+
+/// Most commands answer right away
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Joining access point includes DHCP
+const JOIN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Connecting broker includes DNS lookup and TCP handshake
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Only one MQTT connection is used
+const LINK_ID: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum Error {
+    #[error("Serial port")]
+    Io,
+    /// Modem did not finish command in time
+    #[error("Timeout")]
+    Timeout,
+    /// Modem answered `ERROR` or `FAIL`
+    #[error("Rejected")]
+    Rejected,
+    /// Command with parameters does not fit buffer
+    #[error("Command too long")]
+    TooLong,
+}
+
+/// Kind of line sent by modem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Line {
+    Ok,
+    /// `ERROR` or `FAIL`
+    Failed,
+    /// Wi-Fi or broker connection was lost
+    Disconnected,
+    /// Echo, progress or result line
+    Other,
+}
+
+impl Line {
+    pub fn parse(line: &[u8]) -> Self {
+        match line {
+            b"OK" => Self::Ok,
+            b"ERROR" | b"FAIL" => Self::Failed,
+            b"WIFI DISCONNECT" => Self::Disconnected,
+            _ if line.starts_with(b"+MQTTDISCONNECTED") => Self::Disconnected,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Quality of service of published message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Qos {
+    AtMostOnce = 0,
+    AtLeastOnce = 1,
+}
+
+/// Credentials and identity used with broker
+#[derive(Debug, Clone, Copy)]
+pub struct MqttUser<'a> {
+    pub client_id: &'a str,
+    /// Empty if broker needs no authentication
+    pub username: &'a str,
+    pub password: &'a str,
+}
+
+pub struct Modem<IO> {
+    io: IO,
+    line: Vec<u8, LINE_SIZE>,
+    /// Set when modem reports lost connection, cleared by reconnecting
+    disconnected: bool,
+}
+
+impl<IO: Read + ReadReady + Write> Modem<IO> {
+    pub fn new(io: IO) -> Self {
+        Self {
+            io,
+            line: Vec::new(),
+            disconnected: false,
+        }
+    }
+
+    /// Whether modem reported lost Wi-Fi or broker connection since last connect
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    /// Checks modem answers and switches echo off
+    pub async fn init(&mut self) -> Result<(), Error> {
+        self.command(format_args!("AT"), COMMAND_TIMEOUT).await?;
+        self.command(format_args!("ATE0"), COMMAND_TIMEOUT).await
+    }
+
+    /// Joins access point as station
+    pub async fn join_wifi(&mut self, ssid: &str, password: &str) -> Result<(), Error> {
+        self.command(format_args!("AT+CWMODE=1"), COMMAND_TIMEOUT)
+            .await?;
+        self.command(
+            format_args!("AT+CWJAP={},{}", Quoted(ssid), Quoted(password)),
+            JOIN_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Connects broker, connection left from before is closed first
+    pub async fn mqtt_connect(
+        &mut self,
+        user: &MqttUser<'_>,
+        host: &str,
+        port: u16,
+    ) -> Result<(), Error> {
+        // Fails when there is nothing to close
+        self.command(format_args!("AT+MQTTCLEAN={}", LINK_ID), COMMAND_TIMEOUT)
+            .await
+            .ok();
+        // Scheme 1 is MQTT over TCP
+        self.command(
+            format_args!(
+                "AT+MQTTUSERCFG={},1,{},{},{},0,0,\"\"",
+                LINK_ID,
+                Quoted(user.client_id),
+                Quoted(user.username),
+                Quoted(user.password)
+            ),
+            COMMAND_TIMEOUT,
+        )
+        .await?;
+        // Reconnecting is left to caller
+        self.command(
+            format_args!("AT+MQTTCONN={},{},{},0", LINK_ID, Quoted(host), port),
+            CONNECT_TIMEOUT,
+        )
+        .await?;
+        self.disconnected = false;
+        Ok(())
+    }
+
+    pub async fn mqtt_publish(
+        &mut self,
+        topic: &str,
+        payload: &str,
+        qos: Qos,
+        retain: bool,
+    ) -> Result<(), Error> {
+        self.command(
+            format_args!(
+                "AT+MQTTPUB={},{},{},{},{}",
+                LINK_ID,
+                Quoted(topic),
+                Quoted(payload),
+                qos as u8,
+                u8::from(retain)
+            ),
+            COMMAND_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Sends command and waits for its final result
+    async fn command(
+        &mut self,
+        command: core::fmt::Arguments<'_>,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let mut buffer: String<COMMAND_SIZE> = String::new();
+        write!(buffer, "{}\r\n", command).map_err(|_| Error::TooLong)?;
+        self.drain().await?;
+        self.io
+            .write_all(buffer.as_bytes())
+            .await
+            .map_err(|_| Error::Io)?;
+
+        with_timeout(timeout, self.wait_result())
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    async fn wait_result(&mut self) -> Result<(), Error> {
+        loop {
+            match self.read_line().await? {
+                Line::Ok => return Ok(()),
+                Line::Failed => return Err(Error::Rejected),
+                Line::Disconnected => self.lose_connection(),
+                Line::Other => {}
+            }
+        }
+    }
+
+    /// Drops data received so far, only lost connection is noted
+    async fn drain(&mut self) -> Result<(), Error> {
+        let mut bytes = [0; LINE_SIZE];
+        while self.io.read_ready().map_err(|_| Error::Io)? {
+            let count = self.io.read(&mut bytes).await.map_err(|_| Error::Io)?;
+            for &byte in &bytes[..count] {
+                if self.push(byte) == Some(Line::Disconnected) {
+                    self.lose_connection();
+                }
+            }
+        }
+        self.line.clear();
+        Ok(())
+    }
+
+    /// Reads one non-empty line
+    async fn read_line(&mut self) -> Result<Line, Error> {
+        let mut byte = [0];
+        loop {
+            self.io.read_exact(&mut byte).await.map_err(|_| Error::Io)?;
+            if let Some(line) = self.push(byte[0]) {
+                return Ok(line);
+            }
+        }
+    }
+
+    /// Adds byte to current line, returns kind of line when it is complete
+    fn push(&mut self, byte: u8) -> Option<Line> {
+        match byte {
+            b'\r' => None,
+            b'\n' if self.line.is_empty() => None,
+            b'\n' => {
+                defmt::trace!("esp: {=[u8]:a}", self.line);
+                let line = Line::parse(&self.line);
+                self.line.clear();
+                Some(line)
+            }
+            // Rest of long line is dropped
+            byte => {
+                self.line.push(byte).ok();
+                None
+            }
+        }
+    }
+
+    fn lose_connection(&mut self) {
+        defmt::warn!("esp: connection lost");
+        self.disconnected = true;
+    }
+}
+
+/// String parameter of AT command, quoted with special characters escaped
+struct Quoted<'a>(&'a str);
+
+impl core::fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            if matches!(c, '"' | ',' | '\\') {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        f.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embassy_time::Instant;
+
+    use super::*;
+    use crate::testing::ScriptedSerial;
+
+    #[test]
+    fn times_out_without_result() {
+        let serial = ScriptedSerial::new(&[b"AT\r\n"]);
+        let mut modem = Modem::new(serial.clone());
+        let start = Instant::now();
+
+        assert_eq!(block_on(modem.init()), Err(Error::Timeout));
+        assert_eq!(Instant::now() - start, COMMAND_TIMEOUT);
+        assert_eq!(serial.commands(), ["AT"]);
+    }
+
+    #[test]
+    fn drops_late_result_before_command() {
+        let serial = ScriptedSerial::new(&[b"", b"\r\nERROR\r\n"]);
+        let mut modem = Modem::new(serial.clone());
+
+        assert_eq!(block_on(modem.init()), Err(Error::Timeout));
+        serial.inject(b"\r\nOK\r\n");
+        assert_eq!(
+            block_on(modem.mqtt_publish("t", "1", Qos::AtMostOnce, false)),
+            Err(Error::Rejected)
+        );
+    }
+
+    #[test]
+    fn notes_lost_broker_connection() {
+        let serial = ScriptedSerial::new(&[
+            b"\r\n+MQTTDISCONNECTED:0\r\n\r\nERROR\r\n",
+            b"\r\nOK\r\n",
+            b"\r\nOK\r\n",
+            b"\r\n+MQTTCONNECTED:0,1,\"broker\",\"1883\",\"\",1\r\n\r\nOK\r\n",
+        ]);
+        let mut modem = Modem::new(serial.clone());
+        let user = MqttUser {
+            client_id: "thermometer",
+            username: "",
+            password: "",
+        };
+
+        let result = block_on(modem.mqtt_publish("t", "1", Qos::AtMostOnce, true));
+        assert_eq!(result, Err(Error::Rejected));
+        assert!(modem.is_disconnected());
+
+        assert_eq!(block_on(modem.mqtt_connect(&user, "broker", 1883)), Ok(()));
+        assert!(!modem.is_disconnected());
+        assert_eq!(
+            serial.commands(),
+            [
+                "AT+MQTTPUB=0,\"t\",\"1\",0,1",
+                "AT+MQTTCLEAN=0",
+                "AT+MQTTUSERCFG=0,1,\"thermometer\",\"\",\"\",0,0,\"\"",
+                "AT+MQTTCONN=0,\"broker\",1883,0",
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_connection_lost_between_commands() {
+        let serial = ScriptedSerial::new(&[b"\r\nOK\r\n"]);
+        let mut modem = Modem::new(serial.clone());
+
+        serial.inject(b"WIFI DISCONNECT\r\n");
+        assert_eq!(block_on(modem.init()), Err(Error::Timeout));
+        assert!(modem.is_disconnected());
+    }
+
+    #[test]
+    fn escapes_parameters() {
+        let serial = ScriptedSerial::new(&[b"OK\r\n", b"OK\r\n"]);
+        let mut modem = Modem::new(serial.clone());
+
+        assert_eq!(block_on(modem.join_wifi("a,b", "p\"q\\")), Ok(()));
+        assert_eq!(serial.commands()[1], "AT+CWJAP=\"a\\,b\",\"p\\\"q\\\\\"");
+    }
+}
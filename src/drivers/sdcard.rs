@@ -0,0 +1,310 @@
+//!
+//! SD card driver in SPI mode
+//!
+//! Covers SDSC cards, addressed by byte, and SDHC/SDXC cards, addressed by block. Card has its
+//! own bus, since chip select has to stay low from command until its data is transferred and
+//! the clock has to drop to 400kHz while card is initialized.
+//!
+
+use core::future::Future;
+
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::{digital::OutputPin, spi::SpiBus};
+
+pub const BLOCK_SIZE: usize = 512;
+
+pub type Block = [u8; BLOCK_SIZE];
+
+const GO_IDLE_STATE: u8 = 0;
+const SEND_IF_COND: u8 = 8;
+const SET_BLOCKLEN: u8 = 16;
+const READ_SINGLE_BLOCK: u8 = 17;
+const WRITE_BLOCK: u8 = 24;
+const APP_CMD: u8 = 55;
+const READ_OCR: u8 = 58;
+const SD_SEND_OP_COND: u8 = 41;
+
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+/// 2.7-3.6V and check pattern
+const IF_COND: u32 = 0x1aa;
+/// Host supports high capacity cards
+const HCS: u32 = 1 << 30;
+/// Card capacity status in first byte of OCR
+const CCS: u8 = 0x40;
+
+const DATA_TOKEN: u8 = 0xfe;
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Card answers command within 8 bytes
+const RESPONSE_BYTES: usize = 10;
+/// Card leaves idle state within 1s
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum Error {
+    #[error("Bus")]
+    Bus,
+    /// Card does not answer, it is probably not inserted
+    #[error("No card")]
+    NoCard,
+    /// Card is not supported, e.g. MMC or its voltage range does not fit
+    #[error("Unsupported card")]
+    Unsupported,
+    /// Card rejected command, with its response
+    #[error("Command rejected: {0:#04x}")]
+    Command(u8),
+    /// Card did not send or accept data
+    #[error("Data")]
+    Data,
+    #[error("Timeout")]
+    Timeout,
+}
+
+/// Storage addressed by blocks of [BLOCK_SIZE] bytes
+pub trait BlockDevice {
+    /// Prepares device for access, needed again after it is replaced
+    fn init(&mut self) -> impl Future<Output = Result<(), Error>>;
+
+    fn read(&mut self, index: u32, block: &mut Block) -> impl Future<Output = Result<(), Error>>;
+
+    fn write(&mut self, index: u32, block: &Block) -> impl Future<Output = Result<(), Error>>;
+}
+
+/// SPI bus card is alone on
+pub trait CardBus: SpiBus {
+    /// Switches between 400kHz clock needed until card is initialized and full speed
+    fn set_init_clock(&mut self, init: bool);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Addressing {
+    /// SDSC cards take byte offset of block
+    Byte,
+    /// SDHC and SDXC cards take block index
+    Block,
+}
+
+pub struct SdCard<S, CS> {
+    bus: S,
+    cs: CS,
+    /// `None` until card is initialized
+    addressing: Option<Addressing>,
+}
+
+impl<S: CardBus, CS: OutputPin> SdCard<S, CS> {
+    pub fn new(bus: S, cs: CS) -> Self {
+        Self {
+            bus,
+            cs,
+            addressing: None,
+        }
+    }
+
+    async fn initialize(&mut self) -> Result<Addressing, Error> {
+        // Card enters SPI mode when it gets at least 74 clocks while not selected
+        self.cs.set_high().map_err(|_| Error::Bus)?;
+        self.bus.write(&[0xff; 10]).map_err(|_| Error::Bus)?;
+
+        self.select()?;
+        let mut r1 = 0xff;
+        for _ in 0..10 {
+            r1 = self.command(GO_IDLE_STATE, 0)?;
+            if r1 == R1_IDLE {
+                break;
+            }
+        }
+        if r1 != R1_IDLE {
+            return Err(Error::NoCard);
+        }
+
+        let version_2 = self.command(SEND_IF_COND, IF_COND)? & R1_ILLEGAL_COMMAND == 0;
+        if version_2 {
+            let mut r7 = [0xff; 4];
+            self.transfer(&mut r7)?;
+            if u32::from_be_bytes(r7) & 0xfff != IF_COND {
+                return Err(Error::Unsupported);
+            }
+        }
+
+        let deadline = Instant::now() + INIT_TIMEOUT;
+        loop {
+            self.command(APP_CMD, 0)?;
+            match self.command(SD_SEND_OP_COND, if version_2 { HCS } else { 0 })? {
+                0 => break,
+                R1_IDLE if Instant::now() < deadline => {
+                    self.deselect()?;
+                    Timer::after_millis(10).await;
+                    self.select()?;
+                }
+                R1_IDLE => return Err(Error::Timeout),
+                _ => return Err(Error::Unsupported),
+            }
+        }
+
+        let addressing = if version_2 {
+            check(self.command(READ_OCR, 0)?)?;
+            let mut ocr = [0xff; 4];
+            self.transfer(&mut ocr)?;
+            if ocr[0] & CCS != 0 {
+                Addressing::Block
+            } else {
+                Addressing::Byte
+            }
+        } else {
+            Addressing::Byte
+        };
+        if addressing == Addressing::Byte {
+            check(self.command(SET_BLOCKLEN, BLOCK_SIZE as u32)?)?;
+        }
+        Ok(addressing)
+    }
+
+    fn address(&self, index: u32) -> Result<u32, Error> {
+        match self.addressing.ok_or(Error::NoCard)? {
+            Addressing::Byte => index.checked_mul(BLOCK_SIZE as u32).ok_or(Error::Data),
+            Addressing::Block => Ok(index),
+        }
+    }
+
+    fn read_block(&mut self, address: u32, block: &mut Block) -> Result<(), Error> {
+        check(self.command(READ_SINGLE_BLOCK, address)?)?;
+
+        let deadline = Instant::now() + READ_TIMEOUT;
+        let token = loop {
+            let byte = self.read_byte()?;
+            if byte != 0xff {
+                break byte;
+            }
+            if Instant::now() > deadline {
+                return Err(Error::Timeout);
+            }
+        };
+        if token != DATA_TOKEN {
+            return Err(Error::Data);
+        }
+
+        block.fill(0xff);
+        self.transfer(block)?;
+        // CRC is not checked in SPI mode
+        self.transfer(&mut [0xff; 2])
+    }
+
+    async fn write_block(&mut self, address: u32, block: &Block) -> Result<(), Error> {
+        check(self.command(WRITE_BLOCK, address)?)?;
+
+        self.bus
+            .write(&[0xff, DATA_TOKEN])
+            .map_err(|_| Error::Bus)?;
+        self.bus.write(block).map_err(|_| Error::Bus)?;
+        self.bus.write(&[0xff; 2]).map_err(|_| Error::Bus)?;
+        if self.read_byte()? & 0x1f != DATA_ACCEPTED {
+            return Err(Error::Data);
+        }
+
+        // Card holds data line low while it programs the block
+        let deadline = Instant::now() + WRITE_TIMEOUT;
+        while self.read_byte()? != 0xff {
+            if Instant::now() > deadline {
+                return Err(Error::Timeout);
+            }
+            Timer::after(BUSY_POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+    /// Sends command and returns its R1 response, card has to be selected
+    fn command(&mut self, command: u8, argument: u32) -> Result<u8, Error> {
+        // CRC is checked only until card is in SPI mode, CMD8 is checked always
+        let crc = match command {
+            GO_IDLE_STATE => 0x95,
+            SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        let [a, b, c, d] = argument.to_be_bytes();
+        self.bus
+            .write(&[0xff, 0x40 | command, a, b, c, d, crc])
+            .map_err(|_| Error::Bus)?;
+
+        for _ in 0..RESPONSE_BYTES {
+            let r1 = self.read_byte()?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(Error::NoCard)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut byte = [0xff];
+        self.transfer(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Reads while sending ones, as card expects
+    fn transfer(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.bus.transfer_in_place(buffer).map_err(|_| Error::Bus)
+    }
+
+    fn select(&mut self) -> Result<(), Error> {
+        self.cs.set_low().map_err(|_| Error::Bus)
+    }
+
+    /// Releases card, which lets go of data line on the next clock
+    fn deselect(&mut self) -> Result<(), Error> {
+        self.cs.set_high().map_err(|_| Error::Bus)?;
+        self.bus.write(&[0xff]).map_err(|_| Error::Bus)
+    }
+
+    /// Runs operation with card selected, card is released after errors too
+    fn selected<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.select()?;
+        let result = operation(self);
+        self.deselect().and(result)
+    }
+}
+
+impl<S: CardBus, CS: OutputPin> BlockDevice for SdCard<S, CS> {
+    async fn init(&mut self) -> Result<(), Error> {
+        self.addressing = None;
+        self.bus.set_init_clock(true);
+        let result = self.initialize().await;
+        self.deselect()?;
+        self.bus.set_init_clock(false);
+
+        let addressing = result?;
+        defmt::info!(
+            "sdcard: initialized, addressed by block {=bool}",
+            addressing == Addressing::Block
+        );
+        self.addressing = Some(addressing);
+        Ok(())
+    }
+
+    async fn read(&mut self, index: u32, block: &mut Block) -> Result<(), Error> {
+        let address = self.address(index)?;
+        self.selected(|card| card.read_block(address, block))
+    }
+
+    async fn write(&mut self, index: u32, block: &Block) -> Result<(), Error> {
+        let address = self.address(index)?;
+        self.select()?;
+        let result = self.write_block(address, block).await;
+        self.deselect().and(result)
+    }
+}
+
+/// Accepts R1 of card ready for data commands
+fn check(r1: u8) -> Result<(), Error> {
+    match r1 {
+        0 => Ok(()),
+        r1 => Err(Error::Command(r1)),
+    }
+}
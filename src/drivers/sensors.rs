@@ -1,4 +1,5 @@
 pub mod humidity;
+pub mod status;
 pub mod temperature;
 
 pub mod dht22;
@@ -1,14 +1,21 @@
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use embassy_time::{Delay, Duration, Timer};
-use embedded_hal::delay::DelayNs;
+use embassy_time::Duration;
 
-use crate::{
-    bsp::DhtSingleWirePin,
-    drivers::sensors::{humidity::HumiditySensor, temperature::TemperatureSensor},
+use crate::drivers::sensors::{
+    humidity::HumiditySensor,
+    status::{SensorStatus, StatusSensor},
+    temperature::TemperatureSensor,
+};
+#[cfg(feature = "board")]
+use {
+    crate::{bsp::DhtSingleWirePin, watchdog::CheckIn},
+    embassy_time::{Delay, Timer},
+    embedded_hal::delay::DelayNs,
 };
 
 pub const MEASUREMENT_INTERVAL: Duration = Duration::from_millis(1000);
 
+#[cfg(feature = "board")]
 #[derive(Debug, defmt::Format, thiserror::Error)]
 enum Error {
     #[error("Timeout")]
@@ -20,6 +27,9 @@ enum Error {
 pub struct Shared {
     temperature: Mutex<CriticalSectionRawMutex, f32>,
     humidity: Mutex<CriticalSectionRawMutex, f32>,
+    status: Mutex<CriticalSectionRawMutex, SensorStatus>,
+    errors: Mutex<CriticalSectionRawMutex, u32>,
+    interval: Mutex<CriticalSectionRawMutex, Duration>,
 }
 
 impl Shared {
@@ -27,15 +37,36 @@ impl Shared {
         Self {
             temperature: Mutex::new(0.0),
             humidity: Mutex::new(100.0),
+            status: Mutex::new(SensorStatus::Unknown),
+            errors: Mutex::new(0),
+            interval: Mutex::new(MEASUREMENT_INTERVAL),
         }
     }
 }
 
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Dht22<'a> {
     shared: &'a Shared,
 }
 
+impl Dht22<'_> {
+    pub async fn measurement_interval(&self) -> Duration {
+        *self.shared.interval.lock().await
+    }
+
+    /// Changes time between readings, applied after the next reading
+    pub async fn set_measurement_interval(&self, interval: Duration) {
+        *self.shared.interval.lock().await = interval;
+        defmt::info!("dht22: measurement interval {}ms", interval.as_millis());
+    }
+}
+
 impl TemperatureSensor for Dht22<'_> {
     async fn get_temperature(&self) -> f32 {
         *self.shared.temperature.lock().await
@@ -48,20 +79,51 @@ impl HumiditySensor for Dht22<'_> {
     }
 }
 
+impl StatusSensor for Dht22<'_> {
+    async fn get_status(&self) -> SensorStatus {
+        *self.shared.status.lock().await
+    }
+
+    async fn get_error_count(&self) -> u32 {
+        *self.shared.errors.lock().await
+    }
+}
+
+#[cfg(feature = "board")]
 pub struct Runner<'a> {
     dht_pin: crate::bsp::DhtSingleWirePin,
     delay: Delay,
+    watchdog: Option<CheckIn<'a>>,
     shared: &'a Shared,
 }
 
-impl Runner<'_> {
+#[cfg(feature = "board")]
+impl<'a> Runner<'a> {
+    /// Check in with watchdog on every reading
+    pub fn with_watchdog(self, watchdog: CheckIn<'a>) -> Self {
+        Self {
+            watchdog: Some(watchdog),
+            ..self
+        }
+    }
+
     pub async fn run(mut self) -> ! {
         loop {
-            if let Err(err) = self.read().await {
-                defmt::error!("DHT22 error: {:?}", err);
+            let status = match self.read().await {
+                Ok(()) => SensorStatus::Ok,
+                Err(err) => {
+                    defmt::error!("DHT22 error: {:?}", err);
+                    *self.shared.errors.lock().await += 1;
+                    SensorStatus::Error
+                }
+            };
+            *self.shared.status.lock().await = status;
+
+            let interval = *self.shared.interval.lock().await;
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.check_in(interval);
             }
-
-            Timer::after(MEASUREMENT_INTERVAL).await;
+            Timer::after(interval).await;
         }
     }
 
@@ -156,11 +218,13 @@ impl Runner<'_> {
     }
 }
 
+#[cfg(feature = "board")]
 pub fn new<'a>(dht_pin: DhtSingleWirePin, data: &'a Shared) -> (Dht22<'a>, Runner<'a>) {
     let runner = Runner {
         dht_pin,
         shared: data,
         delay: Delay,
+        watchdog: None,
     };
     (Dht22 { shared: data }, runner)
 }
@@ -4,65 +4,405 @@
 //! LM75 sensor driver requires 2 things to work with:
 //! the data which will be used to
 //!
+//! The overtemperature shutdown (OS) output can be wired to an EXTI pin with
+//! [Runner::with_os_pin], then changes of OS state are reported with [Lm75::wait_os_event]
+//! instead of polling the temperature against a threshold.
+//!
+//! Task can be supervised by [watchdog](crate::watchdog) with [Runner::with_watchdog].
+//!
+//! Configuration can be changed at runtime with [Lm75::set_config], sensor is reconfigured
+//! before the next reading.
+//!
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::Duration;
+
+use crate::{
+    drivers::sensors::{
+        status::{SensorStatus, StatusSensor},
+        temperature::TemperatureSensor,
+    },
+    units::TemperatureUnit,
+};
+#[cfg(feature = "board")]
+use {
+    crate::{bsp::I2cError, units::Temperature, watchdog::CheckIn},
+    embassy_futures::select::{select, select3, Either, Either3},
+    embassy_time::Timer,
+};
 
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use embassy_time::{Duration, Timer};
+pub use lm75::{FaultQueue, OsMode, OsPolarity};
 
-use crate::drivers::sensors::temperature::TemperatureSensor;
+/// Address with A0..A2 pins tied low
+pub const DEFAULT_ADDRESS: u8 = 0x48;
+
+/// Addresses selectable with A0..A2 pins
+pub const ADDRESSES: core::ops::RangeInclusive<u8> = 0x48..=0x4f;
 
 pub const MEASUREMENT_INTERVAL: Duration = Duration::from_millis(100);
 
+/// Time needed by sensor to make one conversion after leaving shutdown
+pub const CONVERSION_TIME: Duration = Duration::from_millis(100);
+
+#[cfg(feature = "board")]
+/// Range of idle period supported by PCT2075, in milliseconds
+const SAMPLE_PERIOD_RANGE_MS: core::ops::RangeInclusive<u64> = 100..=3100;
+
+/// Whether thresholds can be programmed, hysteresis has to be below OS temperature
+pub fn valid_thresholds(os_temperature: f32, hysteresis_temperature: f32) -> bool {
+    os_temperature.is_finite()
+        && hysteresis_temperature.is_finite()
+        && hysteresis_temperature < os_temperature
+}
+
+/// Pin connected to OS output of the sensor
+#[cfg(feature = "board")]
+pub type OsPin = crate::bsp::Lm75OsPin;
+
+/// How sensor is kept between measurements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PowerMode {
+    /// Sensor converts all the time
+    Continuous,
+    /// Sensor is in shutdown between measurements and enabled for one conversion only
+    ///
+    /// # Note
+    /// OS output is not updated while sensor is in shutdown
+    Shutdown,
+    /// PCT2075 idle period is programmed to match measurement interval
+    SamplePeriod,
+}
+
+/// Configuration of the sensor
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// I2C address set by A0..A2 pins, used from start of runner
+    pub address: u8,
+    /// How often temperature is read
+    pub measurement_interval: Duration,
+    pub power_mode: PowerMode,
+    /// Temperature when OS output becomes active, in Celsius
+    pub os_temperature: f32,
+    /// Temperature when OS output becomes inactive again, in Celsius
+    pub hysteresis_temperature: f32,
+    /// Number of consecutive faults needed to change OS output
+    pub fault_queue: FaultQueue,
+    pub os_polarity: OsPolarity,
+    pub os_mode: OsMode,
+}
+
+impl Config {
+    /// Sets OS and hysteresis temperatures given in unit, e.g. as entered by user
+    pub fn with_thresholds(self, os: f32, hysteresis: f32, unit: TemperatureUnit) -> Self {
+        Self {
+            os_temperature: unit.to_celsius(os),
+            hysteresis_temperature: unit.to_celsius(hysteresis),
+            ..self
+        }
+    }
+}
+
+impl Default for Config {
+    /// Power-on defaults of the sensor
+    fn default() -> Self {
+        Self {
+            address: DEFAULT_ADDRESS,
+            measurement_interval: MEASUREMENT_INTERVAL,
+            power_mode: PowerMode::Continuous,
+            os_temperature: 80.0,
+            hysteresis_temperature: 75.0,
+            fault_queue: FaultQueue::_1,
+            os_polarity: OsPolarity::ActiveLow,
+            os_mode: OsMode::Comparator,
+        }
+    }
+}
+
+/// Change of overtemperature state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum OsEvent {
+    /// Temperature is above OS temperature
+    OverTemperature,
+    /// Temperature went below hysteresis temperature
+    Normal,
+}
+
 pub struct Shared {
     temperature: Mutex<CriticalSectionRawMutex, f32>,
+    status: Mutex<CriticalSectionRawMutex, SensorStatus>,
+    errors: Mutex<CriticalSectionRawMutex, u32>,
+    os_event: Signal<CriticalSectionRawMutex, OsEvent>,
+    config: Mutex<CriticalSectionRawMutex, Config>,
+    reconfigure: Signal<CriticalSectionRawMutex, ()>,
 }
 
 impl Shared {
     pub fn new() -> Self {
         Self {
             temperature: Mutex::new(0.0),
+            status: Mutex::new(SensorStatus::Unknown),
+            errors: Mutex::new(0),
+            os_event: Signal::new(),
+            config: Mutex::new(Config::default()),
+            reconfigure: Signal::new(),
         }
     }
 }
 
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Lm75<'a> {
     shared: &'a Shared,
 }
 
+impl Lm75<'_> {
+    /// Waits for the next change of overtemperature state
+    ///
+    /// # Note
+    /// Never returns if runner has no OS pin
+    pub async fn wait_os_event(&self) -> OsEvent {
+        self.shared.os_event.wait().await
+    }
+
+    /// Configuration used by the sensor
+    pub async fn config(&self) -> Config {
+        *self.shared.config.lock().await
+    }
+
+    /// Applies configuration before the next reading
+    pub async fn set_config(&self, config: Config) {
+        *self.shared.config.lock().await = config;
+        self.shared.reconfigure.signal(());
+    }
+}
+
 impl TemperatureSensor for Lm75<'_> {
     async fn get_temperature(&self) -> f32 {
         *self.shared.temperature.lock().await
     }
 }
 
+impl StatusSensor for Lm75<'_> {
+    async fn get_status(&self) -> SensorStatus {
+        *self.shared.status.lock().await
+    }
+
+    async fn get_error_count(&self) -> u32 {
+        *self.shared.errors.lock().await
+    }
+}
+
+#[cfg(feature = "board")]
+type Sensor = lm75::Lm75<crate::bsp::I2cShared, lm75::ic::Pct2075>;
+
+#[cfg(feature = "board")]
 pub struct Runner<'a> {
     bus: crate::bsp::I2cShared,
+    config: Config,
+    os_pin: Option<OsPin>,
+    watchdog: Option<CheckIn<'a>>,
     shared: &'a Shared,
 }
 
+#[cfg(feature = "board")]
 impl<'a> Runner<'a> {
-    pub async fn run(self) -> ! {
-        let mut sensor = lm75::Lm75::new_pct2075(self.bus, lm75::Address::from(0x48));
+    /// Use OS pin to report overtemperature events
+    pub fn with_os_pin(self, os_pin: OsPin) -> Self {
+        Self {
+            os_pin: Some(os_pin),
+            ..self
+        }
+    }
+
+    /// Check in with watchdog on every reading
+    pub fn with_watchdog(self, watchdog: CheckIn<'a>) -> Self {
+        Self {
+            watchdog: Some(watchdog),
+            ..self
+        }
+    }
+
+    pub async fn run(mut self) -> ! {
+        let mut sensor: Sensor =
+            lm75::Lm75::new_pct2075(self.bus, lm75::Address::from(self.config.address));
 
         if sensor.enable().is_err() {
             defmt::error!("Failed to enable LM75B sensor");
         }
 
+        *self.shared.config.lock().await = self.config;
+        Self::apply_config(&mut sensor, &self.config);
+
         Timer::after_ticks(0).await; // Let others do the job
 
+        let mut over_temperature = false;
+
         loop {
-            if let Ok(temp) = sensor.read_temperature() {
-                defmt::trace!("lm75b: temperature is {}", temp);
-                let mut out_temp = self.shared.temperature.lock().await;
-                *out_temp = temp;
+            let interval = self.config.measurement_interval;
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.check_in(interval);
+            }
+            let measured = Self::measure(&mut sensor, self.config.power_mode).await;
+            let status = match measured {
+                Ok(temp) => {
+                    defmt::trace!("lm75b: temperature is {}", Temperature(temp));
+                    let mut out_temp = self.shared.temperature.lock().await;
+                    *out_temp = temp;
+                    SensorStatus::Ok
+                }
+                Err(_) => {
+                    *self.shared.errors.lock().await += 1;
+                    SensorStatus::Error
+                }
+            };
+            *self.shared.status.lock().await = status;
+
+            // State is checked after every reading, so OS output active since start, e.g. when
+            // board starts hot, is reported although it has no edge
+            if let Some(os_pin) = &self.os_pin {
+                Self::update_os_state(
+                    os_pin,
+                    &self.config,
+                    self.shared,
+                    measured.ok(),
+                    &mut over_temperature,
+                );
             }
 
-            Timer::after(MEASUREMENT_INTERVAL).await;
+            let reconfigure = self.shared.reconfigure.wait();
+            let Some(os_pin) = self.os_pin.as_mut() else {
+                if let Either::Second(()) = select(Timer::after(interval), reconfigure).await {
+                    self.config = *self.shared.config.lock().await;
+                    Self::apply_config(&mut sensor, &self.config);
+                }
+                continue;
+            };
+
+            // Edge only wakes runner, state is checked after reading taken right away
+            let woken = select3(
+                Timer::after(interval),
+                os_pin.wait_for_any_edge(),
+                reconfigure,
+            )
+            .await;
+            if let Either3::Third(()) = woken {
+                self.config = *self.shared.config.lock().await;
+                Self::apply_config(&mut sensor, &self.config);
+            }
         }
     }
+
+    /// Signals OS event when over-temperature state differs from reported one
+    ///
+    /// Comparator output follows the state, so its level is used. Interrupt output is active
+    /// once per crossing until any register is read, so the state is derived from temperature
+    /// read successfully instead and left as is without one.
+    fn update_os_state(
+        os_pin: &OsPin,
+        config: &Config,
+        shared: &Shared,
+        temperature: Option<f32>,
+        over_temperature: &mut bool,
+    ) {
+        let state = match (config.os_mode, temperature) {
+            (OsMode::Comparator, _) => match config.os_polarity {
+                OsPolarity::ActiveLow => os_pin.is_low(),
+                OsPolarity::ActiveHigh => os_pin.is_high(),
+            },
+            (OsMode::Interrupt, Some(temp)) if temp > config.os_temperature => true,
+            (OsMode::Interrupt, Some(temp)) if temp < config.hysteresis_temperature => false,
+            (OsMode::Interrupt, _) => *over_temperature,
+        };
+        if state == *over_temperature {
+            return;
+        }
+        *over_temperature = state;
+
+        let event = if state {
+            OsEvent::OverTemperature
+        } else {
+            OsEvent::Normal
+        };
+        defmt::debug!("lm75b: OS event {}", event);
+        shared.os_event.signal(event);
+    }
+
+    fn apply_config(sensor: &mut Sensor, config: &Config) {
+        if Self::configure(sensor, config).is_err() {
+            defmt::error!("Failed to configure LM75B sensor");
+        } else {
+            defmt::info!(
+                "lm75b: OS above {}, released below {}",
+                Temperature(config.os_temperature),
+                Temperature(config.hysteresis_temperature)
+            );
+        }
+
+        if Self::configure_power(sensor, config).is_err() {
+            defmt::error!("Failed to set LM75B power mode");
+        }
+    }
+
+    async fn measure(
+        sensor: &mut Sensor,
+        power_mode: PowerMode,
+    ) -> Result<f32, lm75::Error<I2cError>> {
+        if power_mode != PowerMode::Shutdown {
+            return sensor.read_temperature();
+        }
+
+        sensor.enable()?;
+        Timer::after(CONVERSION_TIME).await;
+        let temp = sensor.read_temperature();
+        sensor.disable()?;
+
+        temp
+    }
+
+    fn configure_power(sensor: &mut Sensor, config: &Config) -> Result<(), lm75::Error<I2cError>> {
+        // Sensor may be in shutdown when configuration changes at runtime
+        match config.power_mode {
+            PowerMode::Continuous => sensor.enable(),
+            PowerMode::Shutdown => sensor.disable(),
+            PowerMode::SamplePeriod => {
+                sensor.enable()?;
+                let period_ms = config.measurement_interval.as_millis().clamp(
+                    *SAMPLE_PERIOD_RANGE_MS.start(),
+                    *SAMPLE_PERIOD_RANGE_MS.end(),
+                );
+                // Sensor accepts multiples of 100ms only
+                sensor.set_sample_rate((period_ms - period_ms % 100) as u16)
+            }
+        }
+    }
+
+    fn configure(sensor: &mut Sensor, config: &Config) -> Result<(), lm75::Error<I2cError>> {
+        sensor.set_os_temperature(config.os_temperature)?;
+        sensor.set_hysteresis_temperature(config.hysteresis_temperature)?;
+        sensor.set_fault_queue(config.fault_queue)?;
+        sensor.set_os_polarity(config.os_polarity)?;
+        sensor.set_os_mode(config.os_mode)?;
+        Ok(())
+    }
 }
 
-pub fn new<'a>(bus: crate::bsp::I2cShared, data: &'a Shared) -> (Lm75<'a>, Runner<'a>) {
-    let runner = Runner { bus, shared: data };
+#[cfg(feature = "board")]
+pub fn new<'a>(
+    bus: crate::bsp::I2cShared,
+    config: Config,
+    data: &'a Shared,
+) -> (Lm75<'a>, Runner<'a>) {
+    let runner = Runner {
+        bus,
+        config,
+        os_pin: None,
+        watchdog: None,
+        shared: data,
+    };
     (Lm75 { shared: data }, runner)
 }
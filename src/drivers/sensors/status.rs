@@ -0,0 +1,19 @@
+use core::future::Future;
+
+/// State of the last reading of sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SensorStatus {
+    /// Nothing was read yet
+    Unknown,
+    Ok,
+    /// Last reading failed, previous value is kept
+    Error,
+}
+
+pub trait StatusSensor {
+    /// Gets state of the last reading
+    fn get_status(&self) -> impl Future<Output = SensorStatus>;
+
+    /// Gets number of failed readings since start
+    fn get_error_count(&self) -> impl Future<Output = u32>;
+}
@@ -0,0 +1,139 @@
+//!
+//! History of fused temperature and humidity
+//!
+//! Measurements are averaged over [POINT_INTERVAL] so a few hours fit into small ring buffers.
+//!
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
+use heapless::HistoryBuffer;
+
+use crate::measurement::{Measurement, MeasurementChannel};
+
+/// Number of points kept for each series
+pub const HISTORY_SIZE: usize = 96;
+
+/// Time covered by one point
+pub const POINT_INTERVAL: Duration = Duration::from_secs(150);
+
+/// Number of points in one hour
+pub const POINTS_PER_HOUR: usize = (60 * 60 / POINT_INTERVAL.as_secs()) as usize;
+
+pub type Series = HistoryBuffer<f32, HISTORY_SIZE>;
+
+/// Averaged points of history
+#[derive(Clone, Default)]
+pub struct HistoryData {
+    pub temperature: Series,
+    pub humidity: Series,
+}
+
+impl HistoryData {
+    pub const fn new() -> Self {
+        Self {
+            temperature: HistoryBuffer::new(),
+            humidity: HistoryBuffer::new(),
+        }
+    }
+}
+
+/// Sums of measurements for point being collected
+#[derive(Default)]
+struct Accumulator {
+    temperature: f32,
+    /// Measurements with temperature, which is NaN when no sensor was read
+    temperature_count: u32,
+    humidity: f32,
+    count: u32,
+}
+
+impl Accumulator {
+    fn add(&mut self, measurement: &Measurement) {
+        if measurement.temperature.is_finite() {
+            self.temperature += measurement.temperature;
+            self.temperature_count += 1;
+        }
+        self.humidity += measurement.humidity;
+        self.count += 1;
+    }
+
+    /// Writes average to history and starts new point
+    fn flush_into(&mut self, data: &mut HistoryData) {
+        if self.count == 0 {
+            return;
+        }
+
+        if self.temperature_count > 0 {
+            data.temperature
+                .write(self.temperature / self.temperature_count as f32);
+        }
+        data.humidity.write(self.humidity / self.count as f32);
+        *self = Self::default();
+    }
+}
+
+pub struct Shared {
+    data: Mutex<CriticalSectionRawMutex, HistoryData>,
+}
+
+impl Shared {
+    pub const fn new() -> Self {
+        Self {
+            data: Mutex::new(HistoryData::new()),
+        }
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct History<'a> {
+    shared: &'a Shared,
+}
+
+impl History<'_> {
+    /// Gives access to history while it is not updated
+    pub async fn with_data<R>(&self, f: impl FnOnce(&HistoryData) -> R) -> R {
+        let data = self.shared.data.lock().await;
+        f(&data)
+    }
+}
+
+pub struct Runner<'a> {
+    measurements: &'a MeasurementChannel,
+    shared: &'a Shared,
+}
+
+impl Runner<'_> {
+    pub async fn run(self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+        let mut accumulator = Accumulator::default();
+        let mut point_end = Instant::now() + POINT_INTERVAL;
+
+        loop {
+            let measurement = subscriber.next_message_pure().await;
+            accumulator.add(&measurement);
+
+            if Instant::now() >= point_end {
+                let mut data = self.shared.data.lock().await;
+                accumulator.flush_into(&mut data);
+                point_end = Instant::now() + POINT_INTERVAL;
+            }
+        }
+    }
+}
+
+pub fn new<'a>(
+    measurements: &'a MeasurementChannel,
+    data: &'a Shared,
+) -> (History<'a>, Runner<'a>) {
+    let runner = Runner {
+        measurements,
+        shared: data,
+    };
+    (History { shared: data }, runner)
+}
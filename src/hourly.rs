@@ -0,0 +1,522 @@
+//!
+//! Hourly summaries of measurements kept in EEPROM
+//!
+//! Minimum, maximum and mean of combined temperature and humidity are collected for every
+//! hour from sensors read successfully and written to [AT24Cxx](crate::drivers::at24cxx)
+//! EEPROM as a ring of one-page records, so a few days of history survive without flash
+//! wear. Hours follow UTC once clock is synchronized, before that they are counted from start.
+//!
+//! Record layout, little-endian:
+//!
+//! | Offset | Size | Value |
+//! |---|---|---|
+//! | 0 | 4 | sequence number |
+//! | 4 | 4 | UTC unix time of start of hour, 0 if clock was not synchronized |
+//! | 8 | 2 | number of measurements |
+//! | 10 | 6 | min, max and mean temperature in hundredths of Celsius, `0x8000` if not read |
+//! | 16 | 6 | min, max and mean humidity in tenths of %, `0xffff` if not read |
+//! | 22 | 6 | reserved |
+//! | 28 | 4 | CRC-32 of all above |
+//!
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::Instant;
+#[cfg_attr(not(target_os = "none"), allow(unused_imports))]
+use num_traits::float::FloatCore;
+
+use crate::{
+    config::store::crc32,
+    drivers::{
+        at24cxx::{Eeprom, Error},
+        sensors::status::SensorStatus,
+    },
+    measurement::{Measurement, MeasurementChannel, SensorId},
+};
+
+/// One page of AT24C32
+pub const RECORD_SIZE: usize = 32;
+
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+const CRC_OFFSET: usize = RECORD_SIZE - 4;
+
+/// Stored in place of temperatures of an hour without any sensor read
+const NO_TEMPERATURE: i16 = i16::MIN;
+/// Stored in place of humidity of an hour without DHT22 read
+const NO_HUMIDITY: u16 = u16::MAX;
+
+/// Log shared by runner and readers
+pub type SharedHourlyLog<E> = Mutex<CriticalSectionRawMutex, HourlyLog<E>>;
+
+/// Hour measurement belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hour {
+    /// UTC unix time of start
+    Utc(u32),
+    /// Hours since start, while clock is not synchronized
+    Uptime(u64),
+}
+
+impl Hour {
+    fn of(measurement: &Measurement) -> Self {
+        match measurement.timestamp {
+            Some(timestamp) => {
+                let seconds = timestamp.0.timestamp() as u64;
+                Hour::Utc((seconds - seconds % SECONDS_PER_HOUR) as u32)
+            }
+            None => Hour::Uptime(Instant::now().as_secs() / SECONDS_PER_HOUR),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl Range {
+    /// Minimum, maximum and mean as stored, all `None` if nothing was collected
+    pub fn values(range: Option<Self>) -> [Option<f32>; 3] {
+        match range {
+            Some(range) => [Some(range.min), Some(range.max), Some(range.mean)],
+            None => [None; 3],
+        }
+    }
+
+    fn from_values(values: [Option<f32>; 3]) -> Option<Self> {
+        let [min, max, mean] = values;
+        Some(Self {
+            min: min?,
+            max: max?,
+            mean: mean?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// UTC unix time of start of hour, `None` if clock was not synchronized
+    pub start: Option<u32>,
+    /// Number of measurements taken during the hour
+    pub samples: u16,
+    /// Combined temperature in Celsius, `None` if no sensor was read
+    pub temperature: Option<Range>,
+    /// Relative humidity in %, `None` if DHT22 was not read
+    pub humidity: Option<Range>,
+}
+
+impl Summary {
+    fn encode(&self, sequence: u32) -> [u8; RECORD_SIZE] {
+        let mut record = [0xff; RECORD_SIZE];
+        record[0..4].copy_from_slice(&sequence.to_le_bytes());
+        record[4..8].copy_from_slice(&self.start.unwrap_or(0).to_le_bytes());
+        record[8..10].copy_from_slice(&self.samples.to_le_bytes());
+
+        let (values, _) = record[10..16].as_chunks_mut::<2>();
+        for (bytes, value) in values.iter_mut().zip(Range::values(self.temperature)) {
+            let centi = value.map_or(NO_TEMPERATURE, |value| (value * 100.0).round() as i16);
+            *bytes = centi.to_le_bytes();
+        }
+        let (values, _) = record[16..22].as_chunks_mut::<2>();
+        for (bytes, value) in values.iter_mut().zip(Range::values(self.humidity)) {
+            let deci = value.map_or(NO_HUMIDITY, |value| (value * 10.0).round() as u16);
+            *bytes = deci.to_le_bytes();
+        }
+
+        let crc = crc32(&record[..CRC_OFFSET]);
+        record[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    /// Decodes record with valid CRC, returns its sequence number too
+    fn decode(record: &[u8; RECORD_SIZE]) -> Option<(u32, Self)> {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                record[offset],
+                record[offset + 1],
+                record[offset + 2],
+                record[offset + 3],
+            ])
+        };
+        if word(CRC_OFFSET) != crc32(&record[..CRC_OFFSET]) {
+            return None;
+        }
+
+        let half = |offset: usize| [record[offset], record[offset + 1]];
+        let temperature = |offset| {
+            let centi = i16::from_le_bytes(half(offset));
+            (centi != NO_TEMPERATURE).then(|| f32::from(centi) / 100.0)
+        };
+        let humidity = |offset| {
+            let deci = u16::from_le_bytes(half(offset));
+            (deci != NO_HUMIDITY).then(|| f32::from(deci) / 10.0)
+        };
+        let start = word(4);
+
+        Some((
+            word(0),
+            Self {
+                start: (start != 0).then_some(start),
+                samples: u16::from_le_bytes(half(8)),
+                temperature: Range::from_values([
+                    temperature(10),
+                    temperature(12),
+                    temperature(14),
+                ]),
+                humidity: Range::from_values([humidity(16), humidity(18), humidity(20)]),
+            },
+        ))
+    }
+}
+
+/// Minimum, maximum, sum and number of values collected
+#[derive(Debug, Clone, Copy)]
+struct Collected {
+    min: f32,
+    max: f32,
+    sum: f32,
+    count: u16,
+}
+
+impl Collected {
+    fn add(collected: &mut Option<Self>, value: Option<f32>) {
+        let Some(value) = value else {
+            return;
+        };
+        match collected {
+            Some(collected) => {
+                collected.min = collected.min.min(value);
+                collected.max = collected.max.max(value);
+                collected.sum += value;
+                collected.count = collected.count.saturating_add(1);
+            }
+            None => {
+                *collected = Some(Self {
+                    min: value,
+                    max: value,
+                    sum: value,
+                    count: 1,
+                })
+            }
+        }
+    }
+
+    fn range(&self) -> Range {
+        Range {
+            min: self.min,
+            max: self.max,
+            mean: self.sum / f32::from(self.count),
+        }
+    }
+}
+
+/// Collects values of one hour
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    hour: Hour,
+    samples: u16,
+    temperature: Option<Collected>,
+    humidity: Option<Collected>,
+}
+
+impl Accumulator {
+    fn new(hour: Hour, measurement: &Measurement) -> Self {
+        let mut accumulator = Self {
+            hour,
+            samples: 0,
+            temperature: None,
+            humidity: None,
+        };
+        accumulator.update(measurement);
+        accumulator
+    }
+
+    /// Takes values of sensors read successfully into account
+    fn update(&mut self, measurement: &Measurement) {
+        // Combined temperature is NaN when no sensor was read
+        let temperature = Some(measurement.temperature).filter(|value| value.is_finite());
+        let humidity = (measurement.sensor_status(SensorId::Dht22) == SensorStatus::Ok)
+            .then_some(measurement.humidity)
+            .filter(|value| value.is_finite());
+
+        self.samples = self.samples.saturating_add(1);
+        Collected::add(&mut self.temperature, temperature);
+        Collected::add(&mut self.humidity, humidity);
+    }
+
+    fn summary(&self) -> Summary {
+        Summary {
+            start: match self.hour {
+                Hour::Utc(start) => Some(start),
+                Hour::Uptime(_) => None,
+            },
+            samples: self.samples,
+            temperature: self.temperature.as_ref().map(Collected::range),
+            humidity: self.humidity.as_ref().map(Collected::range),
+        }
+    }
+}
+
+/// Ring of summaries filling whole EEPROM
+pub struct HourlyLog<E> {
+    eeprom: E,
+    /// Slot written next, holds the oldest summary once ring is full
+    next_slot: u16,
+    sequence: u32,
+}
+
+impl<E: Eeprom> HourlyLog<E> {
+    pub fn new(eeprom: E) -> Self {
+        Self {
+            eeprom,
+            next_slot: 0,
+            sequence: 0,
+        }
+    }
+
+    /// Number of summaries kept
+    pub fn slots(&self) -> u16 {
+        self.eeprom.capacity() / RECORD_SIZE as u16
+    }
+
+    /// Finds newest summary, so ring continues after it
+    pub fn mount(&mut self) -> Result<(), Error> {
+        let mut newest: Option<(u16, u32)> = None;
+        for slot in 0..self.slots() {
+            let Some((sequence, _)) = Summary::decode(&self.read_slot(slot)?) else {
+                continue;
+            };
+            if newest.is_none_or(|(_, newest)| sequence > newest) {
+                newest = Some((slot, sequence));
+            }
+        }
+
+        if let Some((slot, sequence)) = newest {
+            self.next_slot = (slot + 1) % self.slots();
+            self.sequence = sequence.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    pub async fn append(&mut self, summary: &Summary) -> Result<(), Error> {
+        let record = summary.encode(self.sequence);
+        let offset = self.next_slot * RECORD_SIZE as u16;
+        self.next_slot = (self.next_slot + 1) % self.slots();
+        self.sequence = self.sequence.wrapping_add(1);
+        self.eeprom.write(offset, &record).await
+    }
+
+    /// Reads summary by age, 0 is the oldest slot, empty and damaged slots give `None`
+    pub fn read(&mut self, age: u16) -> Result<Option<Summary>, Error> {
+        let slot = (self.next_slot + age) % self.slots();
+        let record = self.read_slot(slot)?;
+        Ok(Summary::decode(&record).map(|(_, summary)| summary))
+    }
+
+    fn read_slot(&mut self, slot: u16) -> Result<[u8; RECORD_SIZE], Error> {
+        let mut record = [0; RECORD_SIZE];
+        self.eeprom.read(slot * RECORD_SIZE as u16, &mut record)?;
+        Ok(record)
+    }
+}
+
+pub struct Runner<'a, E> {
+    measurements: &'a MeasurementChannel,
+    log: &'a SharedHourlyLog<E>,
+}
+
+impl<E: Eeprom> Runner<'_, E> {
+    pub async fn run(self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+
+        {
+            let mut log = self.log.lock().await;
+            match log.mount() {
+                Ok(()) => defmt::info!("hourly: mounted, {=u16} hours fit", log.slots()),
+                Err(err) => defmt::error!("hourly: not mounted: {}", err),
+            }
+        }
+
+        let mut current: Option<Accumulator> = None;
+        loop {
+            let measurement = subscriber.next_message_pure().await;
+            let hour = Hour::of(&measurement);
+
+            match &mut current {
+                Some(accumulator) if accumulator.hour == hour => {
+                    accumulator.update(&measurement);
+                    continue;
+                }
+                // Hour measured partly before clock was synchronized is kept too
+                Some(accumulator) => {
+                    let summary = accumulator.summary();
+                    if let Err(err) = self.log.lock().await.append(&summary).await {
+                        defmt::warn!("hourly: not appended: {}", err);
+                    }
+                }
+                None => {}
+            }
+            current = Some(Accumulator::new(hour, &measurement));
+        }
+    }
+}
+
+pub fn new<'a, E>(
+    measurements: &'a MeasurementChannel,
+    log: &'a SharedHourlyLog<E>,
+) -> Runner<'a, E> {
+    Runner { measurements, log }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::testing::RamEeprom;
+
+    const SLOTS: usize = 4;
+
+    fn summary(start: Option<u32>, temperature: f32) -> Summary {
+        Summary {
+            start,
+            samples: 12,
+            temperature: Some(Range {
+                min: temperature - 1.5,
+                max: temperature + 2.25,
+                mean: temperature,
+            }),
+            humidity: Some(Range {
+                min: 40.5,
+                max: 55.0,
+                mean: 47.2,
+            }),
+        }
+    }
+
+    fn measurement(temperature: f32, humidity: f32, dht22: SensorStatus) -> Measurement {
+        Measurement {
+            timestamp: None,
+            temperatures: [temperature; 2],
+            statuses: [SensorStatus::Ok, dht22],
+            temperature,
+            humidity,
+        }
+    }
+
+    fn log() -> HourlyLog<RamEeprom<{ SLOTS * RECORD_SIZE }>> {
+        HourlyLog::new(RamEeprom::new())
+    }
+
+    #[test]
+    fn decodes_encoded_summary() {
+        let summary = summary(Some(1_700_000_000), -5.75);
+        assert_eq!(
+            Summary::decode(&summary.encode(7)),
+            Some((7, summary)),
+            "values in stored resolution survive"
+        );
+    }
+
+    #[test]
+    fn decodes_hour_without_readings() {
+        let summary = Summary {
+            start: None,
+            samples: 3,
+            temperature: None,
+            humidity: None,
+        };
+        let record = summary.encode(0);
+        assert_eq!(record[10..12], NO_TEMPERATURE.to_le_bytes());
+        assert_eq!(record[16..18], NO_HUMIDITY.to_le_bytes());
+        assert_eq!(Summary::decode(&record), Some((0, summary)));
+    }
+
+    #[test]
+    fn rejects_damaged_record() {
+        let mut record = summary(None, 21.0).encode(1);
+        record[12] ^= 1;
+        assert_eq!(Summary::decode(&record), None);
+        assert_eq!(Summary::decode(&[0xff; RECORD_SIZE]), None, "blank slot");
+    }
+
+    #[test]
+    fn skips_readings_that_failed() {
+        let hour = Hour::Uptime(0);
+        let mut accumulator = Accumulator::new(hour, &measurement(20.0, 40.0, SensorStatus::Ok));
+        accumulator.update(&measurement(f32::NAN, 99.0, SensorStatus::Error));
+        accumulator.update(&measurement(22.0, f32::NAN, SensorStatus::Ok));
+
+        let summary = accumulator.summary();
+        assert_eq!(summary.samples, 3);
+        assert_eq!(
+            summary.temperature,
+            Some(Range {
+                min: 20.0,
+                max: 22.0,
+                mean: 21.0
+            })
+        );
+        assert_eq!(
+            summary.humidity,
+            Some(Range {
+                min: 40.0,
+                max: 40.0,
+                mean: 40.0
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_hour_without_readings_empty() {
+        let failed = measurement(f32::NAN, 0.0, SensorStatus::Unknown);
+        let mut accumulator = Accumulator::new(Hour::Uptime(0), &failed);
+        accumulator.update(&failed);
+
+        let summary = accumulator.summary();
+        assert_eq!(summary.samples, 2);
+        assert_eq!(summary.temperature, None);
+        assert_eq!(summary.humidity, None);
+    }
+
+    #[test]
+    fn mounts_blank_eeprom() {
+        let mut log = log();
+        log.mount().unwrap();
+        assert_eq!(log.slots(), SLOTS as u16);
+        for age in 0..log.slots() {
+            assert_eq!(log.read(age), Ok(None));
+        }
+    }
+
+    #[test]
+    fn continues_after_newest_when_mounted() {
+        let mut log = log();
+        for hour in 0..SLOTS as u32 + 1 {
+            block_on(log.append(&summary(Some(hour * 3600), hour as f32))).unwrap();
+        }
+        assert_eq!(log.next_slot, 1, "oldest slot overwritten");
+
+        let mut log = HourlyLog::new(log.eeprom);
+        log.mount().unwrap();
+        assert_eq!((log.next_slot, log.sequence), (1, SLOTS as u32 + 1));
+        let starts: Vec<_> = (0..log.slots())
+            .map(|age| log.read(age).unwrap().unwrap().start)
+            .collect();
+        assert_eq!(starts, [3600, 7200, 10800, 14400].map(Some), "oldest first");
+    }
+
+    #[test]
+    fn skips_damaged_slot_when_mounted() {
+        let mut log = log();
+        for hour in 0..3 {
+            block_on(log.append(&summary(Some(hour * 3600), 20.0))).unwrap();
+        }
+        log.eeprom.bytes[2 * RECORD_SIZE + 4] ^= 1;
+
+        let mut log = HourlyLog::new(log.eeprom);
+        log.mount().unwrap();
+        assert_eq!(log.next_slot, 2, "continues after newest intact summary");
+    }
+}
@@ -1,8 +1,33 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+// Board and everything driving its peripherals is behind `board` feature, the
+// rest builds on host as well, where tests run
+#[cfg(feature = "board")]
 mod board;
 
 /// Currently only one supported board
+#[cfg(feature = "board")]
 pub use board::nucleo_f411re as bsp;
 
+pub mod calibration;
+#[cfg(feature = "board")]
+pub mod clock;
+pub mod config;
+pub mod datalog;
+pub mod display;
 pub mod drivers;
+pub mod history;
+pub mod hourly;
+pub mod measurement;
+pub mod modbus;
+pub mod mqtt;
+pub mod schedule;
+pub mod sdlog;
+pub mod shell;
+pub mod telemetry;
+#[cfg(test)]
+mod testing;
+pub mod units;
+pub mod usb;
+#[cfg(feature = "board")]
+pub mod watchdog;
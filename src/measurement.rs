@@ -0,0 +1,152 @@
+//!
+//! Readings of all sensors taken at one moment
+//!
+
+use ds323x::{Datelike, NaiveDateTime, Timelike};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, pubsub::PubSubChannel};
+use embassy_time::Duration;
+
+use crate::{
+    drivers::sensors::{dht22, lm75, status::SensorStatus},
+    units::Temperature,
+};
+
+/// Channel delivering newest measurement to subscribers
+pub type MeasurementChannel = PubSubChannel<CriticalSectionRawMutex, Measurement, 1, 10, 1>;
+
+/// Sensors taking part in measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SensorId {
+    Lm75,
+    Dht22,
+}
+
+impl SensorId {
+    pub const ALL: [SensorId; SENSOR_COUNT] = [SensorId::Lm75, SensorId::Dht22];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SensorId::Lm75 => "LM75",
+            SensorId::Dht22 => "DHT22",
+        }
+    }
+
+    /// Shortest time between readings sensor supports, which is its default too
+    pub fn fastest_interval(&self) -> Duration {
+        match self {
+            SensorId::Lm75 => lm75::MEASUREMENT_INTERVAL,
+            SensorId::Dht22 => dht22::MEASUREMENT_INTERVAL,
+        }
+    }
+
+    /// Finds sensor by name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|id| id.name().eq_ignore_ascii_case(name))
+    }
+}
+
+pub const SENSOR_COUNT: usize = 2;
+
+/// UTC date-time of measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp(pub NaiveDateTime);
+
+impl defmt::Format for Timestamp {
+    fn format(&self, fmt: defmt::Formatter) {
+        let date = self.0.date();
+        let time = self.0.time();
+        defmt::write!(
+            fmt,
+            "{=i32:04}-{=u32:02}-{=u32:02}T{=u32:02}:{=u32:02}:{=u32:02}Z",
+            date.year(),
+            date.month(),
+            date.day(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Measurement {
+    /// `None` if clock was not synchronized yet
+    pub timestamp: Option<Timestamp>,
+    /// Temperature of each sensor in order of [SensorId::ALL], in Celsius
+    pub temperatures: [f32; SENSOR_COUNT],
+    /// Status of each sensor in order of [SensorId::ALL]
+    pub statuses: [SensorStatus; SENSOR_COUNT],
+    /// Temperature combined from all sensors, in Celsius
+    pub temperature: f32,
+    /// Relative humidity in %
+    pub humidity: f32,
+}
+
+impl Measurement {
+    /// Average temperature of sensors read successfully, NaN when no sensor is
+    pub fn fused_temperature(
+        temperatures: &[f32; SENSOR_COUNT],
+        statuses: &[SensorStatus; SENSOR_COUNT],
+    ) -> f32 {
+        let (sum, count) = temperatures
+            .iter()
+            .zip(statuses)
+            .filter(|(_, status)| **status == SensorStatus::Ok)
+            .fold((0.0, 0), |(sum, count), (temperature, _)| {
+                (sum + temperature, count + 1)
+            });
+
+        if count == 0 {
+            return f32::NAN;
+        }
+        sum / count as f32
+    }
+
+    pub fn sensor_temperature(&self, id: SensorId) -> f32 {
+        self.temperatures[id as usize]
+    }
+
+    pub fn sensor_status(&self, id: SensorId) -> SensorStatus {
+        self.statuses[id as usize]
+    }
+}
+
+/// Temperatures are logged in preferred unit
+impl defmt::Format for Measurement {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Measurement {{ timestamp: {}", self.timestamp);
+        for id in SensorId::ALL {
+            defmt::write!(
+                fmt,
+                ", {=str}: {} {}",
+                id.name(),
+                Temperature(self.sensor_temperature(id)),
+                self.sensor_status(id)
+            );
+        }
+        defmt::write!(
+            fmt,
+            ", temperature: {}, humidity: {=f32}% }}",
+            Temperature(self.temperature),
+            self.humidity
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fused_temperature_averages_ok_sensors_only() {
+        let temperatures = [20.0, 30.0];
+        let fused = |statuses| Measurement::fused_temperature(&temperatures, &statuses);
+
+        assert_eq!(fused([SensorStatus::Ok, SensorStatus::Ok]), 25.0);
+        assert_eq!(fused([SensorStatus::Error, SensorStatus::Ok]), 30.0);
+        assert_eq!(fused([SensorStatus::Ok, SensorStatus::Unknown]), 20.0);
+        assert!(fused([SensorStatus::Unknown, SensorStatus::Error]).is_nan());
+    }
+}
@@ -0,0 +1,16 @@
+//!
+//! Modbus RTU slave on RS-485
+//!
+//! Readings, sensor status and error counters are served as input registers, and thresholds,
+//! intervals and calibration as holding registers, see [registers]. Function codes 03, 04, 06
+//! and 16 are supported.
+//!
+
+pub mod pdu;
+pub mod registers;
+pub mod rtu;
+#[cfg(feature = "board")]
+mod runner;
+
+#[cfg(feature = "board")]
+pub use runner::{new, Context, Runner};
@@ -0,0 +1,149 @@
+//!
+//! Protocol data unit of Modbus, independent of transport
+//!
+//! Handler does no i/o, so requests can be checked on host against any [Registers].
+//!
+
+/// Function code and data, without address and checksum of transport
+pub const MAX_PDU_SIZE: usize = 253;
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Set in function code of exception response
+const EXCEPTION_FLAG: u8 = 0x80;
+
+/// Most registers read by one request, so response fits in PDU
+const MAX_READ_COUNT: u16 = 125;
+/// Most registers written by one request, so request fits in PDU
+const MAX_WRITE_COUNT: u16 = 123;
+
+/// Reason request was refused, sent in exception response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum Exception {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    ServerDeviceFailure = 0x04,
+}
+
+/// Registers served to client
+pub trait Registers {
+    /// Reads consecutive input registers starting at address
+    fn read_input(&self, address: u16, values: &mut [u16]) -> Result<(), Exception>;
+
+    /// Reads consecutive holding registers starting at address
+    fn read_holding(&self, address: u16, values: &mut [u16]) -> Result<(), Exception>;
+
+    /// Writes consecutive holding registers starting at address, all or none of them
+    fn write_holding(&mut self, address: u16, values: &[u16]) -> Result<(), Exception>;
+}
+
+/// Handles request and writes response, returns size of response
+///
+/// Response must fit [MAX_PDU_SIZE] bytes.
+pub fn handle(request: &[u8], registers: &mut impl Registers, response: &mut [u8]) -> usize {
+    let Some(&function) = request.first() else {
+        return exception(0, Exception::IllegalFunction, response);
+    };
+
+    let result = match function {
+        READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => read(request, registers, response),
+        WRITE_SINGLE_REGISTER => write_single(request, registers, response),
+        WRITE_MULTIPLE_REGISTERS => write_multiple(request, registers, response),
+        _ => Err(Exception::IllegalFunction),
+    };
+
+    match result {
+        Ok(size) => size,
+        Err(err) => exception(function, err, response),
+    }
+}
+
+fn read(
+    request: &[u8],
+    registers: &impl Registers,
+    response: &mut [u8],
+) -> Result<usize, Exception> {
+    let [function, address_high, address_low, count_high, count_low] = *request else {
+        return Err(Exception::IllegalDataValue);
+    };
+    let address = u16::from_be_bytes([address_high, address_low]);
+    let count = u16::from_be_bytes([count_high, count_low]);
+    if !(1..=MAX_READ_COUNT).contains(&count) {
+        return Err(Exception::IllegalDataValue);
+    }
+
+    let mut values = [0; MAX_READ_COUNT as usize];
+    let values = &mut values[..count.into()];
+    if function == READ_INPUT_REGISTERS {
+        registers.read_input(address, values)?;
+    } else {
+        registers.read_holding(address, values)?;
+    }
+
+    response[0] = function;
+    response[1] = (count * 2) as u8;
+    let (chunks, _) = response[2..].as_chunks_mut::<2>();
+    for (bytes, value) in chunks.iter_mut().zip(values.iter()) {
+        *bytes = value.to_be_bytes();
+    }
+    Ok(2 + usize::from(count) * 2)
+}
+
+fn write_single(
+    request: &[u8],
+    registers: &mut impl Registers,
+    response: &mut [u8],
+) -> Result<usize, Exception> {
+    let [_, address_high, address_low, value_high, value_low] = *request else {
+        return Err(Exception::IllegalDataValue);
+    };
+    let address = u16::from_be_bytes([address_high, address_low]);
+    registers.write_holding(address, &[u16::from_be_bytes([value_high, value_low])])?;
+
+    // Echo of request
+    response[..request.len()].copy_from_slice(request);
+    Ok(request.len())
+}
+
+fn write_multiple(
+    request: &[u8],
+    registers: &mut impl Registers,
+    response: &mut [u8],
+) -> Result<usize, Exception> {
+    let [function, address_high, address_low, count_high, count_low, byte_count, ref data @ ..] =
+        *request
+    else {
+        return Err(Exception::IllegalDataValue);
+    };
+    let address = u16::from_be_bytes([address_high, address_low]);
+    let count = u16::from_be_bytes([count_high, count_low]);
+    if !(1..=MAX_WRITE_COUNT).contains(&count)
+        || usize::from(byte_count) != usize::from(count) * 2
+        || data.len() != usize::from(byte_count)
+    {
+        return Err(Exception::IllegalDataValue);
+    }
+
+    let mut values = [0; MAX_WRITE_COUNT as usize];
+    let values = &mut values[..count.into()];
+    let (chunks, _) = data.as_chunks::<2>();
+    for (value, bytes) in values.iter_mut().zip(chunks) {
+        *value = u16::from_be_bytes(*bytes);
+    }
+    registers.write_holding(address, values)?;
+
+    response[0] = function;
+    response[1..5].copy_from_slice(&request[1..5]);
+    Ok(5)
+}
+
+fn exception(function: u8, exception: Exception, response: &mut [u8]) -> usize {
+    response[0] = function | EXCEPTION_FLAG;
+    response[1] = exception as u8;
+    2
+}
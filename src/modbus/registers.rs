@@ -0,0 +1,335 @@
+//!
+//! Register map of the thermometer
+//!
+//! Temperatures are in hundredths of Celsius as signed numbers, whatever unit is preferred
+//! on display, so clients need no configuration.
+//!
+//! Input registers:
+//!
+//! | Address | Value |
+//! |---|---|
+//! | 0 | combined temperature, [NO_DATA] until first measurement or while no sensor is read |
+//! | 1 | relative humidity in tenths of % |
+//! | 2 + 3n | temperature of sensor n in order of [SensorId::ALL] |
+//! | 3 + 3n | status of sensor n, 0 unknown, 1 ok, 2 error |
+//! | 4 + 3n | failed readings of sensor n, saturated |
+//!
+//! Holding registers:
+//!
+//! | Address | Value |
+//! |---|---|
+//! | 0 | over-temperature threshold of LM75 |
+//! | 1 | hysteresis of LM75, below threshold |
+//! | 2 + n | time between readings of sensor n in ms, not below default |
+//! | 2 + [SENSOR_COUNT] + n | calibration offset of sensor n |
+//!
+
+use embassy_time::Duration;
+#[cfg_attr(not(target_os = "none"), allow(unused_imports))]
+use num_traits::float::FloatCore;
+
+use super::pdu::{Exception, Registers};
+use crate::{
+    drivers::sensors::{lm75, status::SensorStatus},
+    measurement::{Measurement, SensorId, SENSOR_COUNT},
+};
+
+/// Value of input registers without data, e.g. before first measurement
+pub const NO_DATA: u16 = 0x8000;
+
+pub const INPUT_COUNT: usize = 2 + 3 * SENSOR_COUNT;
+pub const HOLDING_COUNT: usize = 2 + 2 * SENSOR_COUNT;
+
+const TEMPERATURE_INPUT: usize = 0;
+const HUMIDITY_INPUT: usize = 1;
+const SENSOR_INPUTS: usize = 2;
+
+const OS_TEMPERATURE_HOLDING: usize = 0;
+const HYSTERESIS_HOLDING: usize = 1;
+const INTERVAL_HOLDING: usize = 2;
+const OFFSET_HOLDING: usize = INTERVAL_HOLDING + SENSOR_COUNT;
+
+/// Configuration exposed in holding registers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// Over-temperature threshold of LM75, in Celsius
+    pub os_temperature: f32,
+    /// Hysteresis of LM75, in Celsius
+    pub hysteresis_temperature: f32,
+    /// Time between readings of each sensor in order of [SensorId::ALL]
+    pub intervals: [Duration; SENSOR_COUNT],
+    /// Calibration offset of each sensor in order of [SensorId::ALL], in Celsius
+    pub offsets: [f32; SENSOR_COUNT],
+}
+
+/// Snapshot of registers taken for one request
+pub struct RegisterMap {
+    input: [u16; INPUT_COUNT],
+    holding: [u16; HOLDING_COUNT],
+}
+
+impl RegisterMap {
+    pub fn new(
+        measurement: Option<&Measurement>,
+        error_counts: [u32; SENSOR_COUNT],
+        settings: &Settings,
+    ) -> Self {
+        let mut input = [NO_DATA; INPUT_COUNT];
+        if let Some(measurement) = measurement {
+            if measurement.temperature.is_finite() {
+                input[TEMPERATURE_INPUT] = encode_temperature(measurement.temperature);
+            }
+            input[HUMIDITY_INPUT] = (measurement.humidity * 10.0).round() as u16;
+        }
+        for (id, errors) in SensorId::ALL.into_iter().zip(error_counts) {
+            let sensor = &mut input[SENSOR_INPUTS + 3 * id as usize..][..3];
+            let status = measurement.map_or(SensorStatus::Unknown, |m| m.sensor_status(id));
+            if let Some(measurement) = measurement.filter(|_| status != SensorStatus::Unknown) {
+                sensor[0] = encode_temperature(measurement.sensor_temperature(id));
+            }
+            sensor[1] = match status {
+                SensorStatus::Unknown => 0,
+                SensorStatus::Ok => 1,
+                SensorStatus::Error => 2,
+            };
+            sensor[2] = errors.try_into().unwrap_or(u16::MAX);
+        }
+
+        let mut holding = [0; HOLDING_COUNT];
+        holding[OS_TEMPERATURE_HOLDING] = encode_temperature(settings.os_temperature);
+        holding[HYSTERESIS_HOLDING] = encode_temperature(settings.hysteresis_temperature);
+        for id in SensorId::ALL {
+            let millis = settings.intervals[id as usize].as_millis();
+            holding[INTERVAL_HOLDING + id as usize] = millis.try_into().unwrap_or(u16::MAX);
+            holding[OFFSET_HOLDING + id as usize] =
+                encode_temperature(settings.offsets[id as usize]);
+        }
+
+        Self { input, holding }
+    }
+
+    /// Settings as held in registers, including writes
+    pub fn settings(&self) -> Settings {
+        Self::decode_settings(&self.holding)
+    }
+
+    fn decode_settings(holding: &[u16; HOLDING_COUNT]) -> Settings {
+        Settings {
+            os_temperature: decode_temperature(holding[OS_TEMPERATURE_HOLDING]),
+            hysteresis_temperature: decode_temperature(holding[HYSTERESIS_HOLDING]),
+            intervals: core::array::from_fn(|n| {
+                Duration::from_millis(holding[INTERVAL_HOLDING + n].into())
+            }),
+            offsets: core::array::from_fn(|n| decode_temperature(holding[OFFSET_HOLDING + n])),
+        }
+    }
+
+    fn validate(settings: &Settings) -> Result<(), Exception> {
+        if !lm75::valid_thresholds(settings.os_temperature, settings.hysteresis_temperature) {
+            return Err(Exception::IllegalDataValue);
+        }
+        for id in SensorId::ALL {
+            if settings.intervals[id as usize] < id.fastest_interval() {
+                return Err(Exception::IllegalDataValue);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Registers for RegisterMap {
+    fn read_input(&self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+        values.copy_from_slice(range(&self.input, address, values.len())?);
+        Ok(())
+    }
+
+    fn read_holding(&self, address: u16, values: &mut [u16]) -> Result<(), Exception> {
+        values.copy_from_slice(range(&self.holding, address, values.len())?);
+        Ok(())
+    }
+
+    fn write_holding(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let mut holding = self.holding;
+        range_mut(&mut holding, address, values.len())?.copy_from_slice(values);
+        Self::validate(&Self::decode_settings(&holding))?;
+        self.holding = holding;
+        Ok(())
+    }
+}
+
+fn range(registers: &[u16], address: u16, count: usize) -> Result<&[u16], Exception> {
+    let start = usize::from(address);
+    registers
+        .get(start..start + count)
+        .ok_or(Exception::IllegalDataAddress)
+}
+
+fn range_mut(registers: &mut [u16], address: u16, count: usize) -> Result<&mut [u16], Exception> {
+    let start = usize::from(address);
+    registers
+        .get_mut(start..start + count)
+        .ok_or(Exception::IllegalDataAddress)
+}
+
+/// Hundredths of Celsius as two's complement
+fn encode_temperature(celsius: f32) -> u16 {
+    (celsius * 100.0).round() as i16 as u16
+}
+
+fn decode_temperature(value: u16) -> f32 {
+    f32::from(value as i16) / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus::pdu::{self, MAX_PDU_SIZE};
+
+    fn settings() -> Settings {
+        Settings {
+            os_temperature: 80.0,
+            hysteresis_temperature: 75.0,
+            intervals: SensorId::ALL.map(|id| id.fastest_interval()),
+            offsets: [0.0, -1.5],
+        }
+    }
+
+    fn request(registers: &mut RegisterMap, request: &[u8]) -> Vec<u8> {
+        let mut response = [0; MAX_PDU_SIZE];
+        let size = pdu::handle(request, registers, &mut response);
+        response[..size].to_vec()
+    }
+
+    #[test]
+    fn reads_holding_registers() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        let response = request(&mut registers, &[0x03, 0x00, 0x00, 0x00, 0x06]);
+
+        #[rustfmt::skip]
+        assert_eq!(response, [
+            0x03, 12,
+            0x1f, 0x40, 0x1d, 0x4c, // 80.00, 75.00
+            0x00, 0x64, 0x03, 0xe8, // 100 ms, 1000 ms
+            0x00, 0x00, 0xff, 0x6a, // 0.00, -1.50
+        ]);
+    }
+
+    #[test]
+    fn reads_input_registers_without_data() {
+        let mut registers = RegisterMap::new(None, [3, 70000], &settings());
+
+        let response = request(&mut registers, &[0x04, 0x00, 0x00, 0x00, 0x08]);
+
+        #[rustfmt::skip]
+        assert_eq!(response, [
+            0x04, 16,
+            0x80, 0x00, 0x80, 0x00,
+            0x80, 0x00, 0x00, 0x00, 0x00, 0x03,
+            0x80, 0x00, 0x00, 0x00, 0xff, 0xff,
+        ]);
+    }
+
+    #[test]
+    fn writes_single_register() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        // Threshold of 90.00 Celsius
+        let response = request(&mut registers, &[0x06, 0x00, 0x00, 0x23, 0x28]);
+
+        assert_eq!(response, [0x06, 0x00, 0x00, 0x23, 0x28]);
+        assert_eq!(registers.settings().os_temperature, 90.0);
+    }
+
+    #[test]
+    fn writes_multiple_registers() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        // Intervals of 500 ms and 2000 ms
+        let response = request(
+            &mut registers,
+            &[0x10, 0x00, 0x02, 0x00, 0x02, 4, 0x01, 0xf4, 0x07, 0xd0],
+        );
+
+        assert_eq!(response, [0x10, 0x00, 0x02, 0x00, 0x02]);
+        assert_eq!(
+            registers.settings().intervals,
+            [Duration::from_millis(500), Duration::from_millis(2000)]
+        );
+    }
+
+    #[test]
+    fn refuses_bad_count() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        assert_eq!(
+            request(&mut registers, &[0x03, 0x00, 0x00, 0x00, 0x00]),
+            [0x83, 0x03]
+        );
+        assert_eq!(
+            request(&mut registers, &[0x04, 0x00, 0x00, 0x00, 126]),
+            [0x84, 0x03]
+        );
+        // Byte count not matching register count
+        assert_eq!(
+            request(
+                &mut registers,
+                &[0x10, 0x00, 0x00, 0x00, 0x02, 2, 0x23, 0x28]
+            ),
+            [0x90, 0x03]
+        );
+    }
+
+    #[test]
+    fn refuses_address_out_of_range() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        assert_eq!(
+            request(&mut registers, &[0x03, 0x00, 0x05, 0x00, 0x02]),
+            [0x83, 0x02]
+        );
+        assert_eq!(
+            request(&mut registers, &[0x04, 0xff, 0xff, 0x00, 0x01]),
+            [0x84, 0x02]
+        );
+        assert_eq!(
+            request(&mut registers, &[0x06, 0x00, 0x06, 0x00, 0x00]),
+            [0x86, 0x02]
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_settings() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        // Hysteresis of 80.00 Celsius, same as threshold
+        assert_eq!(
+            request(&mut registers, &[0x06, 0x00, 0x01, 0x1f, 0x40]),
+            [0x86, 0x03]
+        );
+        // Interval of LM75 below 100 ms
+        assert_eq!(
+            request(&mut registers, &[0x06, 0x00, 0x02, 0x00, 0x63]),
+            [0x86, 0x03]
+        );
+        // Threshold and hysteresis swapped at once
+        assert_eq!(
+            request(
+                &mut registers,
+                &[0x10, 0x00, 0x00, 0x00, 0x02, 4, 0x1d, 0x4c, 0x1f, 0x40]
+            ),
+            [0x90, 0x03]
+        );
+        assert_eq!(registers.settings(), settings());
+    }
+
+    #[test]
+    fn refuses_unknown_function() {
+        let mut registers = RegisterMap::new(None, [0; SENSOR_COUNT], &settings());
+
+        assert_eq!(
+            request(&mut registers, &[0x01, 0x00, 0x00, 0x00, 0x01]),
+            [0x81, 0x01]
+        );
+    }
+}
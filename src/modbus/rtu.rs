@@ -0,0 +1,160 @@
+//!
+//! RTU framing of Modbus on serial line
+//!
+//! Frame is slave address, [PDU](super::pdu) and CRC-16 sent low byte first. Frames are
+//! separated by silence, which is detected by the owner of the line.
+//!
+
+use super::pdu::{self, Registers, MAX_PDU_SIZE};
+
+/// Address, PDU and CRC
+pub const MAX_FRAME_SIZE: usize = 1 + MAX_PDU_SIZE + 2;
+
+/// Requests sent to all slaves, never answered
+pub const BROADCAST_ADDRESS: u8 = 0;
+
+/// Addresses slaves can have
+pub const SLAVE_ADDRESSES: core::ops::RangeInclusive<u8> = 1..=247;
+
+/// Address and function code with CRC
+const MIN_FRAME_SIZE: usize = 4;
+
+/// Reversed polynomial 0x8005
+const CRC_POLYNOMIAL: u16 = 0xa001;
+
+/// CRC-16/MODBUS of bytes
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xffff, |crc, &byte| {
+        (0..8).fold(crc ^ u16::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ CRC_POLYNOMIAL
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Handles frame addressed to slave, returns size of reply written to response
+///
+/// Frames which are damaged, addressed to other slaves or broadcast get no reply.
+pub fn handle_frame(
+    address: u8,
+    frame: &[u8],
+    registers: &mut impl Registers,
+    response: &mut [u8; MAX_FRAME_SIZE],
+) -> Option<usize> {
+    if !(MIN_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&frame.len()) {
+        return None;
+    }
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    if crc16(data) != u16::from_le_bytes([crc[0], crc[1]]) {
+        defmt::debug!("modbus: bad crc");
+        return None;
+    }
+
+    let target = data[0];
+    if target != address && target != BROADCAST_ADDRESS {
+        return None;
+    }
+
+    response[0] = address;
+    let size = 1 + pdu::handle(&data[1..], registers, &mut response[1..1 + MAX_PDU_SIZE]);
+    if target == BROADCAST_ADDRESS {
+        return None;
+    }
+
+    let crc = crc16(&response[..size]);
+    response[size..size + 2].copy_from_slice(&crc.to_le_bytes());
+    Some(size + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        measurement::{SensorId, SENSOR_COUNT},
+        modbus::registers::{RegisterMap, Settings},
+    };
+
+    const ADDRESS: u8 = 0x11;
+
+    fn registers() -> RegisterMap {
+        let settings = Settings {
+            os_temperature: 80.0,
+            hysteresis_temperature: 75.0,
+            intervals: SensorId::ALL.map(|id| id.fastest_interval()),
+            offsets: [0.0; SENSOR_COUNT],
+        };
+        RegisterMap::new(None, [0; SENSOR_COUNT], &settings)
+    }
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut frame = data.to_vec();
+        frame.extend_from_slice(&crc16(data).to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn crc_matches_known_vectors() {
+        assert_eq!(crc16(b"123456789"), 0x4b37);
+        assert_eq!(
+            crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]).to_le_bytes(),
+            [0x84, 0x0a]
+        );
+        assert_eq!(
+            crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0a]).to_le_bytes(),
+            [0xc5, 0xcd]
+        );
+    }
+
+    #[test]
+    fn answers_frame_addressed_to_slave() {
+        let mut registers = registers();
+        let mut response = [0; MAX_FRAME_SIZE];
+
+        let size = handle_frame(
+            ADDRESS,
+            &frame(&[ADDRESS, 0x03, 0x00, 0x00, 0x00, 0x01]),
+            &mut registers,
+            &mut response,
+        );
+
+        assert_eq!(size, Some(7));
+        assert_eq!(response[..7], frame(&[ADDRESS, 0x03, 2, 0x1f, 0x40]));
+    }
+
+    #[test]
+    fn ignores_damaged_and_foreign_frames() {
+        let mut registers = registers();
+        let mut response = [0; MAX_FRAME_SIZE];
+
+        let mut damaged = frame(&[ADDRESS, 0x03, 0x00, 0x00, 0x00, 0x01]);
+        damaged[5] ^= 1;
+        let foreign = frame(&[ADDRESS + 1, 0x03, 0x00, 0x00, 0x00, 0x01]);
+
+        for frame in [&damaged[..], &foreign, &[ADDRESS, 0x03]] {
+            assert_eq!(
+                handle_frame(ADDRESS, frame, &mut registers, &mut response),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn applies_broadcast_write_without_reply() {
+        let mut registers = registers();
+        let mut response = [0; MAX_FRAME_SIZE];
+
+        // Threshold of 90.00 Celsius
+        let size = handle_frame(
+            ADDRESS,
+            &frame(&[BROADCAST_ADDRESS, 0x06, 0x00, 0x00, 0x23, 0x28]),
+            &mut registers,
+            &mut response,
+        );
+
+        assert_eq!(size, None);
+        assert_eq!(registers.settings().os_temperature, 90.0);
+    }
+}
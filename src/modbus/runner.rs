@@ -0,0 +1,184 @@
+//!
+//! Modbus slave task serving register map on RS-485
+//!
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{with_timeout, Duration};
+
+use super::{
+    registers::{RegisterMap, Settings},
+    rtu::{self, MAX_FRAME_SIZE},
+};
+use crate::{
+    bsp::{self, Rs485},
+    calibration,
+    drivers::sensors::{
+        dht22::Dht22,
+        lm75::{self, Lm75},
+        status::StatusSensor,
+    },
+    measurement::{Measurement, MeasurementChannel, SensorId},
+};
+
+/// Silence ending a frame, 3.5 characters at 19200 baud
+const FRAME_GAP: Duration = Duration::from_millis(2);
+
+/// Sensors configured over Modbus
+pub struct Context<'a> {
+    pub lm75: Lm75<'a>,
+    pub dht22: Dht22<'a>,
+}
+
+pub struct Runner<'a> {
+    rs485: Rs485,
+    address: u8,
+    measurements: &'a MeasurementChannel,
+    context: Context<'a>,
+    latest: Option<Measurement>,
+}
+
+impl Runner<'_> {
+    pub async fn run(mut self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+        let mut frame = [0; MAX_FRAME_SIZE];
+
+        loop {
+            let received =
+                match select(self.rs485.read(&mut frame), subscriber.next_message_pure()).await {
+                    Either::First(received) => received,
+                    Either::Second(measurement) => {
+                        self.latest = Some(measurement);
+                        continue;
+                    }
+                };
+
+            match self.receive_rest(&mut frame, received).await {
+                Some(size) => self.serve(&frame[..size]).await,
+                None => defmt::warn!("modbus: frame dropped"),
+            }
+        }
+    }
+
+    /// Reads until line is silent, returns size of frame
+    async fn receive_rest(
+        &mut self,
+        frame: &mut [u8; MAX_FRAME_SIZE],
+        received: Result<usize, bsp::UartError>,
+    ) -> Option<usize> {
+        let mut size = match received {
+            Ok(size) => size,
+            Err(err) => {
+                defmt::warn!("modbus: read failed: {}", err);
+                0
+            }
+        };
+        let mut damaged = size == 0;
+
+        loop {
+            // Longer frame is read to the end and dropped
+            let buffer = if size < MAX_FRAME_SIZE {
+                &mut frame[size..]
+            } else {
+                damaged = true;
+                &mut frame[..]
+            };
+            match with_timeout(FRAME_GAP, self.rs485.read(buffer)).await {
+                Ok(Ok(read)) if !damaged => size += read,
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => {
+                    defmt::warn!("modbus: read failed: {}", err);
+                    damaged = true;
+                }
+                Err(_) => break,
+            }
+        }
+
+        (!damaged).then_some(size)
+    }
+
+    async fn serve(&mut self, frame: &[u8]) {
+        let settings = self.settings().await;
+        let error_counts = [
+            self.context.lm75.get_error_count().await,
+            self.context.dht22.get_error_count().await,
+        ];
+        let mut registers = RegisterMap::new(self.latest.as_ref(), error_counts, &settings);
+        // Compared as held in registers, since they are rounded to register resolution
+        let before = registers.settings();
+
+        let mut response = [0; MAX_FRAME_SIZE];
+        let reply = rtu::handle_frame(self.address, frame, &mut registers, &mut response);
+
+        let after = registers.settings();
+        if after != before {
+            self.apply(&before, &after).await;
+        }
+
+        if let Some(size) = reply {
+            if let Err(err) = self.rs485.send(&response[..size]).await {
+                defmt::warn!("modbus: write failed: {}", err);
+            }
+        }
+    }
+
+    async fn settings(&self) -> Settings {
+        let config = self.context.lm75.config().await;
+        Settings {
+            os_temperature: config.os_temperature,
+            hysteresis_temperature: config.hysteresis_temperature,
+            intervals: [
+                config.measurement_interval,
+                self.context.dht22.measurement_interval().await,
+            ],
+            offsets: SensorId::ALL.map(calibration::offset),
+        }
+    }
+
+    /// Applies changed settings, sensors are reconfigured only when needed
+    async fn apply(&self, old: &Settings, new: &Settings) {
+        let lm75 = SensorId::Lm75 as usize;
+        if new.os_temperature != old.os_temperature
+            || new.hysteresis_temperature != old.hysteresis_temperature
+            || new.intervals[lm75] != old.intervals[lm75]
+        {
+            let config = self.context.lm75.config().await;
+            let config = lm75::Config {
+                measurement_interval: new.intervals[lm75],
+                os_temperature: new.os_temperature,
+                hysteresis_temperature: new.hysteresis_temperature,
+                ..config
+            };
+            self.context.lm75.set_config(config).await;
+        }
+
+        let dht22 = SensorId::Dht22 as usize;
+        if new.intervals[dht22] != old.intervals[dht22] {
+            self.context
+                .dht22
+                .set_measurement_interval(new.intervals[dht22])
+                .await;
+        }
+
+        for id in SensorId::ALL {
+            if new.offsets[id as usize] != old.offsets[id as usize] {
+                calibration::set_offset(id, new.offsets[id as usize]);
+            }
+        }
+    }
+}
+
+/// Creates slave answering at address
+pub fn new<'a>(
+    rs485: Rs485,
+    address: u8,
+    measurements: &'a MeasurementChannel,
+    context: Context<'a>,
+) -> Runner<'a> {
+    Runner {
+        rs485,
+        address,
+        measurements,
+        context,
+        latest: None,
+    }
+}
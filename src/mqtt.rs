@@ -0,0 +1,259 @@
+//!
+//! Publishing of measurements to MQTT broker
+//!
+//! Broker is reached through a Wi-Fi modem with AT firmware, see [esp_at]. Readings are
+//! published as text in Celsius under a configurable topic prefix:
+//!
+//! - `<prefix>/temperature` and `<prefix>/humidity` for combined values
+//! - `<prefix>/<sensor>/temperature` for sensors that were read
+//! - `<prefix>/<sensor>/status`, retained
+//!
+//! Lost connection is restored with growing delays between attempts.
+//!
+
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::{Read, ReadReady, Write};
+use heapless::String;
+
+use crate::{
+    display::drawables::measurement_text::write_value,
+    drivers::{
+        esp_at::{self, Modem, MqttUser, Qos},
+        sensors::status::SensorStatus,
+    },
+    measurement::{Measurement, MeasurementChannel, SensorId},
+};
+
+/// Fits prefix, sensor name and value name
+const TOPIC_SIZE: usize = 64;
+
+const PAYLOAD_SIZE: usize = 16;
+
+/// Delay before first reconnect
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Reconnecting is never delayed more
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+pub struct Config<'a> {
+    pub ssid: &'a str,
+    pub wifi_password: &'a str,
+    /// Host name or address of broker
+    pub host: &'a str,
+    pub port: u16,
+    pub user: MqttUser<'a>,
+    /// Topics of readings start with it, without trailing slash
+    pub topic_prefix: &'a str,
+    /// Measurements taken in between are not published
+    pub publish_interval: Duration,
+}
+
+/// Delays between reconnect attempts, doubled after each failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self { delay: MIN_BACKOFF }
+    }
+
+    /// Delay before next attempt
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(MAX_BACKOFF);
+        delay
+    }
+
+    /// Starts over after successful connect
+    pub fn reset(&mut self) {
+        self.delay = MIN_BACKOFF;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Runner<'a, IO> {
+    modem: Modem<IO>,
+    config: Config<'a>,
+    measurements: &'a MeasurementChannel,
+}
+
+impl<IO: Read + ReadReady + Write> Runner<'_, IO> {
+    pub async fn run(mut self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+        let mut backoff = Backoff::new();
+
+        loop {
+            if let Err(err) = self.connect().await {
+                let delay = backoff.next_delay();
+                defmt::warn!(
+                    "mqtt: connect failed: {}, retry in {=u64}s",
+                    err,
+                    delay.as_secs()
+                );
+                Timer::after(delay).await;
+                continue;
+            }
+            backoff.reset();
+            defmt::info!("mqtt: connected to {=str}", self.config.host);
+
+            let mut publish_time = Instant::now();
+            loop {
+                let measurement = subscriber.next_message_pure().await;
+                if Instant::now() < publish_time {
+                    continue;
+                }
+                publish_time = Instant::now() + self.config.publish_interval;
+
+                if let Err(err) = self.publish(&measurement).await {
+                    defmt::warn!("mqtt: publish failed: {}", err);
+                    break;
+                }
+                if self.modem.is_disconnected() {
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<(), esp_at::Error> {
+        let config = &self.config;
+        self.modem.init().await?;
+        self.modem
+            .join_wifi(config.ssid, config.wifi_password)
+            .await?;
+        self.modem
+            .mqtt_connect(&config.user, config.host, config.port)
+            .await
+    }
+
+    async fn publish(&mut self, measurement: &Measurement) -> Result<(), esp_at::Error> {
+        let mut payload: String<PAYLOAD_SIZE> = String::new();
+
+        write_value(Some(measurement.temperature), 2, &mut payload);
+        self.publish_value(None, "temperature", &payload, false)
+            .await?;
+        write_value(Some(measurement.humidity), 1, &mut payload);
+        self.publish_value(None, "humidity", &payload, false)
+            .await?;
+
+        for id in SensorId::ALL {
+            let status = measurement.sensor_status(id);
+            if status == SensorStatus::Ok {
+                write_value(Some(measurement.sensor_temperature(id)), 2, &mut payload);
+                self.publish_value(Some(id), "temperature", &payload, false)
+                    .await?;
+            }
+            let status = match status {
+                SensorStatus::Unknown => "unknown",
+                SensorStatus::Ok => "ok",
+                SensorStatus::Error => "error",
+            };
+            self.publish_value(Some(id), "status", status, true).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Publishes value of sensor, or combined value if there is no sensor
+    async fn publish_value(
+        &mut self,
+        sensor: Option<SensorId>,
+        name: &str,
+        payload: &str,
+        retain: bool,
+    ) -> Result<(), esp_at::Error> {
+        let mut topic: String<TOPIC_SIZE> = String::new();
+        topic
+            .push_str(self.config.topic_prefix)
+            .map_err(|_| esp_at::Error::TooLong)?;
+        for part in sensor.map(|id| id.name()).into_iter().chain([name]) {
+            topic.push('/').map_err(|_| esp_at::Error::TooLong)?;
+            for c in part.chars() {
+                topic
+                    .push(c.to_ascii_lowercase())
+                    .map_err(|_| esp_at::Error::TooLong)?;
+            }
+        }
+
+        self.modem
+            .mqtt_publish(&topic, payload, Qos::AtMostOnce, retain)
+            .await
+    }
+}
+
+/// Creates publisher using modem on byte stream, e.g. serial port
+pub fn new<'a, IO: Read + ReadReady + Write>(
+    io: IO,
+    config: Config<'a>,
+    measurements: &'a MeasurementChannel,
+) -> Runner<'a, IO> {
+    Runner {
+        modem: Modem::new(io),
+        config,
+        measurements,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{future::poll_fn, task::Poll};
+
+    use embassy_futures::{block_on, select::select};
+
+    use super::*;
+    use crate::testing::ScriptedSerial;
+
+    #[test]
+    fn backoff_doubles_up_to_limit() {
+        let mut backoff = Backoff::new();
+
+        let delays: [_; 10] = core::array::from_fn(|_| backoff.next_delay().as_secs());
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 64, 128, 256, 300]);
+        assert_eq!(backoff.next_delay(), MAX_BACKOFF);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), MIN_BACKOFF);
+    }
+
+    #[test]
+    fn retries_failed_connect_with_backoff() {
+        // Modem rejects everything
+        let serial = ScriptedSerial::new(&[b"ERROR\r\n".as_slice(); 5]);
+        let measurements = MeasurementChannel::new();
+        let config = Config {
+            ssid: "network",
+            wifi_password: "secret",
+            host: "broker",
+            port: 1883,
+            user: MqttUser {
+                client_id: "thermometer",
+                username: "",
+                password: "",
+            },
+            topic_prefix: "thermometer",
+            publish_interval: Duration::from_secs(60),
+        };
+        let runner = new(serial.clone(), config, &measurements);
+
+        let attempts = poll_fn(|_| {
+            if serial.commands().len() < 5 {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        });
+        block_on(select(runner.run(), attempts));
+
+        let times = serial.command_times();
+        let delays: Vec<_> = times.windows(2).map(|t| (t[1] - t[0]).as_secs()).collect();
+        assert_eq!(serial.commands(), ["AT"; 5]);
+        assert_eq!(delays, [1, 2, 4, 8]);
+    }
+}
@@ -0,0 +1,159 @@
+//!
+//! Measurements scheduled at exact wall-clock times
+//!
+//! DS3231 alarm fires at the start of every period, so measurements do not drift like
+//! `Timer::after` loops do. Until alarm is configured, or when it does not come in time, the
+//! scheduler falls back to timer.
+//!
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Duration;
+
+#[cfg(feature = "board")]
+use {
+    crate::drivers::ds3231::{
+        self, Alarm, Alarm1Matching, Alarm2Matching, DayAlarm1, DayAlarm2, Ds3231, Hours,
+    },
+    embassy_time::{with_timeout, Timer},
+};
+
+/// Added to period when waiting for alarm, alarm not coming by then is taken as lost
+#[cfg(feature = "board")]
+const ALARM_MARGIN: Duration = Duration::from_secs(2);
+
+/// Request to change period of running scheduler
+pub type PeriodSignal = Signal<CriticalSectionRawMutex, Period>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Period {
+    /// At start of every second
+    EverySecond,
+    /// At 00 seconds of every minute
+    EveryMinute,
+    /// At 00:00 of every hour
+    EveryHour,
+    /// Once a day at given UTC time
+    Daily { hour: u8, minute: u8 },
+}
+
+impl Period {
+    /// Approximate length of period used while alarm is not available
+    pub fn duration(&self) -> Duration {
+        match self {
+            Period::EverySecond => Duration::from_secs(1),
+            Period::EveryMinute => Duration::from_secs(60),
+            Period::EveryHour => Duration::from_secs(60 * 60),
+            Period::Daily { .. } => Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Alarm used for period, alarm 2 has no seconds so it is used for longer periods
+    #[cfg(feature = "board")]
+    fn alarm(&self) -> Alarm {
+        match self {
+            Period::EverySecond => Alarm::Alarm1,
+            _ => Alarm::Alarm2,
+        }
+    }
+
+    /// Alarm not used for period, kept off so it does not wake INT task or crowd out events
+    #[cfg(feature = "board")]
+    fn unused_alarm(&self) -> Alarm {
+        match self.alarm() {
+            Alarm::Alarm1 => Alarm::Alarm2,
+            Alarm::Alarm2 => Alarm::Alarm1,
+        }
+    }
+}
+
+#[cfg(feature = "board")]
+pub struct Scheduler<'a> {
+    rtc: Ds3231<'a>,
+    period: Period,
+    armed: bool,
+}
+
+#[cfg(feature = "board")]
+impl<'a> Scheduler<'a> {
+    pub fn new(rtc: Ds3231<'a>, period: Period) -> Self {
+        Self {
+            rtc,
+            period,
+            armed: false,
+        }
+    }
+
+    pub fn period(&self) -> Period {
+        self.period
+    }
+
+    /// Changes period, alarms are set again on next wait
+    pub fn set_period(&mut self, period: Period) {
+        defmt::info!("schedule: period {}", period);
+        self.period = period;
+        self.armed = false;
+    }
+
+    /// Waits for start of next period
+    pub async fn next(&mut self) {
+        if !self.armed {
+            match self.arm().await {
+                Ok(()) => self.armed = true,
+                Err(err) => {
+                    defmt::warn!("schedule: alarm is not set, using timer: {}", err);
+                    Timer::after(self.period.duration()).await;
+                    return;
+                }
+            }
+        }
+
+        let expected = self.period.alarm();
+        let alarm = async { while self.rtc.wait_alarm().await != expected {} };
+        if with_timeout(self.period.duration() + ALARM_MARGIN, alarm)
+            .await
+            .is_err()
+        {
+            // Period has passed already, so measurement is taken now and alarm is set again
+            defmt::warn!("schedule: alarm is late, using timer");
+            self.armed = false;
+        }
+    }
+
+    async fn arm(&self) -> Result<(), ds3231::Error> {
+        let alarm1 = DayAlarm1 {
+            day: 1,
+            hour: Hours::H24(0),
+            minute: 0,
+            second: 0,
+        };
+        let alarm2 = |hour, minute| DayAlarm2 {
+            day: 1,
+            hour: Hours::H24(hour),
+            minute,
+        };
+
+        self.rtc.disable_alarm(self.period.unused_alarm()).await?;
+        match self.period {
+            Period::EverySecond => {
+                self.rtc
+                    .set_alarm1(alarm1, Alarm1Matching::OncePerSecond)
+                    .await
+            }
+            Period::EveryMinute => {
+                self.rtc
+                    .set_alarm2(alarm2(0, 0), Alarm2Matching::OncePerMinute)
+                    .await
+            }
+            Period::EveryHour => {
+                self.rtc
+                    .set_alarm2(alarm2(0, 0), Alarm2Matching::MinutesMatch)
+                    .await
+            }
+            Period::Daily { hour, minute } => {
+                self.rtc
+                    .set_alarm2(alarm2(hour, minute), Alarm2Matching::HoursAndMinutesMatch)
+                    .await
+            }
+        }
+    }
+}
@@ -0,0 +1,661 @@
+//!
+//! FAT32 volume files are appended to
+//!
+//! Covers what the logger needs: files with short names in root directory, created and
+//! appended to. Volume is the first partition of MBR or the whole card when it has no
+//! partition table. FAT12 and FAT16, found on cards of 2GB and less, are not supported.
+//!
+//! Blocks go through a single cached block, which is written through, so a removed card
+//! loses the data written since the last append at most.
+//!
+
+use ds323x::{Datelike, NaiveDateTime, Timelike};
+
+use crate::drivers::sdcard::{self, Block, BlockDevice, BLOCK_SIZE};
+
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const FAT32_LABEL: &[u8] = b"FAT32   ";
+const PARTITION_TABLE: usize = 0x1be;
+const PARTITION_TYPES_FAT32: [u8; 2] = [0x0b, 0x0c];
+
+const FS_INFO_LEAD: u32 = 0x4161_5252;
+const FS_INFO_STRUCT: u32 = 0x6141_7272;
+const FS_INFO_FREE_COUNT: usize = 488;
+const FREE_COUNT_UNKNOWN: u32 = u32::MAX;
+
+const ENTRY_SIZE: usize = 32;
+const ENTRY_END: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xe5;
+const ATTRIBUTE_ARCHIVE: u8 = 0x20;
+
+const CLUSTER_MASK: u32 = 0x0fff_ffff;
+const CLUSTER_FREE: u32 = 0;
+const CLUSTER_END: u32 = 0x0fff_ffff;
+/// Entries from here on end a chain
+const CLUSTER_END_MIN: u32 = 0x0fff_fff8;
+const FIRST_CLUSTER: u32 = 2;
+
+/// Name and extension of a short name, padded with spaces
+pub type ShortName = [u8; 11];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum Error {
+    #[error("Card: {0}")]
+    Card(#[from] sdcard::Error),
+    #[error("No FAT32 volume")]
+    NoVolume,
+    /// Cluster chain leads outside of volume
+    #[error("Damaged volume")]
+    Damaged,
+    #[error("Volume full")]
+    Full,
+}
+
+/// File open for appending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct File {
+    /// Block and offset of directory entry
+    entry: (u32, usize),
+    /// 0 while file has no cluster
+    first_cluster: u32,
+    /// Cluster holding last byte, 0 while file is empty
+    last_cluster: u32,
+    pub size: u32,
+}
+
+pub struct Volume {
+    fat_start: u32,
+    fat_size: u32,
+    fat_count: u32,
+    data_start: u32,
+    blocks_per_cluster: u32,
+    /// Clusters are numbered from 2 up to this one
+    max_cluster: u32,
+    root_cluster: u32,
+    /// FSInfo block, its free count is marked unknown on first allocation
+    info: Option<u32>,
+    /// Search for free cluster starts here
+    next_free: u32,
+    block: Block,
+    /// Block held in `block`
+    cached: Option<u32>,
+}
+
+impl Volume {
+    pub async fn mount<D: BlockDevice>(device: &mut D) -> Result<Self, Error> {
+        let mut block = [0; BLOCK_SIZE];
+        device.read(0, &mut block).await?;
+        if block[510..] != BOOT_SIGNATURE {
+            return Err(Error::NoVolume);
+        }
+
+        let start = if block[0x52..0x5a] == *FAT32_LABEL {
+            0
+        } else {
+            let partition = &block[PARTITION_TABLE..PARTITION_TABLE + 16];
+            if !PARTITION_TYPES_FAT32.contains(&partition[4]) {
+                return Err(Error::NoVolume);
+            }
+            let start =
+                u32::from_le_bytes([partition[8], partition[9], partition[10], partition[11]]);
+            device.read(start, &mut block).await?;
+            if block[510..] != BOOT_SIGNATURE {
+                return Err(Error::NoVolume);
+            }
+            start
+        };
+
+        let half =
+            |offset: usize| u32::from(u16::from_le_bytes([block[offset], block[offset + 1]]));
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                block[offset],
+                block[offset + 1],
+                block[offset + 2],
+                block[offset + 3],
+            ])
+        };
+        let bytes_per_block = half(0x0b);
+        let blocks_per_cluster = u32::from(block[0x0d]);
+        let reserved = half(0x0e);
+        let fat_count = u32::from(block[0x10]);
+        let fat_size = word(0x24);
+        let total = word(0x20);
+        let info = half(0x30);
+        // FAT12 and FAT16 give size of FAT in the older field
+        if bytes_per_block != BLOCK_SIZE as u32
+            || !blocks_per_cluster.is_power_of_two()
+            || reserved == 0
+            || fat_count == 0
+            || half(0x16) != 0
+            || fat_size == 0
+        {
+            return Err(Error::NoVolume);
+        }
+
+        let fat_start = start + reserved;
+        let data_start = fat_start + fat_count * fat_size;
+        let data_blocks = (start + total)
+            .checked_sub(data_start)
+            .ok_or(Error::NoVolume)?;
+        let clusters =
+            (data_blocks / blocks_per_cluster).min(fat_size * (BLOCK_SIZE as u32 / 4) - 2);
+        let root_cluster = word(0x2c);
+        let max_cluster = clusters + 1;
+        if !(FIRST_CLUSTER..=max_cluster).contains(&root_cluster) {
+            return Err(Error::NoVolume);
+        }
+
+        Ok(Self {
+            fat_start,
+            fat_size,
+            fat_count,
+            data_start,
+            blocks_per_cluster,
+            max_cluster,
+            root_cluster,
+            info: (info != 0 && info != 0xffff).then_some(start + info),
+            next_free: FIRST_CLUSTER,
+            block,
+            cached: None,
+        })
+    }
+
+    /// Opens file in root directory, file is created when there is none
+    pub async fn open<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        name: &ShortName,
+        time: NaiveDateTime,
+    ) -> Result<File, Error> {
+        let mut free = None;
+        let mut cluster = self.root_cluster;
+        'clusters: loop {
+            for index in self.cluster_blocks(cluster) {
+                self.load(device, index).await?;
+                for offset in (0..BLOCK_SIZE).step_by(ENTRY_SIZE) {
+                    let entry = &self.block[offset..offset + ENTRY_SIZE];
+                    match entry[0] {
+                        ENTRY_END => {
+                            free = free.or(Some((index, offset)));
+                            break 'clusters;
+                        }
+                        ENTRY_DELETED => free = free.or(Some((index, offset))),
+                        _ if entry[..11] == *name => {
+                            return self.opened(device, (index, offset)).await
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            match self.next_cluster(device, cluster).await? {
+                Some(next) => cluster = next,
+                None => break,
+            }
+        }
+
+        let entry = match free {
+            Some(entry) => entry,
+            // Every entry is taken, directory grows by a cluster
+            None => {
+                let added = self.allocate(device, Some(cluster)).await?;
+                for index in self.cluster_blocks(added) {
+                    self.block.fill(0);
+                    self.cached = Some(index);
+                    self.store(device).await?;
+                }
+                (self.cluster_block(added), 0)
+            }
+        };
+
+        self.load(device, entry.0).await?;
+        let record = &mut self.block[entry.1..entry.1 + ENTRY_SIZE];
+        record.fill(0);
+        record[..11].copy_from_slice(name);
+        record[11] = ATTRIBUTE_ARCHIVE;
+        let (date, time) = (fat_date(&time), fat_time(&time));
+        record[14..16].copy_from_slice(&time.to_le_bytes());
+        record[16..18].copy_from_slice(&date.to_le_bytes());
+        record[18..20].copy_from_slice(&date.to_le_bytes());
+        record[22..24].copy_from_slice(&time.to_le_bytes());
+        record[24..26].copy_from_slice(&date.to_le_bytes());
+        self.store(device).await?;
+
+        Ok(File {
+            entry,
+            first_cluster: 0,
+            last_cluster: 0,
+            size: 0,
+        })
+    }
+
+    /// Appends data to file and updates its directory entry
+    pub async fn append<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        file: &mut File,
+        data: &[u8],
+        time: NaiveDateTime,
+    ) -> Result<(), Error> {
+        let cluster_size = self.blocks_per_cluster * BLOCK_SIZE as u32;
+        let mut data = data;
+        while !data.is_empty() {
+            let cluster = if file.size == 0 && file.first_cluster != 0 {
+                file.first_cluster
+            } else if file.size == 0 {
+                let cluster = self.allocate(device, None).await?;
+                file.first_cluster = cluster;
+                cluster
+            } else if file.size.is_multiple_of(cluster_size) {
+                match self.next_cluster(device, file.last_cluster).await? {
+                    Some(next) => next,
+                    None => self.allocate(device, Some(file.last_cluster)).await?,
+                }
+            } else {
+                file.last_cluster
+            };
+
+            let offset = file.size % cluster_size;
+            let index = self.cluster_block(cluster) + offset / BLOCK_SIZE as u32;
+            let start = (offset as usize) % BLOCK_SIZE;
+            let length = data.len().min(BLOCK_SIZE - start);
+            if length < BLOCK_SIZE {
+                self.load(device, index).await?;
+            } else {
+                self.cached = Some(index);
+            }
+            self.block[start..start + length].copy_from_slice(&data[..length]);
+            self.store(device).await?;
+
+            data = &data[length..];
+            file.size += length as u32;
+            file.last_cluster = cluster;
+        }
+
+        let (index, offset) = file.entry;
+        self.load(device, index).await?;
+        let record = &mut self.block[offset..offset + ENTRY_SIZE];
+        let [low, low_high, high_low, high] = file.first_cluster.to_le_bytes();
+        record[20..22].copy_from_slice(&[high_low, high]);
+        record[26..28].copy_from_slice(&[low, low_high]);
+        record[22..24].copy_from_slice(&fat_time(&time).to_le_bytes());
+        record[24..26].copy_from_slice(&fat_date(&time).to_le_bytes());
+        record[28..32].copy_from_slice(&file.size.to_le_bytes());
+        self.store(device).await
+    }
+
+    /// Reads directory entry of existing file and finds its last cluster
+    async fn opened<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        entry: (u32, usize),
+    ) -> Result<File, Error> {
+        let record = &self.block[entry.1..entry.1 + ENTRY_SIZE];
+        let first_cluster = u32::from_le_bytes([record[26], record[27], record[20], record[21]]);
+        let size = u32::from_le_bytes([record[28], record[29], record[30], record[31]]);
+
+        let mut last_cluster = 0;
+        if size > 0 {
+            let cluster_size = self.blocks_per_cluster * BLOCK_SIZE as u32;
+            last_cluster = first_cluster;
+            for _ in 0..(size - 1) / cluster_size {
+                last_cluster = self
+                    .next_cluster(device, last_cluster)
+                    .await?
+                    .ok_or(Error::Damaged)?;
+            }
+        }
+        Ok(File {
+            entry,
+            first_cluster,
+            last_cluster,
+            size,
+        })
+    }
+
+    async fn next_cluster<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        cluster: u32,
+    ) -> Result<Option<u32>, Error> {
+        match self.fat_entry(device, cluster).await? {
+            next if next >= CLUSTER_END_MIN => Ok(None),
+            next if (FIRST_CLUSTER..=self.max_cluster).contains(&next) => Ok(Some(next)),
+            _ => Err(Error::Damaged),
+        }
+    }
+
+    /// Takes free cluster as the end of chain, which continues previous cluster
+    async fn allocate<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        previous: Option<u32>,
+    ) -> Result<u32, Error> {
+        if let Some(info) = self.info.take() {
+            self.invalidate_free_count(device, info).await?;
+        }
+
+        let mut cluster = self.next_free;
+        loop {
+            if self.fat_entry(device, cluster).await? == CLUSTER_FREE {
+                break;
+            }
+            cluster = if cluster == self.max_cluster {
+                FIRST_CLUSTER
+            } else {
+                cluster + 1
+            };
+            if cluster == self.next_free {
+                return Err(Error::Full);
+            }
+        }
+
+        self.set_fat_entry(device, cluster, CLUSTER_END).await?;
+        if let Some(previous) = previous {
+            self.set_fat_entry(device, previous, cluster).await?;
+        }
+        self.next_free = cluster;
+        Ok(cluster)
+    }
+
+    /// Free count kept by FSInfo is only a hint, unknown count makes others count again
+    async fn invalidate_free_count<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        info: u32,
+    ) -> Result<(), Error> {
+        self.load(device, info).await?;
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                self.block[offset],
+                self.block[offset + 1],
+                self.block[offset + 2],
+                self.block[offset + 3],
+            ])
+        };
+        if word(0) != FS_INFO_LEAD || word(484) != FS_INFO_STRUCT {
+            return Ok(());
+        }
+        self.block[FS_INFO_FREE_COUNT..FS_INFO_FREE_COUNT + 4]
+            .copy_from_slice(&FREE_COUNT_UNKNOWN.to_le_bytes());
+        self.store(device).await
+    }
+
+    async fn fat_entry<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        cluster: u32,
+    ) -> Result<u32, Error> {
+        let (index, offset) = self.fat_position(cluster);
+        self.load(device, index).await?;
+        let bytes = &self.block[offset..offset + 4];
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & CLUSTER_MASK)
+    }
+
+    /// Writes entry to every copy of FAT, keeping its reserved top bits
+    async fn set_fat_entry<D: BlockDevice>(
+        &mut self,
+        device: &mut D,
+        cluster: u32,
+        value: u32,
+    ) -> Result<(), Error> {
+        let (index, offset) = self.fat_position(cluster);
+        for copy in 0..self.fat_count {
+            self.load(device, index + copy * self.fat_size).await?;
+            let bytes = &mut self.block[offset..offset + 4];
+            let reserved =
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & !CLUSTER_MASK;
+            bytes.copy_from_slice(&(reserved | value).to_le_bytes());
+            self.store(device).await?;
+        }
+        Ok(())
+    }
+
+    fn fat_position(&self, cluster: u32) -> (u32, usize) {
+        let offset = cluster * 4;
+        (
+            self.fat_start + offset / BLOCK_SIZE as u32,
+            offset as usize % BLOCK_SIZE,
+        )
+    }
+
+    fn cluster_block(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - FIRST_CLUSTER) * self.blocks_per_cluster
+    }
+
+    fn cluster_blocks(&self, cluster: u32) -> core::ops::Range<u32> {
+        let first = self.cluster_block(cluster);
+        first..first + self.blocks_per_cluster
+    }
+
+    async fn load<D: BlockDevice>(&mut self, device: &mut D, index: u32) -> Result<(), Error> {
+        if self.cached != Some(index) {
+            // Block is not cached if reading it fails halfway
+            self.cached = None;
+            device.read(index, &mut self.block).await?;
+            self.cached = Some(index);
+        }
+        Ok(())
+    }
+
+    async fn store<D: BlockDevice>(&mut self, device: &mut D) -> Result<(), Error> {
+        let index = self.cached.ok_or(Error::Damaged)?;
+        let result = device.write(index, &self.block).await;
+        if result.is_err() {
+            self.cached = None;
+        }
+        Ok(result?)
+    }
+}
+
+/// Short name of `NAME.EXT`, `None` for names not fitting 8.3 upper case
+pub fn short_name(name: &str) -> Option<ShortName> {
+    let (stem, extension) = name.split_once('.').unwrap_or((name, ""));
+    let valid = |part: &str, length| {
+        !part.is_empty()
+            && part.len() <= length
+            && part
+                .bytes()
+                .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit() || byte == b'_')
+    };
+    if !valid(stem, 8) || !(extension.is_empty() || valid(extension, 3)) {
+        return None;
+    }
+
+    let mut short = [b' '; 11];
+    short[..stem.len()].copy_from_slice(stem.as_bytes());
+    short[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    Some(short)
+}
+
+fn fat_date(time: &NaiveDateTime) -> u16 {
+    let year = time.year().clamp(1980, 2107) as u16 - 1980;
+    (year << 9) | ((time.month() as u16) << 5) | time.day() as u16
+}
+
+fn fat_time(time: &NaiveDateTime) -> u16 {
+    ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() as u16 / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use ds323x::NaiveDate;
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::testing::RamCard;
+
+    fn time() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 7)
+            .unwrap()
+            .and_hms_opt(8, 30, 10)
+            .unwrap()
+    }
+
+    fn name(name: &str) -> ShortName {
+        short_name(name).unwrap()
+    }
+
+    async fn write_file(card: &mut RamCard, file_name: &str, data: &[u8]) -> Result<File, Error> {
+        let mut volume = Volume::mount(card).await?;
+        let mut file = volume.open(card, &name(file_name), time()).await?;
+        volume.append(card, &mut file, data, time()).await?;
+        Ok(file)
+    }
+
+    #[test]
+    fn pads_short_names() {
+        assert_eq!(&short_name("20240307.CSV").unwrap(), b"20240307CSV");
+        assert_eq!(&short_name("LOG").unwrap(), b"LOG        ");
+        assert_eq!(short_name("log.csv"), None, "lower case");
+        assert_eq!(short_name("202403071.CSV"), None, "long");
+        assert_eq!(short_name(".CSV"), None);
+    }
+
+    #[test]
+    fn writes_file_on_card_without_partition_table() {
+        let mut card = RamCard::formatted(false);
+        block_on(write_file(&mut card, "A.CSV", b"hello\r\n")).unwrap();
+        assert_eq!(card.read_file("A.CSV").unwrap(), b"hello\r\n");
+    }
+
+    #[test]
+    fn writes_file_on_first_partition() {
+        let mut card = RamCard::formatted(true);
+        block_on(write_file(&mut card, "A.CSV", b"hello\r\n")).unwrap();
+        assert_eq!(card.read_file("A.CSV").unwrap(), b"hello\r\n");
+        assert!(
+            card.blocks[..63]
+                .iter()
+                .skip(1)
+                .all(|block| block.iter().all(|byte| *byte == 0)),
+            "gap before partition untouched"
+        );
+    }
+
+    #[test]
+    fn stamps_directory_entry() {
+        let mut card = RamCard::formatted(false);
+        block_on(write_file(&mut card, "A.CSV", b"hello")).unwrap();
+        // Reserved blocks and two FATs of 16 blocks come first
+        let directory = &card.blocks[32 + 2 * 16];
+        let entry = &directory[..ENTRY_SIZE];
+        assert_eq!(entry[..11], *b"A       CSV");
+        assert_eq!(entry[11], ATTRIBUTE_ARCHIVE);
+        // 2024-03-07 08:30:10
+        let date = (44 << 9) | (3 << 5) | 7;
+        let time = (8 << 11) | (30 << 5) | 5;
+        assert_eq!(u16::from_le_bytes([entry[24], entry[25]]), date);
+        assert_eq!(u16::from_le_bytes([entry[22], entry[23]]), time);
+    }
+
+    #[test]
+    fn appends_across_blocks_and_clusters() {
+        let mut card = RamCard::format(2048, 2, false);
+        let data: Vec<u8> = (0..2400).map(|index| (index % 251) as u8).collect();
+        block_on(async {
+            let mut volume = Volume::mount(&mut card).await.unwrap();
+            let mut file = volume
+                .open(&mut card, &name("A.BIN"), time())
+                .await
+                .unwrap();
+            for part in [
+                &data[..700],
+                &data[700..1024],
+                &data[1024..1500],
+                &data[1500..],
+            ] {
+                volume
+                    .append(&mut card, &mut file, part, time())
+                    .await
+                    .unwrap();
+            }
+            assert_eq!(file.size, 2400);
+        });
+        assert_eq!(card.read_file("A.BIN").unwrap(), data);
+    }
+
+    #[test]
+    fn reopens_file_and_appends_to_its_end() {
+        let mut card = RamCard::formatted(true);
+        let first = [b'a'; 600];
+        block_on(write_file(&mut card, "A.CSV", &first)).unwrap();
+        let file = block_on(write_file(&mut card, "A.CSV", b"bcd")).unwrap();
+        assert_eq!(file.size, 603);
+
+        let mut expected = first.to_vec();
+        expected.extend_from_slice(b"bcd");
+        assert_eq!(card.read_file("A.CSV").unwrap(), expected);
+    }
+
+    #[test]
+    fn grows_root_directory() {
+        let mut card = RamCard::formatted(false);
+        block_on(async {
+            let mut volume = Volume::mount(&mut card).await.unwrap();
+            for index in 0..20 {
+                let file_name = std::format!("F{index}.CSV");
+                let mut file = volume
+                    .open(&mut card, &name(&file_name), time())
+                    .await
+                    .unwrap();
+                volume
+                    .append(&mut card, &mut file, file_name.as_bytes(), time())
+                    .await
+                    .unwrap();
+            }
+        });
+        assert_ne!(
+            card.fat_entry(2),
+            CLUSTER_END,
+            "second cluster of directory"
+        );
+        for index in 0..20 {
+            let file_name = std::format!("F{index}.CSV");
+            assert_eq!(card.read_file(&file_name).unwrap(), file_name.as_bytes());
+        }
+    }
+
+    #[test]
+    fn marks_free_count_unknown_once_allocated() {
+        let mut card = RamCard::formatted(false);
+        assert_ne!(card.free_count(), FREE_COUNT_UNKNOWN);
+        block_on(write_file(&mut card, "A.CSV", b"hello")).unwrap();
+        assert_eq!(card.free_count(), FREE_COUNT_UNKNOWN);
+    }
+
+    #[test]
+    fn reports_full_volume() {
+        // Root directory and 5 free clusters
+        let mut card = RamCard::format(40, 1, false);
+        let data = [b'x'; 5 * BLOCK_SIZE];
+        block_on(write_file(&mut card, "A.CSV", &data)).unwrap();
+        assert_eq!(
+            block_on(write_file(&mut card, "A.CSV", b"y")),
+            Err(Error::Full)
+        );
+        assert_eq!(card.read_file("A.CSV").unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_fat16_volume() {
+        let mut card = RamCard::formatted(true);
+        let boot = &mut card.blocks[63];
+        boot[0x16] = 32;
+        boot[0x52..0x5a].fill(0);
+        assert_eq!(
+            block_on(Volume::mount(&mut card)).err(),
+            Some(Error::NoVolume)
+        );
+    }
+
+    #[test]
+    fn rejects_card_without_volume() {
+        let mut card = RamCard::formatted(false);
+        card.blocks[0].fill(0);
+        assert_eq!(
+            block_on(Volume::mount(&mut card)).err(),
+            Some(Error::NoVolume)
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//!
+//! Daily CSV files of measurements on microSD card
+//!
+//! Every day gets its own `YYYYMMDD.CSV` file in the root directory of a FAT32 card, so files
+//! stay small enough to open on any computer and a damaged file loses one day at most. Rows
+//! hold UTC time, temperature of each sensor, combined temperature and humidity; sensor
+//! fields are left empty while sensor is not read successfully. Measurements taken before
+//! clock is synchronized are not logged, as they have no day to go to.
+//!
+//! Rows are collected in memory and written at an interval, which spares the card a write
+//! of the same blocks every minute. Card can be removed at any time: rows wait in memory
+//! until a card is inserted again and are dropped only once they no longer fit.
+//!
+
+pub mod fat;
+mod runner;
+
+use core::fmt::Write as _;
+
+use ds323x::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use heapless::{String, Vec};
+use ufmt::uwrite;
+
+use crate::{
+    display::drawables::measurement_text::write_value,
+    drivers::{sdcard::BlockDevice, sensors::status::SensorStatus},
+    measurement::{Measurement, SensorId, Timestamp},
+};
+pub use fat::Error;
+use fat::{File, Volume};
+pub use runner::{new, Runner};
+
+/// Length of `YYYYMMDD.CSV`
+pub const FILE_NAME_SIZE: usize = 12;
+
+/// Longest row, header included
+pub const MAX_ROW_SIZE: usize = 64;
+
+/// Rows waiting to be written, about 20 minutes of one row per minute
+pub const PENDING_SIZE: usize = 1024;
+
+pub type Row = String<MAX_ROW_SIZE>;
+
+/// Name of file holding measurements of the day of timestamp
+pub fn file_name(timestamp: &Timestamp) -> String<FILE_NAME_SIZE> {
+    let date = timestamp.0.date();
+    let mut name = String::new();
+    write!(
+        name,
+        "{:04}{:02}{:02}.CSV",
+        date.year(),
+        date.month(),
+        date.day()
+    )
+    .ok();
+    name
+}
+
+/// First row of every file, ends with CRLF
+pub fn header() -> Row {
+    let mut row = Row::new();
+    row.push_str("time").ok();
+    for id in SensorId::ALL {
+        uwrite!(row, ",{}", id.name()).ok();
+    }
+    row.push_str(",temperature,humidity\r\n").ok();
+    row
+}
+
+/// Row of measurement taken at timestamp, ends with CRLF
+pub fn row(timestamp: &Timestamp, measurement: &Measurement) -> Row {
+    let date = timestamp.0.date();
+    let time = timestamp.0.time();
+    let mut row = Row::new();
+    write!(
+        row,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        date.year(),
+        date.month(),
+        date.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+    .ok();
+
+    let mut value: String<MAX_ROW_SIZE> = String::new();
+    // Field is left empty for values not read, spreadsheets take placeholders for text
+    let mut field = |row: &mut Row, number: f32, decimals: u8, read: bool| {
+        value.clear();
+        if read && number.is_finite() {
+            write_value(Some(number), decimals, &mut value);
+        }
+        uwrite!(row, ",{}", value.as_str()).ok();
+    };
+    for id in SensorId::ALL {
+        let read = measurement.statuses[id as usize] == SensorStatus::Ok;
+        field(&mut row, measurement.sensor_temperature(id), 2, read);
+    }
+    field(&mut row, measurement.temperature, 2, true);
+    field(&mut row, measurement.humidity, 1, true);
+    row.push_str("\r\n").ok();
+    row
+}
+
+/// Writes rows of measurements to card, mounting it as needed
+pub struct Logger<D> {
+    card: D,
+    /// `None` until card is mounted, card is mounted again after any error
+    volume: Option<Volume>,
+    /// File of day last written to
+    file: Option<(NaiveDate, File)>,
+    pending: Vec<u8, PENDING_SIZE>,
+    /// Day of pending rows and time of last of them
+    latest: Option<NaiveDateTime>,
+}
+
+impl<D: BlockDevice> Logger<D> {
+    pub fn new(card: D) -> Self {
+        Self {
+            card,
+            volume: None,
+            file: None,
+            pending: Vec::new(),
+            latest: None,
+        }
+    }
+
+    /// Bytes of rows waiting to be written
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Queues row of measurement, rows of previous day or rows not fitting are written first
+    ///
+    /// Rows queued earlier are dropped when they can not be written to make room.
+    pub async fn append(&mut self, measurement: &Measurement) -> Result<(), Error> {
+        let Some(timestamp) = measurement.timestamp else {
+            return Ok(());
+        };
+        let row = row(&timestamp, measurement);
+
+        let other_day = self
+            .latest
+            .is_some_and(|latest| latest.date() != timestamp.0.date());
+        let full = self.pending.len() + row.len() > PENDING_SIZE;
+        let mut result = Ok(());
+        if other_day || full {
+            result = self.flush().await;
+            if !self.pending.is_empty() {
+                defmt::warn!("sdlog: dropped {=usize} bytes", self.pending.len());
+                self.pending.clear();
+            }
+        }
+
+        self.pending.extend_from_slice(row.as_bytes()).ok();
+        self.latest = Some(timestamp.0);
+        result
+    }
+
+    /// Writes pending rows to file of their day, file is created with header first
+    ///
+    /// Card is mounted when it is not, so flush with nothing pending mounts an inserted card.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let result = self.write_pending().await;
+        match result {
+            Ok(()) => self.pending.clear(),
+            // Card could be replaced by the next attempt
+            Err(_) => self.eject(),
+        }
+        result
+    }
+
+    /// Forgets mounted volume, card is mounted again before the next write
+    pub fn eject(&mut self) {
+        self.volume = None;
+        self.file = None;
+    }
+
+    async fn write_pending(&mut self) -> Result<(), Error> {
+        let volume = match &mut self.volume {
+            Some(volume) => volume,
+            None => {
+                self.card.init().await?;
+                let volume = Volume::mount(&mut self.card).await?;
+                defmt::info!("sdlog: mounted");
+                self.volume.insert(volume)
+            }
+        };
+
+        let Some(latest) = self.latest.filter(|_| !self.pending.is_empty()) else {
+            return Ok(());
+        };
+        let day = latest.date();
+        let file = match &mut self.file {
+            Some((file_day, file)) if *file_day == day => file,
+            file => {
+                let name = file_name(&Timestamp(latest));
+                let name = fat::short_name(&name).ok_or(Error::Damaged)?;
+                let opened = volume.open(&mut self.card, &name, latest).await?;
+                &mut file.insert((day, opened)).1
+            }
+        };
+
+        if file.size == 0 {
+            volume
+                .append(&mut self.card, file, header().as_bytes(), latest)
+                .await?;
+        }
+        volume
+            .append(&mut self.card, file, &self.pending, latest)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ds323x::NaiveDate;
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::testing::RamCard;
+
+    fn timestamp(day: u32, hour: u32, minute: u32) -> Timestamp {
+        Timestamp(
+            NaiveDate::from_ymd_opt(2024, 3, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 5)
+                .unwrap(),
+        )
+    }
+
+    fn measurement(timestamp: Option<Timestamp>) -> Measurement {
+        Measurement {
+            timestamp,
+            temperatures: [21.5, 22.25],
+            statuses: [SensorStatus::Ok, SensorStatus::Ok],
+            temperature: 21.875,
+            humidity: 45.25,
+        }
+    }
+
+    fn contents(card: &mut RamCard, name: &str) -> std::string::String {
+        let bytes = card.read_file(name).expect("file");
+        std::string::String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn names_file_by_day() {
+        assert_eq!(file_name(&timestamp(7, 23, 59)).as_str(), "20240307.CSV");
+    }
+
+    #[test]
+    fn formats_header_and_rows() {
+        assert_eq!(
+            header().as_str(),
+            "time,LM75,DHT22,temperature,humidity\r\n"
+        );
+        assert_eq!(
+            row(&timestamp(7, 8, 30), &measurement(None)).as_str(),
+            "2024-03-07T08:30:05Z,21.50,22.25,21.88,45.3\r\n"
+        );
+    }
+
+    #[test]
+    fn leaves_fields_of_sensors_not_read_empty() {
+        let mut measurement = measurement(None);
+        measurement.statuses[SensorId::Dht22 as usize] = SensorStatus::Error;
+        measurement.humidity = f32::NAN;
+        assert_eq!(
+            row(&timestamp(7, 8, 30), &measurement).as_str(),
+            "2024-03-07T08:30:05Z,21.50,,21.88,\r\n"
+        );
+    }
+
+    #[test]
+    fn writes_header_once_and_rows_on_flush() {
+        block_on(async {
+            let mut logger = Logger::new(RamCard::formatted(false));
+            logger
+                .append(&measurement(Some(timestamp(7, 8, 30))))
+                .await
+                .unwrap();
+            logger.append(&measurement(None)).await.unwrap();
+            assert!(
+                logger.card.read_file("20240307.CSV").is_none(),
+                "not flushed"
+            );
+            logger.flush().await.unwrap();
+            assert_eq!(logger.pending(), 0);
+
+            logger
+                .append(&measurement(Some(timestamp(7, 8, 31))))
+                .await
+                .unwrap();
+            logger.flush().await.unwrap();
+            assert_eq!(
+                contents(&mut logger.card, "20240307.CSV"),
+                "time,LM75,DHT22,temperature,humidity\r\n\
+                 2024-03-07T08:30:05Z,21.50,22.25,21.88,45.3\r\n\
+                 2024-03-07T08:31:05Z,21.50,22.25,21.88,45.3\r\n"
+            );
+        })
+    }
+
+    #[test]
+    fn starts_new_file_on_day_change() {
+        block_on(async {
+            let mut logger = Logger::new(RamCard::formatted(true));
+            logger
+                .append(&measurement(Some(timestamp(7, 23, 59))))
+                .await
+                .unwrap();
+            logger
+                .append(&measurement(Some(timestamp(8, 0, 0))))
+                .await
+                .unwrap();
+            logger.flush().await.unwrap();
+
+            let first = contents(&mut logger.card, "20240307.CSV");
+            assert_eq!(first.lines().count(), 2);
+            assert!(first.ends_with("2024-03-07T23:59:05Z,21.50,22.25,21.88,45.3\r\n"));
+            let second = contents(&mut logger.card, "20240308.CSV");
+            assert!(second.starts_with("time,"));
+            assert!(second.ends_with("2024-03-08T00:00:05Z,21.50,22.25,21.88,45.3\r\n"));
+        })
+    }
+
+    #[test]
+    fn keeps_rows_while_card_is_removed() {
+        block_on(async {
+            let mut logger = Logger::new(RamCard::formatted(false));
+            logger
+                .append(&measurement(Some(timestamp(7, 8, 30))))
+                .await
+                .unwrap();
+            logger.flush().await.unwrap();
+
+            logger.card.inserted = false;
+            logger
+                .append(&measurement(Some(timestamp(7, 8, 31))))
+                .await
+                .unwrap();
+            assert!(logger.flush().await.is_err());
+            assert!(logger.pending() > 0, "kept for next card");
+
+            logger.card.inserted = true;
+            logger.flush().await.unwrap();
+            let contents = contents(&mut logger.card, "20240307.CSV");
+            assert_eq!(contents.lines().count(), 3);
+            assert!(contents.ends_with("2024-03-07T08:31:05Z,21.50,22.25,21.88,45.3\r\n"));
+        })
+    }
+
+    #[test]
+    fn mounts_replaced_card_after_eject() {
+        block_on(async {
+            let mut logger = Logger::new(RamCard::formatted(false));
+            logger
+                .append(&measurement(Some(timestamp(7, 8, 30))))
+                .await
+                .unwrap();
+            logger.flush().await.unwrap();
+
+            logger.eject();
+            logger.card = RamCard::formatted(true);
+            logger
+                .append(&measurement(Some(timestamp(7, 8, 31))))
+                .await
+                .unwrap();
+            logger.flush().await.unwrap();
+            let contents = contents(&mut logger.card, "20240307.CSV");
+            assert!(contents.starts_with("time,"), "header on new card");
+            assert_eq!(contents.lines().count(), 2);
+        })
+    }
+
+    #[test]
+    fn drops_rows_not_fitting_while_card_is_removed() {
+        block_on(async {
+            let mut card = RamCard::formatted(false);
+            card.inserted = false;
+            let mut logger = Logger::new(card);
+            let row_size = row(&timestamp(7, 8, 0), &measurement(None)).len();
+
+            let mut failed = 0;
+            for minute in 0..30 {
+                let result = logger
+                    .append(&measurement(Some(timestamp(7, 8, minute))))
+                    .await;
+                if result.is_err() {
+                    failed += 1;
+                    assert_eq!(logger.pending(), row_size, "newest row kept");
+                }
+            }
+            assert_eq!(failed, 1);
+            assert_eq!(logger.pending(), (30 - PENDING_SIZE / row_size) * row_size);
+        })
+    }
+}
@@ -0,0 +1,88 @@
+//!
+//! Task logging measurements to microSD card
+//!
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::InputPin;
+
+use super::Logger;
+use crate::{drivers::sdcard::BlockDevice, measurement::MeasurementChannel};
+
+/// Time between checks of card detect switch when no measurement comes
+const DETECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Queues one measurement per interval and writes them to card at flush interval
+pub struct Runner<'a, D, P> {
+    measurements: &'a MeasurementChannel,
+    logger: Logger<D>,
+    /// Low while card is inserted
+    detect_pin: P,
+    interval: Duration,
+    flush_interval: Duration,
+}
+
+impl<D: BlockDevice, P: InputPin> Runner<'_, D, P> {
+    pub async fn run(mut self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+
+        let mut inserted = false;
+        let mut log_time = Instant::now();
+        let mut flush_time = Instant::now();
+        loop {
+            let received = select(
+                subscriber.next_message_pure(),
+                Timer::after(DETECT_INTERVAL),
+            )
+            .await;
+            if let Either::First(measurement) = received {
+                if Instant::now() >= log_time {
+                    log_time = Instant::now() + self.interval;
+                    if let Err(err) = self.logger.append(&measurement).await {
+                        defmt::warn!("sdlog: not written: {}", err);
+                    }
+                }
+            }
+
+            let present = self.detect_pin.is_low().unwrap_or(false);
+            if present != inserted {
+                inserted = present;
+                if inserted {
+                    defmt::info!("sdlog: card inserted");
+                    // Mounted right away, so a card that does not work is told at once
+                    flush_time = Instant::now();
+                } else {
+                    defmt::info!(
+                        "sdlog: card removed, {=usize} bytes pending",
+                        self.logger.pending()
+                    );
+                    self.logger.eject();
+                }
+            }
+
+            if inserted && Instant::now() >= flush_time {
+                flush_time = Instant::now() + self.flush_interval;
+                if let Err(err) = self.logger.flush().await {
+                    defmt::warn!("sdlog: not written: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Creates logger queueing one measurement per interval and writing them at flush interval
+pub fn new<D: BlockDevice, P>(
+    measurements: &MeasurementChannel,
+    card: D,
+    detect_pin: P,
+    interval: Duration,
+    flush_interval: Duration,
+) -> Runner<'_, D, P> {
+    Runner {
+        measurements,
+        logger: Logger::new(card),
+        detect_pin,
+        interval,
+        flush_interval,
+    }
+}
@@ -0,0 +1,366 @@
+//!
+//! Commands of serial shell
+//!
+//! Parsing does no i/o and needs no peripherals, so it can be checked on host.
+//!
+
+use core::str::SplitAsciiWhitespace;
+
+use crate::{
+    drivers::sensors::lm75, measurement::SensorId, schedule::Period, units::TemperatureUnit,
+};
+
+/// What is printed when new measurement is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamMode {
+    Off,
+    /// Summary line of every measurement
+    Text,
+    /// Telemetry frames, see [telemetry]
+    Binary,
+}
+
+/// What is done with measurement log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogAction {
+    /// Prints all records as CSV
+    Dump,
+    Clear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Help,
+    /// Lists sensors with their last readings
+    Sensors,
+    Stream(StreamMode),
+    /// Shows number of failed readings of each sensor
+    Errors,
+    /// Sets time between readings of sensor
+    Interval {
+        sensor: SensorId,
+        millis: u32,
+    },
+    /// Sets when measurements are taken
+    Period(Period),
+    /// Sets offset added to readings of sensor, in preferred unit
+    Calibrate {
+        sensor: SensorId,
+        offset: f32,
+    },
+    /// Sets overtemperature thresholds of LM75, in preferred unit
+    Thresholds {
+        os: f32,
+        hysteresis: f32,
+    },
+    Unit(TemperatureUnit),
+    /// Lists devices answering on i2c bus
+    Scan,
+    Log(LogAction),
+    /// Lists hourly summaries kept in EEPROM
+    Hourly,
+    /// Saves current configuration to flash
+    Save,
+    /// Erases saved configuration and restarts with defaults
+    FactoryReset,
+    Reboot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format, thiserror::Error)]
+pub enum ParseError {
+    #[error("Empty line")]
+    Empty,
+    #[error("Unknown command")]
+    UnknownCommand,
+    #[error("Missing argument")]
+    MissingArgument,
+    #[error("Too many arguments")]
+    UnexpectedArgument,
+    #[error("Unknown sensor")]
+    InvalidSensor,
+    #[error("Invalid number")]
+    InvalidNumber,
+    #[error("Invalid period")]
+    InvalidPeriod,
+    #[error("Unknown unit")]
+    InvalidUnit,
+    #[error("Unknown stream mode")]
+    InvalidStreamMode,
+    #[error("Unknown log action")]
+    InvalidLogAction,
+    /// Hysteresis is not below OS temperature
+    #[error("Hysteresis must be below OS temperature")]
+    InvalidThresholds,
+}
+
+/// Usage of commands, one line per command
+pub const HELP: &[&str] = &[
+    "help                         this text",
+    "sensors                      last readings",
+    "stream off|on|binary         print new measurements",
+    "errors                       failed readings",
+    "interval <sensor> <ms>       time between readings",
+    "period second|minute|hour    when measurements are taken",
+    "period daily <hh:mm>         measure once a day, UTC",
+    "calibrate <sensor> <offset>  offset added to readings",
+    "thresholds <os> <hyst>       LM75 overtemperature",
+    "unit c|f|k                   temperature unit",
+    "scan                         list i2c devices",
+    "log dump|clear               measurement log",
+    "hourly                       hourly min/max/mean",
+    "save                         keep configuration",
+    "factory-reset                erase configuration",
+    "reboot                       restart firmware",
+];
+
+/// Parses line, words are separated by any whitespace and commands are case-insensitive
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    let mut args = line.split_ascii_whitespace();
+    let name = args.next().ok_or(ParseError::Empty)?;
+    let is = |expected: &str| name.eq_ignore_ascii_case(expected);
+
+    let command = if is("help") || name == "?" {
+        Command::Help
+    } else if is("sensors") {
+        Command::Sensors
+    } else if is("stream") {
+        Command::Stream(parse_stream_mode(&mut args)?)
+    } else if is("errors") {
+        Command::Errors
+    } else if is("interval") {
+        let sensor = parse_sensor(&mut args)?;
+        let millis = parse_number(&mut args)?;
+        if millis == 0 {
+            return Err(ParseError::InvalidNumber);
+        }
+        Command::Interval { sensor, millis }
+    } else if is("period") {
+        Command::Period(parse_period(&mut args)?)
+    } else if is("calibrate") {
+        let sensor = parse_sensor(&mut args)?;
+        let offset = parse_temperature(&mut args)?;
+        Command::Calibrate { sensor, offset }
+    } else if is("thresholds") {
+        let os = parse_temperature(&mut args)?;
+        let hysteresis = parse_temperature(&mut args)?;
+        if !lm75::valid_thresholds(os, hysteresis) {
+            return Err(ParseError::InvalidThresholds);
+        }
+        Command::Thresholds { os, hysteresis }
+    } else if is("unit") {
+        Command::Unit(parse_unit(&mut args)?)
+    } else if is("scan") {
+        Command::Scan
+    } else if is("log") {
+        Command::Log(parse_log_action(&mut args)?)
+    } else if is("hourly") {
+        Command::Hourly
+    } else if is("save") {
+        Command::Save
+    } else if is("factory-reset") {
+        Command::FactoryReset
+    } else if is("reboot") {
+        Command::Reboot
+    } else {
+        return Err(ParseError::UnknownCommand);
+    };
+
+    match args.next() {
+        Some(_) => Err(ParseError::UnexpectedArgument),
+        None => Ok(command),
+    }
+}
+
+fn next_arg<'a>(args: &mut SplitAsciiWhitespace<'a>) -> Result<&'a str, ParseError> {
+    args.next().ok_or(ParseError::MissingArgument)
+}
+
+fn parse_sensor(args: &mut SplitAsciiWhitespace) -> Result<SensorId, ParseError> {
+    SensorId::from_name(next_arg(args)?).ok_or(ParseError::InvalidSensor)
+}
+
+fn parse_number<T: core::str::FromStr>(args: &mut SplitAsciiWhitespace) -> Result<T, ParseError> {
+    next_arg(args)?
+        .parse()
+        .map_err(|_| ParseError::InvalidNumber)
+}
+
+/// Temperature or offset, `nan` and `inf` are refused as they would reach sensors and flash
+fn parse_temperature(args: &mut SplitAsciiWhitespace) -> Result<f32, ParseError> {
+    let value: f32 = parse_number(args)?;
+    if !value.is_finite() {
+        return Err(ParseError::InvalidNumber);
+    }
+    Ok(value)
+}
+
+fn parse_stream_mode(args: &mut SplitAsciiWhitespace) -> Result<StreamMode, ParseError> {
+    let name = next_arg(args)?;
+    let is = |expected: &str| name.eq_ignore_ascii_case(expected);
+
+    if is("off") {
+        Ok(StreamMode::Off)
+    } else if is("on") || is("text") {
+        Ok(StreamMode::Text)
+    } else if is("binary") {
+        Ok(StreamMode::Binary)
+    } else {
+        Err(ParseError::InvalidStreamMode)
+    }
+}
+
+fn parse_log_action(args: &mut SplitAsciiWhitespace) -> Result<LogAction, ParseError> {
+    let name = next_arg(args)?;
+    let is = |expected: &str| name.eq_ignore_ascii_case(expected);
+
+    if is("dump") {
+        Ok(LogAction::Dump)
+    } else if is("clear") {
+        Ok(LogAction::Clear)
+    } else {
+        Err(ParseError::InvalidLogAction)
+    }
+}
+
+fn parse_period(args: &mut SplitAsciiWhitespace) -> Result<Period, ParseError> {
+    let name = next_arg(args)?;
+    let is = |expected: &str| name.eq_ignore_ascii_case(expected);
+
+    if is("second") {
+        Ok(Period::EverySecond)
+    } else if is("minute") {
+        Ok(Period::EveryMinute)
+    } else if is("hour") {
+        Ok(Period::EveryHour)
+    } else if is("daily") {
+        let (hour, minute) = next_arg(args)?
+            .split_once(':')
+            .ok_or(ParseError::InvalidPeriod)?;
+        let hour: u8 = hour.parse().map_err(|_| ParseError::InvalidPeriod)?;
+        let minute: u8 = minute.parse().map_err(|_| ParseError::InvalidPeriod)?;
+        if hour > 23 || minute > 59 {
+            return Err(ParseError::InvalidPeriod);
+        }
+        Ok(Period::Daily { hour, minute })
+    } else {
+        Err(ParseError::InvalidPeriod)
+    }
+}
+
+fn parse_unit(args: &mut SplitAsciiWhitespace) -> Result<TemperatureUnit, ParseError> {
+    let name = next_arg(args)?;
+    TemperatureUnit::ALL
+        .into_iter()
+        .find(|unit| {
+            // Symbol without degree sign, e.g. "C" of "°C"
+            let letter = unit.symbol().trim_start_matches('°');
+            name.eq_ignore_ascii_case(letter)
+        })
+        .ok_or(ParseError::InvalidUnit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_case_insensitive() {
+        assert_eq!(parse("HELP"), Ok(Command::Help));
+        assert_eq!(parse("Sensors"), Ok(Command::Sensors));
+        assert_eq!(
+            parse("STREAM Binary"),
+            Ok(Command::Stream(StreamMode::Binary))
+        );
+        assert_eq!(parse("log DUMP"), Ok(Command::Log(LogAction::Dump)));
+        assert_eq!(
+            parse("unit F"),
+            Ok(Command::Unit(TemperatureUnit::Fahrenheit))
+        );
+        assert_eq!(parse("Period Hour"), Ok(Command::Period(Period::EveryHour)));
+        assert_eq!(
+            parse("interval dht22 2500"),
+            Ok(Command::Interval {
+                sensor: SensorId::Dht22,
+                millis: 2500
+            })
+        );
+    }
+
+    #[test]
+    fn words_are_separated_by_any_whitespace() {
+        assert_eq!(
+            parse("  calibrate\tLM75   -0.5 "),
+            Ok(Command::Calibrate {
+                sensor: SensorId::Lm75,
+                offset: -0.5
+            })
+        );
+        assert_eq!(parse(" \t "), Err(ParseError::Empty));
+    }
+
+    #[test]
+    fn arguments_are_counted() {
+        assert_eq!(parse("interval lm75"), Err(ParseError::MissingArgument));
+        assert_eq!(parse("reboot now"), Err(ParseError::UnexpectedArgument));
+        assert_eq!(parse("unit c f"), Err(ParseError::UnexpectedArgument));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert_eq!(parse("frobnicate"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("interval bmp280 100"), Err(ParseError::InvalidSensor));
+        assert_eq!(parse("interval lm75 0"), Err(ParseError::InvalidNumber));
+        assert_eq!(parse("interval lm75 -5"), Err(ParseError::InvalidNumber));
+        assert_eq!(parse("calibrate lm75 1,5"), Err(ParseError::InvalidNumber));
+        assert_eq!(parse("calibrate lm75 nan"), Err(ParseError::InvalidNumber));
+        assert_eq!(parse("thresholds inf 0"), Err(ParseError::InvalidNumber));
+        assert_eq!(parse("thresholds 80 -inf"), Err(ParseError::InvalidNumber));
+        assert_eq!(parse("unit r"), Err(ParseError::InvalidUnit));
+        assert_eq!(parse("stream loud"), Err(ParseError::InvalidStreamMode));
+        assert_eq!(parse("log erase"), Err(ParseError::InvalidLogAction));
+    }
+
+    #[test]
+    fn daily_period_takes_valid_time_only() {
+        assert_eq!(
+            parse("period daily 07:30"),
+            Ok(Command::Period(Period::Daily {
+                hour: 7,
+                minute: 30
+            }))
+        );
+        assert_eq!(
+            parse("period daily 23:59"),
+            Ok(Command::Period(Period::Daily {
+                hour: 23,
+                minute: 59
+            }))
+        );
+        for time in ["24:00", "12:60", "1230", "12:", ":30", "-1:00"] {
+            let line = format!("period daily {time}");
+            assert_eq!(parse(&line), Err(ParseError::InvalidPeriod), "{line}");
+        }
+        assert_eq!(parse("period daily"), Err(ParseError::MissingArgument));
+        assert_eq!(parse("period weekly"), Err(ParseError::InvalidPeriod));
+    }
+
+    #[test]
+    fn hysteresis_must_be_below_os_temperature() {
+        assert_eq!(
+            parse("thresholds 80 75"),
+            Ok(Command::Thresholds {
+                os: 80.0,
+                hysteresis: 75.0
+            })
+        );
+        assert_eq!(
+            parse("thresholds 75 80"),
+            Err(ParseError::InvalidThresholds)
+        );
+        assert_eq!(
+            parse("thresholds 80 80"),
+            Err(ParseError::InvalidThresholds)
+        );
+        assert_eq!(parse("thresholds 80"), Err(ParseError::MissingArgument));
+    }
+}
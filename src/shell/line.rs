@@ -0,0 +1,149 @@
+//!
+//! Line editing of terminal input
+//!
+//! Terminals send characters as they are typed and expect them to be echoed back, so the
+//! buffer tells what has to be shown for every received byte.
+//!
+
+use heapless::Vec;
+
+pub const LINE_SIZE: usize = 64;
+
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7F;
+
+/// What received byte did to the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// Byte was appended and should be echoed
+    Insert(u8),
+    /// Last byte was removed and should be erased from terminal
+    Erase,
+    /// Line is complete
+    Submit,
+    /// Byte was dropped: line is full, or nothing to erase, or control character
+    Ignore,
+}
+
+#[derive(Default)]
+pub struct LineBuffer {
+    line: Vec<u8, LINE_SIZE>,
+    /// Line ended with CR, so LF of CRLF is skipped
+    after_cr: bool,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Edit {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+
+        match byte {
+            b'\r' => Edit::Submit,
+            b'\n' if after_cr => Edit::Ignore,
+            b'\n' => Edit::Submit,
+            BACKSPACE | DELETE => match self.line.pop() {
+                Some(_) => Edit::Erase,
+                None => Edit::Ignore,
+            },
+            byte if byte.is_ascii_graphic() || byte == b' ' => match self.line.push(byte) {
+                Ok(()) => Edit::Insert(byte),
+                Err(_) => Edit::Ignore,
+            },
+            _ => Edit::Ignore,
+        }
+    }
+
+    /// Line typed so far, only printable ASCII is accepted so it is always valid text
+    pub fn line(&self) -> &str {
+        core::str::from_utf8(&self.line).unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        self.line.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(buffer: &mut LineBuffer, bytes: &[u8]) -> std::vec::Vec<Edit> {
+        bytes.iter().map(|&byte| buffer.feed(byte)).collect()
+    }
+
+    #[test]
+    fn printable_bytes_are_echoed() {
+        let mut buffer = LineBuffer::new();
+        let edits = feed(&mut buffer, b"unit c");
+
+        assert!(edits
+            .iter()
+            .zip(b"unit c")
+            .all(|(edit, &byte)| *edit == Edit::Insert(byte)));
+        assert_eq!(buffer.line(), "unit c");
+    }
+
+    #[test]
+    fn every_line_ending_submits_once() {
+        for ending in [&b"\r"[..], b"\n", b"\r\n"] {
+            let mut buffer = LineBuffer::new();
+            feed(&mut buffer, b"help");
+            let submits = feed(&mut buffer, ending)
+                .into_iter()
+                .filter(|edit| *edit == Edit::Submit)
+                .count();
+            assert_eq!(submits, 1, "{ending:?}");
+        }
+    }
+
+    #[test]
+    fn lf_after_crlf_submits_empty_line() {
+        let mut buffer = LineBuffer::new();
+        assert_eq!(
+            feed(&mut buffer, b"\r\n\n"),
+            [Edit::Submit, Edit::Ignore, Edit::Submit]
+        );
+        assert_eq!(feed(&mut buffer, b"\r\r"), [Edit::Submit, Edit::Submit]);
+    }
+
+    #[test]
+    fn backspace_and_delete_erase_last_byte() {
+        let mut buffer = LineBuffer::new();
+        feed(&mut buffer, b"scam");
+        assert_eq!(buffer.feed(BACKSPACE), Edit::Erase);
+        assert_eq!(buffer.feed(b'n'), Edit::Insert(b'n'));
+        assert_eq!(buffer.line(), "scan");
+
+        assert_eq!(feed(&mut buffer, &[DELETE; 5]).last(), Some(&Edit::Ignore));
+        assert_eq!(buffer.line(), "");
+    }
+
+    #[test]
+    fn control_and_non_ascii_bytes_are_ignored() {
+        let mut buffer = LineBuffer::new();
+        assert_eq!(
+            feed(&mut buffer, &[0x1B, b'\t', 0xC3, 0xA9]),
+            [Edit::Ignore; 4]
+        );
+        assert_eq!(buffer.line(), "");
+    }
+
+    #[test]
+    fn overflowing_bytes_are_dropped() {
+        let mut buffer = LineBuffer::new();
+        feed(&mut buffer, &[b'x'; LINE_SIZE]);
+        assert_eq!(buffer.feed(b'y'), Edit::Ignore);
+        assert_eq!(buffer.line().len(), LINE_SIZE);
+
+        // Line can still be edited and submitted
+        assert_eq!(buffer.feed(BACKSPACE), Edit::Erase);
+        assert_eq!(buffer.feed(b'y'), Edit::Insert(b'y'));
+        assert_eq!(buffer.feed(b'\r'), Edit::Submit);
+
+        buffer.clear();
+        assert_eq!(buffer.line(), "");
+    }
+}
@@ -0,0 +1,21 @@
+//!
+//! Line-oriented shell on serial port
+//!
+//! Sensors can be inspected and configured without a debug probe: connect a terminal to the
+//! ST-LINK virtual COM port at 115200 baud and type `help`. Temperatures are shown and entered
+//! in the preferred unit.
+//!
+//! Shell works on any byte stream, so the same commands are served on other ports, e.g. the
+//! [USB serial device](crate::usb).
+//!
+//! With `stream binary` the port carries [telemetry](crate::telemetry) frames for host tools.
+//! Typed characters are not echoed then, so only command replies are mixed into the stream.
+//!
+
+pub mod command;
+pub mod line;
+#[cfg(feature = "board")]
+mod runner;
+
+#[cfg(feature = "board")]
+pub use runner::{new, Context, Runner};
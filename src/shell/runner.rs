@@ -0,0 +1,466 @@
+//!
+//! Shell task reading commands from byte stream
+//!
+
+use core::{fmt::Write as _, ops::RangeInclusive};
+
+use embassy_futures::select::{select3, Either3};
+use embassy_time::Duration;
+use embedded_hal::i2c::I2c as _;
+use embedded_io_async::{Read, Write};
+use heapless::String;
+use ufmt::uwrite;
+
+use super::{
+    command::{self, Command, LogAction, ParseError, StreamMode, HELP},
+    line::{Edit, LineBuffer},
+};
+use crate::{
+    bsp::I2cShared,
+    calibration,
+    config::{self, Settings, SharedStore},
+    datalog::{self, ring::Position, Entry, SharedLog},
+    display::drawables::measurement_text::write_value,
+    drivers::{
+        at24cxx::At24cxx,
+        sensors::{
+            dht22::Dht22,
+            lm75::{self, Lm75},
+            status::{SensorStatus, StatusSensor},
+        },
+    },
+    hourly::{Range, SharedHourlyLog},
+    measurement::{Measurement, MeasurementChannel, SensorId},
+    schedule::PeriodSignal,
+    telemetry::{self, Telemetry, MAX_FRAME_SIZE},
+    units,
+};
+
+const PROMPT: &str = "> ";
+
+/// Size of one line of output
+const OUTPUT_SIZE: usize = 64;
+
+/// Addresses probed by scan, others are reserved by i2c specification
+const I2C_ADDRESSES: RangeInclusive<u8> = 0x08..=0x77;
+
+/// Sensors and services configured from shell
+pub struct Context<'a> {
+    pub lm75: Lm75<'a>,
+    pub dht22: Dht22<'a>,
+    /// Bus scanned for devices
+    pub i2c: I2cShared,
+    /// Receives period of measurements
+    pub period: &'a PeriodSignal,
+    /// Source of frames sent in binary stream mode
+    pub telemetry: Telemetry<'a>,
+    pub config: &'a SharedStore,
+    /// Settings loaded at start, updated from sensors when saved
+    pub settings: Settings,
+    pub log: &'a SharedLog,
+    pub hourly: &'a SharedHourlyLog<At24cxx>,
+}
+
+pub struct Runner<'a, IO> {
+    io: IO,
+    measurements: &'a MeasurementChannel,
+    context: Context<'a>,
+    latest: Option<Measurement>,
+    stream_mode: StreamMode,
+}
+
+impl<IO: Read + Write> Runner<'_, IO> {
+    pub async fn run(mut self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+        let mut frames = defmt::unwrap!(self.context.telemetry.subscriber());
+        let mut line = LineBuffer::new();
+        let mut received = [0; 16];
+
+        self.write(PROMPT).await;
+
+        loop {
+            let read = match select3(
+                self.io.read(&mut received),
+                subscriber.next_message_pure(),
+                frames.next_message_pure(),
+            )
+            .await
+            {
+                Either3::First(Ok(read)) => read,
+                Either3::First(Err(err)) => {
+                    defmt::warn!("shell: read failed: {}", defmt::Debug2Format(&err));
+                    continue;
+                }
+                Either3::Second(measurement) => {
+                    self.latest = Some(measurement);
+                    if self.stream_mode == StreamMode::Text {
+                        self.write_summary(&measurement).await;
+                    }
+                    continue;
+                }
+                // Frames are taken all the time, so stale frames are not sent when stream starts
+                Either3::Third(frame) => {
+                    if self.stream_mode == StreamMode::Binary {
+                        self.write_frame(&frame).await;
+                    }
+                    continue;
+                }
+            };
+
+            let echo = self.stream_mode != StreamMode::Binary;
+            for &byte in &received[..read] {
+                match line.feed(byte) {
+                    Edit::Insert(byte) if echo => self.write_bytes(&[byte]).await,
+                    Edit::Erase if echo => self.write("\x08 \x08").await,
+                    Edit::Submit => {
+                        if echo {
+                            self.write("\r\n").await;
+                        }
+                        self.execute_line(line.line()).await;
+                        line.clear();
+                        if self.stream_mode != StreamMode::Binary {
+                            self.write(PROMPT).await;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    async fn execute_line(&mut self, line: &str) {
+        match command::parse(line) {
+            Ok(command) => {
+                defmt::debug!("shell: {=str}", line);
+                self.execute(command).await;
+            }
+            Err(ParseError::Empty) => {}
+            Err(err) => {
+                let mut output: String<OUTPUT_SIZE> = String::new();
+                write!(output, "error: {}, type help", err).ok();
+                self.write_line(&output).await;
+            }
+        }
+    }
+
+    async fn execute(&mut self, command: Command) {
+        let unit = units::temperature_unit();
+
+        match command {
+            Command::Help => {
+                for usage in HELP {
+                    self.write_line(usage).await;
+                }
+            }
+            Command::Sensors => self.list_sensors().await,
+            Command::Stream(mode) => {
+                self.stream_mode = mode;
+                self.write_line("ok").await;
+            }
+            Command::Errors => {
+                for id in SensorId::ALL {
+                    let errors = match id {
+                        SensorId::Lm75 => self.context.lm75.get_error_count().await,
+                        SensorId::Dht22 => self.context.dht22.get_error_count().await,
+                    };
+                    let mut output: String<OUTPUT_SIZE> = String::new();
+                    uwrite!(output, "{}: {}", id.name(), errors).ok();
+                    self.write_line(&output).await;
+                }
+            }
+            Command::Interval { sensor, millis } => {
+                let interval = Duration::from_millis(millis.into());
+                let fastest = sensor.fastest_interval();
+                if interval < fastest {
+                    let mut output: String<OUTPUT_SIZE> = String::new();
+                    uwrite!(
+                        output,
+                        "error: shortest interval is {}ms",
+                        fastest.as_millis()
+                    )
+                    .ok();
+                    self.write_line(&output).await;
+                    return;
+                }
+
+                match sensor {
+                    SensorId::Lm75 => {
+                        let config = self.context.lm75.config().await;
+                        let config = lm75::Config {
+                            measurement_interval: interval,
+                            ..config
+                        };
+                        self.context.lm75.set_config(config).await;
+                    }
+                    SensorId::Dht22 => {
+                        self.context.dht22.set_measurement_interval(interval).await;
+                    }
+                }
+                self.write_line("ok").await;
+            }
+            Command::Period(period) => {
+                self.context.period.signal(period);
+                self.write_line("ok").await;
+            }
+            Command::Calibrate { sensor, offset } => {
+                calibration::set_offset(sensor, unit.difference_to_celsius(offset));
+                self.write_line("ok").await;
+            }
+            Command::Thresholds { os, hysteresis } => {
+                let config = self.context.lm75.config().await;
+                let config = config.with_thresholds(os, hysteresis, unit);
+                self.context.lm75.set_config(config).await;
+                self.write_line("ok").await;
+            }
+            Command::Unit(unit) => {
+                units::set_temperature_unit(unit);
+                self.write_line("ok").await;
+            }
+            Command::Scan => self.scan().await,
+            Command::Log(LogAction::Dump) => self.dump_log().await,
+            Command::Log(LogAction::Clear) => {
+                let result = self.context.log.lock().await.clear();
+                match result {
+                    Ok(()) => self.write_line("ok").await,
+                    Err(err) => self.write_log_error(err).await,
+                }
+            }
+            Command::Hourly => self.list_hourly().await,
+            Command::Save => {
+                let context = &mut self.context;
+                context
+                    .settings
+                    .capture(&context.lm75, &context.dht22)
+                    .await;
+                let result = config::save(&mut *context.config.lock().await, &context.settings);
+                self.write_result(result).await;
+            }
+            Command::FactoryReset => {
+                let result = config::factory_reset(&mut *self.context.config.lock().await);
+                self.write_result(result).await;
+                if result.is_ok() {
+                    self.write_line("rebooting").await;
+                    self.io.flush().await.ok();
+                    cortex_m::peripheral::SCB::sys_reset();
+                }
+            }
+            Command::Reboot => {
+                self.write_line("rebooting").await;
+                self.io.flush().await.ok();
+                cortex_m::peripheral::SCB::sys_reset();
+            }
+        }
+    }
+
+    async fn list_sensors(&mut self) {
+        let Some(measurement) = self.latest else {
+            self.write_line("no measurements yet").await;
+            return;
+        };
+        let unit = units::temperature_unit();
+
+        let mut value: String<OUTPUT_SIZE> = String::new();
+        for id in SensorId::ALL {
+            let status = match measurement.sensor_status(id) {
+                SensorStatus::Unknown => "unknown",
+                SensorStatus::Ok => "ok",
+                SensorStatus::Error => "error",
+            };
+            // Value of sensor not read successfully is stale, placeholder is shown like on display
+            let temperature = (measurement.sensor_status(id) == SensorStatus::Ok)
+                .then(|| unit.from_celsius(measurement.sensor_temperature(id)));
+            write_value(temperature, 2, &mut value);
+
+            let mut output: String<OUTPUT_SIZE> = String::new();
+            uwrite!(
+                output,
+                "{}: {}{} {}",
+                id.name(),
+                value.as_str(),
+                unit.symbol(),
+                status
+            )
+            .ok();
+            self.write_line(&output).await;
+        }
+
+        self.write_summary(&measurement).await;
+    }
+
+    /// Writes combined temperature and humidity of measurement in one line
+    async fn write_summary(&mut self, measurement: &Measurement) {
+        let unit = units::temperature_unit();
+        let mut output: String<OUTPUT_SIZE> = String::new();
+        let mut value: String<OUTPUT_SIZE> = String::new();
+
+        write_value(
+            Some(unit.from_celsius(measurement.temperature)),
+            2,
+            &mut value,
+        );
+        uwrite!(output, "temperature: {}{}", value.as_str(), unit.symbol()).ok();
+        let humidity = (measurement.sensor_status(SensorId::Dht22) == SensorStatus::Ok)
+            .then_some(measurement.humidity);
+        write_value(humidity, 1, &mut value);
+        uwrite!(output, ", humidity: {}%", value.as_str()).ok();
+        self.write_line(&output).await;
+    }
+
+    /// Writes log as CSV, temperatures in Celsius and time as unix seconds
+    async fn dump_log(&mut self) {
+        let mut header: String<OUTPUT_SIZE> = String::new();
+        header.push_str("time").ok();
+        for id in SensorId::ALL {
+            uwrite!(header, ",{},{} status", id.name(), id.name()).ok();
+        }
+        header.push_str(",humidity").ok();
+        self.write_line(&header).await;
+
+        let mut position = Position::default();
+        loop {
+            // Log is locked per record, so logging goes on during long dumps
+            let record = self.context.log.lock().await.read(&mut position);
+            let entry = match record {
+                Ok(Some(record)) => Entry::decode(&record),
+                Ok(None) => break,
+                Err(err) => {
+                    self.write_log_error(err).await;
+                    return;
+                }
+            };
+
+            let mut output: String<OUTPUT_SIZE> = String::new();
+            let mut value: String<OUTPUT_SIZE> = String::new();
+            if let Some(timestamp) = entry.timestamp {
+                uwrite!(output, "{}", timestamp).ok();
+            }
+            for id in SensorId::ALL {
+                let status = entry.statuses[id as usize];
+                // Field is left empty for sensors not read yet
+                value.clear();
+                if status != SensorStatus::Unknown {
+                    write_value(Some(entry.temperatures[id as usize]), 2, &mut value);
+                }
+                let status = match status {
+                    SensorStatus::Unknown => "unknown",
+                    SensorStatus::Ok => "ok",
+                    SensorStatus::Error => "error",
+                };
+                uwrite!(output, ",{},{}", value.as_str(), status).ok();
+            }
+            write_value(Some(entry.humidity), 1, &mut value);
+            uwrite!(output, ",{}", value.as_str()).ok();
+            self.write_line(&output).await;
+        }
+    }
+
+    /// Writes hourly summaries as CSV, oldest first
+    async fn list_hourly(&mut self) {
+        self.write_line("time,samples,min,max,mean,humidity min,humidity max,humidity mean")
+            .await;
+
+        let slots = self.context.hourly.lock().await.slots();
+        for age in 0..slots {
+            let summary = match self.context.hourly.lock().await.read(age) {
+                Ok(Some(summary)) => summary,
+                Ok(None) => continue,
+                Err(err) => {
+                    let mut output: String<OUTPUT_SIZE> = String::new();
+                    write!(output, "error: {}", err).ok();
+                    self.write_line(&output).await;
+                    return;
+                }
+            };
+
+            let mut output: String<OUTPUT_SIZE> = String::new();
+            let mut value: String<OUTPUT_SIZE> = String::new();
+            if let Some(start) = summary.start {
+                uwrite!(output, "{}", start).ok();
+            }
+            uwrite!(output, ",{}", summary.samples).ok();
+            for value_celsius in Range::values(summary.temperature) {
+                write_value(value_celsius, 2, &mut value);
+                uwrite!(output, ",{}", value.as_str()).ok();
+            }
+            for value_percent in Range::values(summary.humidity) {
+                write_value(value_percent, 1, &mut value);
+                uwrite!(output, ",{}", value.as_str()).ok();
+            }
+            self.write_line(&output).await;
+        }
+    }
+
+    async fn write_log_error(&mut self, err: datalog::Error) {
+        let mut output: String<OUTPUT_SIZE> = String::new();
+        write!(output, "error: {}", err).ok();
+        self.write_line(&output).await;
+    }
+
+    async fn write_frame(&mut self, frame: &telemetry::Frame) {
+        let mut buffer = [0; MAX_FRAME_SIZE];
+        match telemetry::encode(frame, &mut buffer) {
+            Ok(encoded) => self.write_bytes(encoded).await,
+            Err(err) => defmt::warn!("shell: frame not encoded: {}", defmt::Debug2Format(&err)),
+        }
+    }
+
+    async fn scan(&mut self) {
+        let mut found = 0;
+        for address in I2C_ADDRESSES {
+            // Device answering its address acknowledges the read
+            if self.context.i2c.read(address, &mut [0]).is_err() {
+                continue;
+            }
+            found += 1;
+
+            let mut output: String<OUTPUT_SIZE> = String::new();
+            uwrite!(output, "0x{:02x}", address).ok();
+            self.write_line(&output).await;
+        }
+
+        let mut output: String<OUTPUT_SIZE> = String::new();
+        uwrite!(output, "{} devices", found).ok();
+        self.write_line(&output).await;
+    }
+
+    async fn write_result(&mut self, result: Result<(), config::Error>) {
+        match result {
+            Ok(()) => self.write_line("ok").await,
+            Err(err) => {
+                let mut output: String<OUTPUT_SIZE> = String::new();
+                write!(output, "error: {}", err).ok();
+                self.write_line(&output).await;
+            }
+        }
+    }
+
+    async fn write_line(&mut self, text: &str) {
+        self.write(text).await;
+        self.write("\r\n").await;
+    }
+
+    async fn write(&mut self, text: &str) {
+        self.write_bytes(text.as_bytes()).await;
+    }
+
+    async fn write_bytes(&mut self, bytes: &[u8]) {
+        if let Err(err) = self.io.write_all(bytes).await {
+            defmt::warn!("shell: write failed: {}", defmt::Debug2Format(&err));
+        }
+    }
+}
+
+/// Creates shell served on byte stream, e.g. serial port
+pub fn new<'a, IO: Read + Write>(
+    io: IO,
+    measurements: &'a MeasurementChannel,
+    context: Context<'a>,
+) -> Runner<'a, IO> {
+    Runner {
+        io,
+        measurements,
+        context,
+        latest: None,
+        stream_mode: StreamMode::Off,
+    }
+}
@@ -0,0 +1,184 @@
+//!
+//! Telemetry sent to host in binary format
+//!
+//! Readings, changes of sensor status, alarms and statistics are queued as [Frame]s of the
+//! [telemetry] format, and written by owners of serial links, see [crate::shell]. Every link
+//! takes all frames and drops those it does not send.
+//!
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    pubsub::{self, PubSubChannel, Subscriber},
+};
+use embassy_time::{Duration, Instant, Timer};
+use heapless::Vec;
+
+use crate::{
+    display::statistics::{self, Statistics},
+    drivers::sensors::{lm75::OsEvent, status::SensorStatus},
+    measurement::{Measurement, MeasurementChannel, SensorId, SENSOR_COUNT},
+};
+
+pub use telemetry::{encode, Frame, Message, MAX_FRAME_SIZE};
+
+/// How often statistics are sent
+pub const STATISTICS_INTERVAL: Duration = Duration::from_secs(60);
+
+const QUEUE_SIZE: usize = 8;
+
+/// Serial links frames are sent on, UART and USB shell
+const LINKS: usize = 2;
+
+pub type TelemetryChannel = PubSubChannel<CriticalSectionRawMutex, Frame, QUEUE_SIZE, LINKS, 0>;
+
+pub type TelemetrySubscriber<'a> =
+    Subscriber<'a, CriticalSectionRawMutex, Frame, QUEUE_SIZE, LINKS, 0>;
+
+impl From<SensorId> for telemetry::Sensor {
+    fn from(id: SensorId) -> Self {
+        match id {
+            SensorId::Lm75 => telemetry::Sensor::Lm75,
+            SensorId::Dht22 => telemetry::Sensor::Dht22,
+        }
+    }
+}
+
+impl From<SensorStatus> for telemetry::SensorStatus {
+    fn from(status: SensorStatus) -> Self {
+        match status {
+            SensorStatus::Unknown => telemetry::SensorStatus::Unknown,
+            SensorStatus::Ok => telemetry::SensorStatus::Ok,
+            SensorStatus::Error => telemetry::SensorStatus::Error,
+        }
+    }
+}
+
+impl From<OsEvent> for telemetry::Alarm {
+    fn from(event: OsEvent) -> Self {
+        match event {
+            OsEvent::OverTemperature => telemetry::Alarm::OverTemperature,
+            OsEvent::Normal => telemetry::Alarm::Normal,
+        }
+    }
+}
+
+impl From<&Measurement> for telemetry::Reading {
+    fn from(measurement: &Measurement) -> Self {
+        let mut sensors = Vec::new();
+        for id in SensorId::ALL {
+            let reading = telemetry::SensorReading {
+                sensor: id.into(),
+                temperature: measurement.sensor_temperature(id),
+                status: measurement.sensor_status(id).into(),
+            };
+            // Format has room for more sensors than board has
+            sensors.push(reading).ok();
+        }
+
+        Self {
+            timestamp: measurement
+                .timestamp
+                .map(|timestamp| timestamp.0.timestamp()),
+            sensors,
+            temperature: measurement.temperature,
+            humidity: measurement.humidity,
+        }
+    }
+}
+
+impl From<&Statistics> for telemetry::Statistics {
+    fn from(statistics: &Statistics) -> Self {
+        let convert = |min_max: statistics::MinMax| telemetry::MinMax {
+            min: min_max.min,
+            max: min_max.max,
+        };
+        Self {
+            temperature: statistics.temperature.map(convert),
+            humidity: statistics.humidity.map(convert),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Telemetry<'a> {
+    channel: &'a TelemetryChannel,
+}
+
+impl<'a> Telemetry<'a> {
+    /// Queues message stamped with uptime, message is dropped when queue is full
+    pub fn send(&self, message: Message) {
+        let frame = Frame::new(Instant::now().as_millis(), message);
+        if self
+            .channel
+            .immediate_publisher()
+            .try_publish(frame)
+            .is_err()
+        {
+            defmt::trace!("telemetry: queue is full, message dropped");
+        }
+    }
+
+    pub fn send_alarm(&self, event: OsEvent) {
+        self.send(Message::Alarm(event.into()));
+    }
+
+    /// Subscribes link to frames, each link receives every frame
+    pub fn subscriber(&self) -> Result<TelemetrySubscriber<'a>, pubsub::Error> {
+        self.channel.subscriber()
+    }
+}
+
+/// Turns measurements into readings, status changes and periodic statistics
+pub struct Runner<'a> {
+    measurements: &'a MeasurementChannel,
+    telemetry: Telemetry<'a>,
+}
+
+impl Runner<'_> {
+    pub async fn run(self) -> ! {
+        let mut subscriber = defmt::unwrap!(self.measurements.subscriber());
+        let mut statistics = Statistics::new();
+        let mut statuses = [SensorStatus::Unknown; SENSOR_COUNT];
+        let mut statistics_time = Instant::now() + STATISTICS_INTERVAL;
+
+        loop {
+            let measurement =
+                match select(subscriber.next_message_pure(), Timer::at(statistics_time)).await {
+                    Either::First(measurement) => measurement,
+                    Either::Second(()) => {
+                        let message = Message::Statistics((&statistics).into());
+                        self.telemetry.send(message);
+                        statistics_time += STATISTICS_INTERVAL;
+                        continue;
+                    }
+                };
+
+            for id in SensorId::ALL {
+                let status = measurement.sensor_status(id);
+                if status != statuses[id as usize] {
+                    statuses[id as usize] = status;
+                    self.telemetry.send(Message::Status {
+                        sensor: id.into(),
+                        status: status.into(),
+                    });
+                }
+            }
+
+            statistics.update(&measurement);
+            self.telemetry.send(Message::Reading((&measurement).into()));
+        }
+    }
+}
+
+pub fn new<'a>(
+    measurements: &'a MeasurementChannel,
+    channel: &'a TelemetryChannel,
+) -> (Telemetry<'a>, Runner<'a>) {
+    let telemetry = Telemetry { channel };
+    let runner = Runner {
+        measurements,
+        telemetry,
+    };
+    (telemetry, runner)
+}
@@ -7,14 +7,13 @@ use crate::i2c::I2cProtected;
 
 use embassy_executor::SendSpawner;
 
-mod ds3231;
 mod lm75b;
 
 /// Maximum possible number of sources
-pub const MAX_SOURCES: usize = 2;
+pub const MAX_SOURCES: usize = 1;
 
 /// Channels for sources to use
-static SOURCES_CHANNELS: [Channel; MAX_SOURCES] = [Channel::new(), Channel::new()]; // TODO! user array_map or something
+static SOURCES_CHANNELS: [Channel; MAX_SOURCES] = [Channel::new()]; // TODO! user array_map or something
 
 /// Information needed to initialize the sources
 pub struct SourcesInitInfo<'spawn> {
@@ -32,10 +31,5 @@ pub fn init(info: &SourcesInitInfo) -> &'static [Channel] {
         lm75b::init(info, channel);
     }
 
-    {
-        let channel = &SOURCES_CHANNELS[1];
-        ds3231::init(info, channel);
-    }
-
     &SOURCES_CHANNELS
 }
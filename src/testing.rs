@@ -0,0 +1,482 @@
+//!
+//! Support of host tests
+//!
+//! Provides the defmt logger and the time driver, which are provided by the board otherwise.
+//! Time is simulated per test thread: it stands still until a timer is awaited and then jumps
+//! to its expiration, so tests of timeouts and backoff run instantly and deterministically.
+//!
+//! [ScriptedSerial] stands in for a device answering commands on a serial line, [RamFlash]
+//! for NOR flash that loses power in the middle of a write, [RamEeprom] for I2C EEPROM and
+//! [RamCard] for microSD card formatted as FAT32.
+//!
+
+use core::{
+    cell::{Cell, RefCell},
+    convert::Infallible,
+    task::Waker,
+};
+use std::{collections::VecDeque, rc::Rc, string::String, vec::Vec};
+
+use embassy_time::Instant;
+use embassy_time_driver::Driver;
+use embedded_io_async::{ErrorType, Read, ReadReady, Write};
+use embedded_storage::nor_flash::{self, NorFlash, NorFlashErrorKind, ReadNorFlash};
+
+use crate::drivers::{
+    at24cxx::{self, Eeprom},
+    sdcard::{self, Block, BlockDevice, BLOCK_SIZE},
+};
+
+/// Logs are discarded, tests check results instead
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {}
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {}
+
+    unsafe fn write(_bytes: &[u8]) {}
+}
+
+defmt::timestamp!("");
+
+#[defmt::panic_handler]
+fn panic() -> ! {
+    panic!("defmt panic")
+}
+
+std::thread_local! {
+    static NOW: Cell<u64> = const { Cell::new(0) };
+}
+
+struct SimulatedTime;
+
+impl Driver for SimulatedTime {
+    fn now(&self) -> u64 {
+        NOW.with(Cell::get)
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &Waker) {
+        NOW.with(|now| now.set(now.get().max(at)));
+        waker.wake_by_ref();
+    }
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: SimulatedTime = SimulatedTime);
+
+/// Serial line answering each line written to it with next scripted reply
+///
+/// Empty reply is no answer at all, reading then waits forever. Clones share the line, so
+/// test can keep one to check commands and inject data.
+#[derive(Clone, Default)]
+pub struct ScriptedSerial {
+    inner: Rc<RefCell<Script>>,
+}
+
+#[derive(Default)]
+struct Script {
+    replies: VecDeque<&'static [u8]>,
+    received: VecDeque<u8>,
+    line: Vec<u8>,
+    commands: Vec<(Instant, String)>,
+}
+
+impl ScriptedSerial {
+    pub fn new(replies: &[&'static [u8]]) -> Self {
+        let serial = Self::default();
+        serial.inner.borrow_mut().replies.extend(replies);
+        serial
+    }
+
+    /// Sends data unrelated to any command, e.g. late reply
+    pub fn inject(&self, bytes: &[u8]) {
+        self.inner.borrow_mut().received.extend(bytes);
+    }
+
+    /// Lines written so far, without line endings
+    pub fn commands(&self) -> Vec<String> {
+        let script = self.inner.borrow();
+        script
+            .commands
+            .iter()
+            .map(|(_, command)| command.clone())
+            .collect()
+    }
+
+    /// Times lines were written at
+    pub fn command_times(&self) -> Vec<Instant> {
+        let script = self.inner.borrow();
+        script.commands.iter().map(|&(time, _)| time).collect()
+    }
+}
+
+impl ErrorType for ScriptedSerial {
+    type Error = Infallible;
+}
+
+impl Read for ScriptedSerial {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        if self.inner.borrow().received.is_empty() {
+            return core::future::pending().await;
+        }
+        let mut script = self.inner.borrow_mut();
+        let count = buf.len().min(script.received.len());
+        for (byte, received) in buf.iter_mut().zip(script.received.drain(..count)) {
+            *byte = received;
+        }
+        Ok(count)
+    }
+}
+
+impl ReadReady for ScriptedSerial {
+    fn read_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.inner.borrow().received.is_empty())
+    }
+}
+
+impl Write for ScriptedSerial {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        let mut script = self.inner.borrow_mut();
+        for &byte in buf {
+            if byte != b'\n' {
+                script.line.push(byte);
+                continue;
+            }
+            let line = core::mem::take(&mut script.line);
+            let command = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(&line));
+            script.commands.push((Instant::now(), command.into_owned()));
+            if let Some(reply) = script.replies.pop_front() {
+                script.received.extend(reply);
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+/// NOR flash in memory, erased to `0xff` and written by clearing bits only
+pub struct RamFlash<const SIZE: usize, const ERASE_SIZE: usize> {
+    pub bytes: [u8; SIZE],
+    /// Bytes written before power is lost, writing fails from then on
+    pub power_budget: Option<usize>,
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize> RamFlash<SIZE, ERASE_SIZE> {
+    pub fn new() -> Self {
+        Self {
+            bytes: [0xff; SIZE],
+            power_budget: None,
+        }
+    }
+
+    fn check(&self, offset: u32, length: usize, align: usize) -> Result<usize, NorFlashErrorKind> {
+        let offset = offset as usize;
+        if !offset.is_multiple_of(align) || !length.is_multiple_of(align) {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        if offset + length > SIZE {
+            return Err(NorFlashErrorKind::OutOfBounds);
+        }
+        Ok(offset)
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize> Default for RamFlash<SIZE, ERASE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize> nor_flash::ErrorType
+    for RamFlash<SIZE, ERASE_SIZE>
+{
+    type Error = NorFlashErrorKind;
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize> ReadNorFlash for RamFlash<SIZE, ERASE_SIZE> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), NorFlashErrorKind> {
+        let offset = self.check(offset, bytes.len(), Self::READ_SIZE)?;
+        bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+impl<const SIZE: usize, const ERASE_SIZE: usize> NorFlash for RamFlash<SIZE, ERASE_SIZE> {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), NorFlashErrorKind> {
+        let length = to.checked_sub(from).ok_or(NorFlashErrorKind::OutOfBounds)? as usize;
+        let from = self.check(from, length, ERASE_SIZE)?;
+        self.bytes[from..from + length].fill(0xff);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), NorFlashErrorKind> {
+        let offset = self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+        let budget = self.power_budget.unwrap_or(usize::MAX);
+        for (target, byte) in self.bytes[offset..].iter_mut().zip(bytes).take(budget) {
+            *target &= byte;
+        }
+        if let Some(budget) = &mut self.power_budget {
+            if *budget < bytes.len() {
+                *budget = 0;
+                return Err(NorFlashErrorKind::Other);
+            }
+            *budget -= bytes.len();
+        }
+        Ok(())
+    }
+}
+
+/// EEPROM in memory, blank chips read as `0xff`
+pub struct RamEeprom<const SIZE: usize> {
+    pub bytes: [u8; SIZE],
+}
+
+impl<const SIZE: usize> RamEeprom<SIZE> {
+    pub fn new() -> Self {
+        Self {
+            bytes: [0xff; SIZE],
+        }
+    }
+
+    fn range(&self, offset: u16, length: usize) -> Result<core::ops::Range<usize>, at24cxx::Error> {
+        let offset = usize::from(offset);
+        if offset + length > SIZE {
+            return Err(at24cxx::Error::OutOfRange);
+        }
+        Ok(offset..offset + length)
+    }
+}
+
+impl<const SIZE: usize> Default for RamEeprom<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> Eeprom for RamEeprom<SIZE> {
+    fn capacity(&self) -> u16 {
+        SIZE as u16
+    }
+
+    fn read(&mut self, offset: u16, buffer: &mut [u8]) -> Result<(), at24cxx::Error> {
+        let range = self.range(offset, buffer.len())?;
+        buffer.copy_from_slice(&self.bytes[range]);
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u16, data: &[u8]) -> Result<(), at24cxx::Error> {
+        let range = self.range(offset, data.len())?;
+        self.bytes[range].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Card in memory, freshly formatted as FAT32 with one empty root directory cluster
+pub struct RamCard {
+    pub blocks: Vec<Block>,
+    /// Card does not answer while removed
+    pub inserted: bool,
+}
+
+impl RamCard {
+    const RESERVED: u32 = 32;
+    const FAT_COUNT: u32 = 2;
+    const ROOT_CLUSTER: u32 = 2;
+    /// Start of partition on partitioned card
+    const PARTITION_START: u32 = 63;
+
+    /// Card of 2048 blocks with a cluster of one block, so files soon span clusters
+    pub fn formatted(partitioned: bool) -> Self {
+        Self::format(2048, 1, partitioned)
+    }
+
+    /// Card of blocks, whose volume is in MBR partition or takes the whole card
+    pub fn format(count: u32, blocks_per_cluster: u32, partitioned: bool) -> Self {
+        let mut blocks = vec![[0; BLOCK_SIZE]; count as usize];
+        let start = if partitioned {
+            let mbr = &mut blocks[0];
+            let partition = &mut mbr[0x1be..0x1ce];
+            partition[4] = 0x0c;
+            partition[8..12].copy_from_slice(&Self::PARTITION_START.to_le_bytes());
+            partition[12..16].copy_from_slice(&(count - Self::PARTITION_START).to_le_bytes());
+            mbr[510..].copy_from_slice(&[0x55, 0xaa]);
+            Self::PARTITION_START
+        } else {
+            0
+        };
+
+        let total = count - start;
+        let clusters = (total - Self::RESERVED) / blocks_per_cluster;
+        let fat_size = ((clusters + 2) * 4).div_ceil(BLOCK_SIZE as u32);
+
+        let boot = &mut blocks[start as usize];
+        boot[..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+        boot[3..11].copy_from_slice(b"MSWIN4.1");
+        boot[0x0b..0x0d].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+        boot[0x0d] = blocks_per_cluster as u8;
+        boot[0x0e..0x10].copy_from_slice(&(Self::RESERVED as u16).to_le_bytes());
+        boot[0x10] = Self::FAT_COUNT as u8;
+        boot[0x15] = 0xf8;
+        boot[0x20..0x24].copy_from_slice(&total.to_le_bytes());
+        boot[0x24..0x28].copy_from_slice(&fat_size.to_le_bytes());
+        boot[0x2c..0x30].copy_from_slice(&Self::ROOT_CLUSTER.to_le_bytes());
+        boot[0x30..0x32].copy_from_slice(&1_u16.to_le_bytes());
+        boot[0x52..0x5a].copy_from_slice(b"FAT32   ");
+        boot[510..].copy_from_slice(&[0x55, 0xaa]);
+
+        let info = &mut blocks[start as usize + 1];
+        info[..4].copy_from_slice(&0x4161_5252_u32.to_le_bytes());
+        info[484..488].copy_from_slice(&0x6141_7272_u32.to_le_bytes());
+        info[488..492].copy_from_slice(&(clusters - 1).to_le_bytes());
+        info[492..496].copy_from_slice(&3_u32.to_le_bytes());
+        info[508..].copy_from_slice(&0xaa55_0000_u32.to_le_bytes());
+
+        for copy in 0..Self::FAT_COUNT {
+            let fat = &mut blocks[(start + Self::RESERVED + copy * fat_size) as usize];
+            for (cluster, entry) in [0x0fff_fff8_u32, 0x0fff_ffff, 0x0fff_ffff]
+                .iter()
+                .enumerate()
+            {
+                fat[cluster * 4..cluster * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+            }
+        }
+
+        Self {
+            blocks,
+            inserted: true,
+        }
+    }
+
+    /// Contents of file in root directory, read by following its cluster chain
+    pub fn read_file(&self, name: &str) -> Option<Vec<u8>> {
+        let (stem, extension) = name.split_once('.').unwrap_or((name, ""));
+        let mut short = [b' '; 11];
+        short[..stem.len()].copy_from_slice(stem.as_bytes());
+        short[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+
+        let directory: Vec<u8> = self
+            .chain(Self::ROOT_CLUSTER)
+            .into_iter()
+            .flat_map(|cluster| self.cluster(cluster))
+            .collect();
+        let entry = directory
+            .chunks(32)
+            .take_while(|entry| entry[0] != 0)
+            .find(|entry| entry[..11] == short)?;
+        let first = u32::from_le_bytes([entry[26], entry[27], entry[20], entry[21]]);
+        let size = u32::from_le_bytes([entry[28], entry[29], entry[30], entry[31]]) as usize;
+        if size == 0 {
+            return Some(Vec::new());
+        }
+
+        let mut data: Vec<u8> = self
+            .chain(first)
+            .into_iter()
+            .flat_map(|cluster| self.cluster(cluster))
+            .collect();
+        assert!(data.len() >= size, "chain shorter than file");
+        data.truncate(size);
+        Some(data)
+    }
+
+    /// Entry of cluster in FAT, copies of FAT have to agree
+    pub fn fat_entry(&self, cluster: u32) -> u32 {
+        let (start, fat_size, _) = self.layout();
+        let entries: Vec<u32> = (0..Self::FAT_COUNT)
+            .map(|copy| {
+                let offset = cluster as usize * 4;
+                let block = &self.blocks
+                    [(start + Self::RESERVED + copy * fat_size) as usize + offset / BLOCK_SIZE];
+                let offset = offset % BLOCK_SIZE;
+                u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap()) & 0x0fff_ffff
+            })
+            .collect();
+        assert!(
+            entries.iter().all(|entry| *entry == entries[0]),
+            "copies of FAT differ"
+        );
+        entries[0]
+    }
+
+    /// Free count kept by FSInfo block
+    pub fn free_count(&self) -> u32 {
+        let (start, _, _) = self.layout();
+        let info = &self.blocks[start as usize + 1];
+        u32::from_le_bytes(info[488..492].try_into().unwrap())
+    }
+
+    fn chain(&self, first: u32) -> Vec<u32> {
+        let mut chain = vec![first];
+        loop {
+            let next = self.fat_entry(*chain.last().unwrap());
+            if next >= 0x0fff_fff8 {
+                return chain;
+            }
+            assert!(next >= 2 && !chain.contains(&next), "broken chain");
+            chain.push(next);
+        }
+    }
+
+    fn cluster(&self, cluster: u32) -> Vec<u8> {
+        let (start, fat_size, blocks_per_cluster) = self.layout();
+        let first = start
+            + Self::RESERVED
+            + Self::FAT_COUNT * fat_size
+            + (cluster - 2) * blocks_per_cluster;
+        (first..first + blocks_per_cluster)
+            .flat_map(|index| self.blocks[index as usize])
+            .collect()
+    }
+
+    /// Start of volume, size of FAT and blocks per cluster as formatted
+    fn layout(&self) -> (u32, u32, u32) {
+        let start = if self.blocks[0][0x52..0x5a] == *b"FAT32   " {
+            0
+        } else {
+            Self::PARTITION_START
+        };
+        let boot = &self.blocks[start as usize];
+        let fat_size = u32::from_le_bytes(boot[0x24..0x28].try_into().unwrap());
+        (start, fat_size, u32::from(boot[0x0d]))
+    }
+
+    fn block(&self, index: u32) -> Result<usize, sdcard::Error> {
+        if !self.inserted {
+            return Err(sdcard::Error::NoCard);
+        }
+        let index = index as usize;
+        if index >= self.blocks.len() {
+            return Err(sdcard::Error::Command(0x40));
+        }
+        Ok(index)
+    }
+}
+
+impl BlockDevice for RamCard {
+    async fn init(&mut self) -> Result<(), sdcard::Error> {
+        self.block(0).map(|_| ())
+    }
+
+    async fn read(&mut self, index: u32, block: &mut Block) -> Result<(), sdcard::Error> {
+        let index = self.block(index)?;
+        block.copy_from_slice(&self.blocks[index]);
+        Ok(())
+    }
+
+    async fn write(&mut self, index: u32, block: &Block) -> Result<(), sdcard::Error> {
+        let index = self.block(index)?;
+        self.blocks[index].copy_from_slice(block);
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//!
+//! Temperature unit preferred by user
+//!
+//! Temperatures are kept in Celsius everywhere and converted to preferred unit only when they
+//! are shown, logged or entered by user, so the unit can be switched at any time.
+//!
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+static TEMPERATURE_UNIT: AtomicU8 = AtomicU8::new(TemperatureUnit::Celsius.code());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    pub const ALL: [TemperatureUnit; 3] = [
+        TemperatureUnit::Celsius,
+        TemperatureUnit::Fahrenheit,
+        TemperatureUnit::Kelvin,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+
+    /// Value stored in configuration, never changes for existing units
+    pub const fn code(&self) -> u8 {
+        match self {
+            TemperatureUnit::Celsius => 0,
+            TemperatureUnit::Fahrenheit => 1,
+            TemperatureUnit::Kelvin => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|unit| unit.code() == code)
+    }
+
+    /// Unit shown after this one when user cycles through units
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|unit| unit == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn from_celsius(&self, celsius: f32) -> f32 {
+        self.converter()(celsius)
+    }
+
+    pub fn to_celsius(&self, value: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => value,
+            TemperatureUnit::Fahrenheit => (value - 32.0) / 1.8,
+            TemperatureUnit::Kelvin => value - 273.15,
+        }
+    }
+
+    /// Converts difference of temperatures, e.g. calibration offset, to Celsius
+    pub fn difference_to_celsius(&self, value: f32) -> f32 {
+        match self {
+            TemperatureUnit::Fahrenheit => value / 1.8,
+            _ => value,
+        }
+    }
+
+    /// Conversion from Celsius, for drawables taking plain functions
+    pub fn converter(&self) -> fn(f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => |celsius| celsius,
+            TemperatureUnit::Fahrenheit => |celsius| celsius * 1.8 + 32.0,
+            TemperatureUnit::Kelvin => |celsius| celsius + 273.15,
+        }
+    }
+}
+
+/// Unit temperatures are shown and entered in
+pub fn temperature_unit() -> TemperatureUnit {
+    let code = TEMPERATURE_UNIT.load(Ordering::Relaxed);
+    TemperatureUnit::from_code(code).unwrap_or(TemperatureUnit::Celsius)
+}
+
+pub fn set_temperature_unit(unit: TemperatureUnit) {
+    TEMPERATURE_UNIT.store(unit.code(), Ordering::Relaxed);
+    defmt::info!("units: temperature in {=str}", unit.symbol());
+}
+
+/// Temperature in Celsius, logged in preferred unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Temperature(pub f32);
+
+impl defmt::Format for Temperature {
+    fn format(&self, fmt: defmt::Formatter) {
+        let unit = temperature_unit();
+        defmt::write!(
+            fmt,
+            "{=f32}{=str}",
+            unit.from_celsius(self.0),
+            unit.symbol()
+        );
+    }
+}
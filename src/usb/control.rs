@@ -0,0 +1,310 @@
+//!
+//! Enumeration and CDC-ACM requests on the control pipe
+//!
+
+use embassy_futures::select::{select, Either};
+use embassy_usb_driver::{Bus, ControlPipe, Driver, EndpointAddress, Event};
+
+use super::{descriptor, Endpoints, LineCoding, State};
+
+const STANDARD: u8 = 0x00;
+const CLASS: u8 = 0x20;
+const RECIPIENT_ENDPOINT: u8 = 0x02;
+
+const GET_STATUS: u8 = 0x00;
+const CLEAR_FEATURE: u8 = 0x01;
+const SET_FEATURE: u8 = 0x03;
+const SET_ADDRESS: u8 = 0x05;
+const GET_DESCRIPTOR: u8 = 0x06;
+const GET_CONFIGURATION: u8 = 0x08;
+const SET_CONFIGURATION: u8 = 0x09;
+const GET_INTERFACE: u8 = 0x0a;
+const SET_INTERFACE: u8 = 0x0b;
+
+const SET_LINE_CODING: u8 = 0x20;
+const GET_LINE_CODING: u8 = 0x21;
+const SET_CONTROL_LINE_STATE: u8 = 0x22;
+const SEND_BREAK: u8 = 0x23;
+
+const ENDPOINT_HALT: u16 = 0;
+const DTR: u16 = 0x01;
+
+/// Longest reply, configuration descriptor
+const REPLY_SIZE: usize = 128;
+
+/// Setup packet starting control transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Setup {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+}
+
+impl Setup {
+    fn parse(packet: [u8; 8]) -> Self {
+        Self {
+            request_type: packet[0],
+            request: packet[1],
+            value: u16::from_le_bytes([packet[2], packet[3]]),
+            index: u16::from_le_bytes([packet[4], packet[5]]),
+            length: u16::from_le_bytes([packet[6], packet[7]]),
+        }
+    }
+
+    /// Data stage goes from device to host
+    fn is_in(&self) -> bool {
+        self.request_type & 0x80 != 0
+    }
+
+    /// Standard, class or vendor request
+    fn kind(&self) -> u8 {
+        self.request_type & 0x60
+    }
+
+    fn recipient(&self) -> u8 {
+        self.request_type & 0x1f
+    }
+}
+
+/// Splits reply into packets of data stage, each with flags first and last
+///
+/// Reply is cut to length requested. Host knows a shorter reply ended only by a short packet,
+/// so zero-length packet follows a reply of whole packets.
+fn packets(
+    reply: &[u8],
+    max_packet_size: usize,
+    requested: u16,
+) -> impl Iterator<Item = (&[u8], bool, bool)> {
+    let reply = &reply[..reply.len().min(usize::from(requested))];
+    let zero_length = reply.len().is_multiple_of(max_packet_size)
+        && (reply.is_empty() || reply.len() < usize::from(requested));
+    let count = reply.len().div_ceil(max_packet_size) + usize::from(zero_length);
+    (0..count).map(move |packet| {
+        let start = (packet * max_packet_size).min(reply.len());
+        let end = (start + max_packet_size).min(reply.len());
+        (&reply[start..end], packet == 0, packet + 1 == count)
+    })
+}
+
+/// Answers host on control pipe and enables function once host configures it
+pub struct Runner<'d, D: Driver<'d>> {
+    bus: D::Bus,
+    control: D::ControlPipe,
+    endpoints: Endpoints,
+    state: &'d State,
+    configured: bool,
+}
+
+impl<'d, D: Driver<'d>> Runner<'d, D> {
+    pub(super) fn new(
+        bus: D::Bus,
+        control: D::ControlPipe,
+        endpoints: Endpoints,
+        state: &'d State,
+    ) -> Self {
+        Self {
+            bus,
+            control,
+            endpoints,
+            state,
+            configured: false,
+        }
+    }
+
+    pub async fn run(mut self) -> ! {
+        loop {
+            match select(self.bus.poll(), self.control.setup()).await {
+                Either::First(event) => self.handle_event(event).await,
+                Either::Second(packet) => {
+                    let setup = Setup::parse(packet);
+                    if setup.is_in() {
+                        self.handle_in(&setup).await;
+                    } else {
+                        self.handle_out(&setup).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::PowerDetected => {
+                defmt::info!("usb: powered");
+                self.bus.enable().await;
+            }
+            Event::PowerRemoved => {
+                self.configure(false);
+                self.bus.disable().await;
+            }
+            // Host configures device again after reset
+            Event::Reset => self.configure(false),
+            Event::Suspend | Event::Resume => {}
+        }
+    }
+
+    /// Enables or disables endpoints of function
+    fn configure(&mut self, configured: bool) {
+        if configured != self.configured {
+            defmt::info!("usb: configured {=bool}", configured);
+        }
+        for address in self.endpoints.all() {
+            self.bus.endpoint_set_enabled(address, configured);
+        }
+        self.configured = configured;
+        if !configured {
+            self.state.set_dtr(false);
+        }
+    }
+
+    async fn handle_in(&mut self, setup: &Setup) {
+        let mut reply = [0; REPLY_SIZE];
+        let Some(length) = self.reply(setup, &mut reply) else {
+            self.control.reject().await;
+            return;
+        };
+
+        let max_packet_size = self.control.max_packet_size();
+        for (packet, first, last) in packets(&reply[..length], max_packet_size, setup.length) {
+            // New setup packet from host abandons reply
+            if self.control.data_in(packet, first, last).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Writes reply to request, `None` rejects request
+    fn reply(&mut self, setup: &Setup, reply: &mut [u8; REPLY_SIZE]) -> Option<usize> {
+        match (setup.kind(), setup.request) {
+            (STANDARD, GET_DESCRIPTOR) => {
+                let [index, kind] = setup.value.to_le_bytes();
+                descriptor::write(kind, index, &self.endpoints, reply)
+            }
+            (STANDARD, GET_CONFIGURATION) => {
+                reply[0] = if self.configured {
+                    descriptor::CONFIGURATION_VALUE
+                } else {
+                    0
+                };
+                Some(1)
+            }
+            (STANDARD, GET_STATUS) => {
+                // Only endpoints have status, halt in bit 0
+                if setup.recipient() == RECIPIENT_ENDPOINT {
+                    let address = EndpointAddress::from(setup.index as u8);
+                    reply[0] = u8::from(self.bus.endpoint_is_stalled(address));
+                }
+                Some(2)
+            }
+            (STANDARD, GET_INTERFACE) if self.configured => Some(1),
+            (CLASS, GET_LINE_CODING) => {
+                let data = self.state.line_coding().encode();
+                reply[..data.len()].copy_from_slice(&data);
+                Some(data.len())
+            }
+            _ => None,
+        }
+    }
+
+    async fn handle_out(&mut self, setup: &Setup) {
+        match (setup.kind(), setup.request) {
+            (STANDARD, SET_ADDRESS) => {
+                self.control.accept_set_address(setup.value as u8).await;
+                return;
+            }
+            (STANDARD, SET_CONFIGURATION)
+                if setup.value <= u16::from(descriptor::CONFIGURATION_VALUE) =>
+            {
+                self.configure(setup.value != 0);
+            }
+            (STANDARD, SET_INTERFACE) if self.configured && setup.value == 0 => {}
+            (STANDARD, CLEAR_FEATURE | SET_FEATURE)
+                if setup.recipient() == RECIPIENT_ENDPOINT && setup.value == ENDPOINT_HALT =>
+            {
+                let address = EndpointAddress::from(setup.index as u8);
+                self.bus
+                    .endpoint_set_stalled(address, setup.request == SET_FEATURE);
+            }
+            (CLASS, SET_LINE_CODING) if usize::from(setup.length) == LineCoding::SIZE => {
+                let mut data = [0; LineCoding::SIZE];
+                match self.control.data_out(&mut data, true, true).await {
+                    Ok(LineCoding::SIZE) => self.state.set_line_coding(LineCoding::decode(&data)),
+                    // New setup packet from host abandons request
+                    Err(_) => return,
+                    Ok(_) => {
+                        self.control.reject().await;
+                        return;
+                    }
+                }
+            }
+            (CLASS, SET_CONTROL_LINE_STATE) => {
+                let dtr = setup.value & DTR != 0;
+                defmt::debug!("usb: DTR {=bool}", dtr);
+                self.state.set_dtr(dtr);
+            }
+            (CLASS, SEND_BREAK) => {}
+            _ => {
+                self.control.reject().await;
+                return;
+            }
+        }
+        self.control.accept().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lengths(reply_length: usize, requested: u16) -> Vec<(usize, bool, bool)> {
+        let reply = [0; REPLY_SIZE];
+        packets(&reply[..reply_length], 64, requested)
+            .map(|(packet, first, last)| (packet.len(), first, last))
+            .collect()
+    }
+
+    #[test]
+    fn parses_setup_packet() {
+        let setup = Setup::parse([0x80, GET_DESCRIPTOR, 0x00, 0x02, 0x00, 0x00, 0xff, 0x00]);
+        assert_eq!(
+            setup,
+            Setup {
+                request_type: 0x80,
+                request: GET_DESCRIPTOR,
+                value: 0x0200,
+                index: 0,
+                length: 255,
+            }
+        );
+        assert!(setup.is_in());
+        assert_eq!(setup.kind(), STANDARD);
+
+        let setup = Setup::parse([0x21, SET_LINE_CODING, 0, 0, 0, 0, 7, 0]);
+        assert!(!setup.is_in());
+        assert_eq!((setup.kind(), setup.recipient()), (CLASS, 0x01));
+    }
+
+    #[test]
+    fn splits_reply_into_packets() {
+        assert_eq!(lengths(18, 64), [(18, true, true)]);
+        assert_eq!(lengths(67, 255), [(64, true, false), (3, false, true)]);
+        assert_eq!(lengths(67, 9), [(9, true, true)], "cut to requested");
+    }
+
+    #[test]
+    fn ends_reply_of_whole_packets_with_zero_length_packet() {
+        assert_eq!(
+            lengths(64, 255),
+            [(64, true, false), (0, false, true)],
+            "host waits for more"
+        );
+        assert_eq!(
+            lengths(64, 64),
+            [(64, true, true)],
+            "host got all requested"
+        );
+        assert_eq!(lengths(0, 2), [(0, true, true)], "empty reply");
+    }
+}
@@ -0,0 +1,305 @@
+//!
+//! Descriptors of device with one CDC-ACM function
+//!
+//! Device class is CDC, so hosts bind their serial driver without an interface association.
+//!
+
+use super::{Endpoints, MAX_PACKET_SIZE, NOTIFICATION_INTERVAL_MS, NOTIFICATION_PACKET_SIZE};
+
+/// pid.codes test vendor and product, unit is not sold
+const VENDOR_ID: u16 = 0x1209;
+const PRODUCT_ID: u16 = 0x0001;
+
+const MANUFACTURER: &str = "embassy-stm32-temp";
+const PRODUCT: &str = "Temperature sensor";
+
+/// English (United States), the only language strings are given in
+const LANGUAGE_ID: u16 = 0x0409;
+
+const DEVICE: u8 = 1;
+const CONFIGURATION: u8 = 2;
+const STRING: u8 = 3;
+const INTERFACE: u8 = 4;
+const ENDPOINT: u8 = 5;
+const CS_INTERFACE: u8 = 0x24;
+
+const CLASS_CDC: u8 = 0x02;
+const CLASS_CDC_DATA: u8 = 0x0a;
+const SUBCLASS_ACM: u8 = 0x02;
+
+const FUNCTIONAL_HEADER: u8 = 0x00;
+const FUNCTIONAL_CALL_MANAGEMENT: u8 = 0x01;
+const FUNCTIONAL_ACM: u8 = 0x02;
+const FUNCTIONAL_UNION: u8 = 0x06;
+
+/// SET_LINE_CODING, GET_LINE_CODING, SET_CONTROL_LINE_STATE and SERIAL_STATE
+const ACM_CAPABILITIES: u8 = 0x02;
+
+const TRANSFER_BULK: u8 = 0x02;
+const TRANSFER_INTERRUPT: u8 = 0x03;
+
+pub const CONFIGURATION_VALUE: u8 = 1;
+const COMMUNICATION_INTERFACE: u8 = 0;
+const DATA_INTERFACE: u8 = 1;
+
+/// Most the device draws from bus, in units of 2mA
+const MAX_POWER: u8 = 100 / 2;
+
+const DEVICE_SIZE: usize = 18;
+const CONFIGURATION_SIZE: usize = 67;
+
+const MANUFACTURER_INDEX: u8 = 1;
+const PRODUCT_INDEX: u8 = 2;
+
+fn device() -> [u8; DEVICE_SIZE] {
+    let [vendor_low, vendor_high] = VENDOR_ID.to_le_bytes();
+    let [product_low, product_high] = PRODUCT_ID.to_le_bytes();
+    [
+        DEVICE_SIZE as u8,
+        DEVICE,
+        // USB 2.0
+        0x00,
+        0x02,
+        CLASS_CDC,
+        0x00,
+        0x00,
+        MAX_PACKET_SIZE as u8,
+        vendor_low,
+        vendor_high,
+        product_low,
+        product_high,
+        // Release 1.0
+        0x00,
+        0x01,
+        MANUFACTURER_INDEX,
+        PRODUCT_INDEX,
+        // No serial number
+        0x00,
+        1,
+    ]
+}
+
+fn configuration(endpoints: &Endpoints) -> [u8; CONFIGURATION_SIZE] {
+    let [total_low, total_high] = (CONFIGURATION_SIZE as u16).to_le_bytes();
+    let [notification_low, notification_high] = NOTIFICATION_PACKET_SIZE.to_le_bytes();
+    let [packet_low, packet_high] = MAX_PACKET_SIZE.to_le_bytes();
+    [
+        9,
+        CONFIGURATION,
+        total_low,
+        total_high,
+        2,
+        CONFIGURATION_VALUE,
+        0,
+        // Bus powered
+        0x80,
+        MAX_POWER,
+        // Communication interface
+        9,
+        INTERFACE,
+        COMMUNICATION_INTERFACE,
+        0,
+        1,
+        CLASS_CDC,
+        SUBCLASS_ACM,
+        0,
+        0,
+        // Header, CDC 1.10
+        5,
+        CS_INTERFACE,
+        FUNCTIONAL_HEADER,
+        0x10,
+        0x01,
+        // Call management is not done by device
+        5,
+        CS_INTERFACE,
+        FUNCTIONAL_CALL_MANAGEMENT,
+        0x00,
+        DATA_INTERFACE,
+        4,
+        CS_INTERFACE,
+        FUNCTIONAL_ACM,
+        ACM_CAPABILITIES,
+        5,
+        CS_INTERFACE,
+        FUNCTIONAL_UNION,
+        COMMUNICATION_INTERFACE,
+        DATA_INTERFACE,
+        7,
+        ENDPOINT,
+        endpoints.notification.into(),
+        TRANSFER_INTERRUPT,
+        notification_low,
+        notification_high,
+        NOTIFICATION_INTERVAL_MS,
+        // Data interface
+        9,
+        INTERFACE,
+        DATA_INTERFACE,
+        0,
+        2,
+        CLASS_CDC_DATA,
+        0,
+        0,
+        0,
+        7,
+        ENDPOINT,
+        endpoints.read.into(),
+        TRANSFER_BULK,
+        packet_low,
+        packet_high,
+        0,
+        7,
+        ENDPOINT,
+        endpoints.write.into(),
+        TRANSFER_BULK,
+        packet_low,
+        packet_high,
+        0,
+    ]
+}
+
+/// Writes string descriptor, UTF-16 of text
+fn string(text: &str, buffer: &mut [u8]) -> Option<usize> {
+    let mut length = 2;
+    for unit in text.encode_utf16() {
+        buffer
+            .get_mut(length..length + 2)?
+            .copy_from_slice(&unit.to_le_bytes());
+        length += 2;
+    }
+    buffer[0] = u8::try_from(length).ok()?;
+    buffer[1] = STRING;
+    Some(length)
+}
+
+/// Writes descriptor of kind and index requested by host, returns its length
+///
+/// Gives `None` for descriptors the device does not have.
+pub(super) fn write(
+    kind: u8,
+    index: u8,
+    endpoints: &Endpoints,
+    buffer: &mut [u8],
+) -> Option<usize> {
+    let mut copy = |descriptor: &[u8]| {
+        buffer
+            .get_mut(..descriptor.len())?
+            .copy_from_slice(descriptor);
+        Some(descriptor.len())
+    };
+    match (kind, index) {
+        (DEVICE, 0) => copy(&device()),
+        (CONFIGURATION, 0) => copy(&configuration(endpoints)),
+        (STRING, 0) => {
+            let [low, high] = LANGUAGE_ID.to_le_bytes();
+            copy(&[4, STRING, low, high])
+        }
+        (STRING, MANUFACTURER_INDEX) => string(MANUFACTURER, buffer),
+        (STRING, PRODUCT_INDEX) => string(PRODUCT, buffer),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_usb_driver::{Direction, EndpointAddress};
+
+    use super::*;
+
+    fn endpoints() -> Endpoints {
+        Endpoints {
+            notification: EndpointAddress::from_parts(1, Direction::In),
+            read: EndpointAddress::from_parts(2, Direction::Out),
+            write: EndpointAddress::from_parts(2, Direction::In),
+        }
+    }
+
+    /// Splits descriptors by their length bytes, checking each fits
+    fn split(mut data: &[u8]) -> Vec<&[u8]> {
+        let mut descriptors = Vec::new();
+        while !data.is_empty() {
+            let (descriptor, rest) = data.split_at(usize::from(data[0]));
+            descriptors.push(descriptor);
+            data = rest;
+        }
+        descriptors
+    }
+
+    #[test]
+    fn describes_cdc_device() {
+        let mut buffer = [0; 128];
+        let length = write(DEVICE, 0, &endpoints(), &mut buffer).unwrap();
+        assert_eq!(length, DEVICE_SIZE);
+        assert_eq!(buffer[0], DEVICE_SIZE as u8);
+        assert_eq!(buffer[4], CLASS_CDC);
+        assert_eq!(buffer[7], 64, "control packet size");
+        assert_eq!(buffer[8..12], [0x09, 0x12, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn lists_interfaces_and_endpoints_in_configuration() {
+        let mut buffer = [0; 128];
+        let length = write(CONFIGURATION, 0, &endpoints(), &mut buffer).unwrap();
+        let data = &buffer[..length];
+        assert_eq!(
+            usize::from(u16::from_le_bytes([data[2], data[3]])),
+            length,
+            "total length"
+        );
+
+        let descriptors = split(data);
+        let kinds: Vec<_> = descriptors.iter().map(|descriptor| descriptor[1]).collect();
+        assert_eq!(
+            kinds,
+            [
+                CONFIGURATION,
+                INTERFACE,
+                CS_INTERFACE,
+                CS_INTERFACE,
+                CS_INTERFACE,
+                CS_INTERFACE,
+                ENDPOINT,
+                INTERFACE,
+                ENDPOINT,
+                ENDPOINT
+            ]
+        );
+        let addresses: Vec<_> = descriptors
+            .iter()
+            .filter(|descriptor| descriptor[1] == ENDPOINT)
+            .map(|descriptor| (descriptor[2], descriptor[3]))
+            .collect();
+        assert_eq!(
+            addresses,
+            [
+                (0x81, TRANSFER_INTERRUPT),
+                (0x02, TRANSFER_BULK),
+                (0x82, TRANSFER_BULK)
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_strings_as_utf16() {
+        let mut buffer = [0; 128];
+        assert_eq!(
+            write(STRING, 0, &endpoints(), &mut buffer),
+            Some(4),
+            "languages"
+        );
+        assert_eq!(buffer[2..4], [0x09, 0x04]);
+
+        let length = write(STRING, PRODUCT_INDEX, &endpoints(), &mut buffer).unwrap();
+        assert_eq!(length, 2 + 2 * PRODUCT.len());
+        assert_eq!(buffer[..6], [length as u8, STRING, b'T', 0, b'e', 0]);
+    }
+
+    #[test]
+    fn has_no_other_descriptors() {
+        let mut buffer = [0; 128];
+        assert_eq!(write(STRING, 3, &endpoints(), &mut buffer), None);
+        assert_eq!(write(DEVICE, 1, &endpoints(), &mut buffer), None);
+        assert_eq!(write(6, 0, &endpoints(), &mut buffer), None, "qualifier");
+    }
+}
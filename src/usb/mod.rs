@@ -0,0 +1,173 @@
+//!
+//! USB serial device the shell is served on
+//!
+//! Minimal USB device with a single CDC-ACM function, built directly on [embassy_usb_driver], so
+//! units deployed without an ST-LINK show up as a serial port on any PC. [Runner] answers
+//! requests of host on the control pipe and [Port] is the byte stream of the function, which
+//! the [shell](crate::shell) is served on like on the UART.
+//!
+//! Output is discarded while no terminal is open on host, i.e. DTR is not set, so an unattended
+//! port does not stall the shell.
+//!
+
+mod control;
+mod descriptor;
+mod port;
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_usb_driver::{Driver, Endpoint as _, EndpointAddress, EndpointType};
+
+pub use control::Runner;
+pub use port::Port;
+
+/// Largest packet of full-speed bulk and control endpoints
+pub const MAX_PACKET_SIZE: u16 = 64;
+
+/// Serial state notification is the largest, it is never sent though
+const NOTIFICATION_PACKET_SIZE: u16 = 8;
+
+/// Longest polling interval, notifications are not sent
+const NOTIFICATION_INTERVAL_MS: u8 = 255;
+
+/// Serial line parameters set by host
+///
+/// Nothing is transmitted over a real line, host only reads them back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct LineCoding {
+    pub baud_rate: u32,
+    /// 0 for 1, 1 for 1.5 and 2 for 2 stop bits
+    pub stop_bits: u8,
+    /// 0 for none, then odd, even, mark and space
+    pub parity: u8,
+    pub data_bits: u8,
+}
+
+impl LineCoding {
+    /// Same as ST-LINK virtual COM port
+    pub const DEFAULT: Self = Self {
+        baud_rate: 115_200,
+        stop_bits: 0,
+        parity: 0,
+        data_bits: 8,
+    };
+
+    const SIZE: usize = 7;
+
+    fn encode(&self) -> [u8; Self::SIZE] {
+        let mut data = [0; Self::SIZE];
+        data[..4].copy_from_slice(&self.baud_rate.to_le_bytes());
+        data[4] = self.stop_bits;
+        data[5] = self.parity;
+        data[6] = self.data_bits;
+        data
+    }
+
+    fn decode(data: &[u8; Self::SIZE]) -> Self {
+        Self {
+            baud_rate: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            stop_bits: data[4],
+            parity: data[5],
+            data_bits: data[6],
+        }
+    }
+}
+
+/// Endpoints of CDC-ACM function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Endpoints {
+    /// Interrupt IN of communication interface
+    notification: EndpointAddress,
+    /// Bulk OUT of data interface
+    read: EndpointAddress,
+    /// Bulk IN of data interface
+    write: EndpointAddress,
+}
+
+impl Endpoints {
+    fn all(&self) -> [EndpointAddress; 3] {
+        [self.notification, self.read, self.write]
+    }
+}
+
+/// State shared by runner and port
+pub struct State {
+    line_coding: CriticalSectionMutex<Cell<LineCoding>>,
+    /// Terminal is open on host
+    dtr: CriticalSectionMutex<Cell<bool>>,
+}
+
+impl State {
+    pub const fn new() -> Self {
+        Self {
+            line_coding: CriticalSectionMutex::new(Cell::new(LineCoding::DEFAULT)),
+            dtr: CriticalSectionMutex::new(Cell::new(false)),
+        }
+    }
+
+    pub fn line_coding(&self) -> LineCoding {
+        self.line_coding.lock(Cell::get)
+    }
+
+    pub fn dtr(&self) -> bool {
+        self.dtr.lock(Cell::get)
+    }
+
+    fn set_line_coding(&self, line_coding: LineCoding) {
+        self.line_coding.lock(|cell| cell.set(line_coding));
+    }
+
+    fn set_dtr(&self, dtr: bool) {
+        self.dtr.lock(|cell| cell.set(dtr));
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Allocates endpoints of CDC-ACM function and starts device
+pub fn new<'d, D: Driver<'d>>(mut driver: D, state: &'d State) -> (Runner<'d, D>, Port<'d, D>) {
+    let notification = defmt::unwrap!(driver.alloc_endpoint_in(
+        EndpointType::Interrupt,
+        None,
+        NOTIFICATION_PACKET_SIZE,
+        NOTIFICATION_INTERVAL_MS,
+    ));
+    let read =
+        defmt::unwrap!(driver.alloc_endpoint_out(EndpointType::Bulk, None, MAX_PACKET_SIZE, 0));
+    let write =
+        defmt::unwrap!(driver.alloc_endpoint_in(EndpointType::Bulk, None, MAX_PACKET_SIZE, 0));
+    let endpoints = Endpoints {
+        notification: notification.info().addr,
+        read: read.info().addr,
+        write: write.info().addr,
+    };
+
+    let (bus, control) = driver.start(MAX_PACKET_SIZE);
+    (
+        Runner::new(bus, control, endpoints, state),
+        Port::new(read, write, state),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_line_coding() {
+        let line_coding = LineCoding {
+            baud_rate: 9600,
+            stop_bits: 2,
+            parity: 1,
+            data_bits: 7,
+        };
+        let data = line_coding.encode();
+        assert_eq!(data, [0x80, 0x25, 0, 0, 2, 1, 7]);
+        assert_eq!(LineCoding::decode(&data), line_coding);
+    }
+}
@@ -0,0 +1,79 @@
+//!
+//! Byte stream over bulk endpoints of CDC-ACM function
+//!
+
+use core::ops::Range;
+
+use embassy_usb_driver::{Driver, Endpoint as _, EndpointError, EndpointIn as _, EndpointOut as _};
+use embedded_io_async::{ErrorType, Read, Write};
+
+use super::{State, MAX_PACKET_SIZE};
+
+/// Packets written are one byte short of full, so every packet ends a transfer and host passes
+/// output on without waiting for a zero-length packet
+const WRITE_SIZE: usize = MAX_PACKET_SIZE as usize - 1;
+
+pub struct Port<'d, D: Driver<'d>> {
+    read_endpoint: D::EndpointOut,
+    write_endpoint: D::EndpointIn,
+    state: &'d State,
+    /// Last packet received, reads can take less than a packet
+    packet: [u8; MAX_PACKET_SIZE as usize],
+    /// Part of packet not read yet
+    unread: Range<usize>,
+}
+
+impl<'d, D: Driver<'d>> Port<'d, D> {
+    pub(super) fn new(
+        read_endpoint: D::EndpointOut,
+        write_endpoint: D::EndpointIn,
+        state: &'d State,
+    ) -> Self {
+        Self {
+            read_endpoint,
+            write_endpoint,
+            state,
+            packet: [0; MAX_PACKET_SIZE as usize],
+            unread: 0..0,
+        }
+    }
+}
+
+impl<'d, D: Driver<'d>> ErrorType for Port<'d, D> {
+    type Error = EndpointError;
+}
+
+impl<'d, D: Driver<'d>> Read for Port<'d, D> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.unread.is_empty() {
+            match self.read_endpoint.read(&mut self.packet).await {
+                Ok(length) => self.unread = 0..length,
+                // Not configured yet or reset by host, reading goes on once host configures it
+                Err(EndpointError::Disabled) => self.read_endpoint.wait_enabled().await,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let length = self.unread.len().min(buf.len());
+        buf[..length].copy_from_slice(&self.packet[self.unread.start..][..length]);
+        self.unread.start += length;
+        Ok(length)
+    }
+}
+
+impl<'d, D: Driver<'d>> Write for Port<'d, D> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, EndpointError> {
+        // Nobody reads output while terminal is closed, it would only stall the writer
+        if !self.state.dtr() {
+            return Ok(buf.len());
+        }
+
+        let length = buf.len().min(WRITE_SIZE);
+        self.write_endpoint.write(&buf[..length]).await?;
+        Ok(length)
+    }
+}
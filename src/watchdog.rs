@@ -0,0 +1,199 @@
+//!
+//! Supervision of long-running tasks with independent watchdog
+//!
+//! Every supervised task registers and then checks in on each pass of its loop, telling when
+//! it checks in next. Watchdog is fed only while no task is past its deadline, so a task
+//! stuck in a busy-wait or a hung bus transaction resets the board. Task that missed its
+//! deadline is kept in RAM which is not cleared on reset and reported on next boot, together
+//! with watchdog resets no task is known for, e.g. when supervisor itself was stuck.
+//!
+//! Supervisor runs in the highest priority executor, so it keeps running when a task blocks
+//! its executor.
+//!
+
+use core::{cell::Cell, mem::MaybeUninit, ptr};
+
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::bsp::{self, Watchdog, WATCHDOG_TIMEOUT};
+
+/// Added to every check-in period, covers work done between check-ins
+pub const SLACK: Duration = Duration::from_secs(5);
+
+/// Allowed before first check-in, covers initialization of sensors and display
+const STARTUP_TIME: Duration = Duration::from_secs(30);
+
+/// Watchdog is fed several times per timeout
+const FEED_INTERVAL: Duration = Duration::from_ticks(WATCHDOG_TIMEOUT.as_ticks() / 4);
+
+/// Marks valid record of missed task
+const RECORD_MAGIC: u32 = 0x5744_4f47;
+
+pub const TASK_COUNT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TaskId {
+    Lm75,
+    Dht22,
+    Display,
+    /// Main loop combining and publishing measurements
+    Processing,
+}
+
+impl TaskId {
+    pub const ALL: [TaskId; TASK_COUNT] = [
+        TaskId::Lm75,
+        TaskId::Dht22,
+        TaskId::Display,
+        TaskId::Processing,
+    ];
+}
+
+/// Magic and task that missed its deadline before last reset
+///
+/// Section is neither initialized nor zeroed on start, so record survives reset but holds
+/// garbage after power-on. It is only accessed by volatile reads and writes of whole words
+/// and the magic tells a valid record apart.
+#[link_section = ".uninit.watchdog"]
+static mut RECORD: MaybeUninit<[u32; 2]> = MaybeUninit::uninit();
+
+/// Words of [RECORD], magic first
+fn record_words() -> (*mut u32, *mut u32) {
+    let magic = (&raw mut RECORD).cast::<u32>();
+    // SAFETY: both words lie within RECORD
+    (magic, unsafe { magic.add(1) })
+}
+
+fn record_missed(task: TaskId) {
+    let (magic, missed) = record_words();
+    // SAFETY: RECORD is only written here, by supervisor after which no task runs, and read
+    // on boot before supervisor starts. Magic is written last, so a torn record is not valid.
+    unsafe {
+        ptr::write_volatile(missed, task as u32);
+        ptr::write_volatile(magic, RECORD_MAGIC);
+    }
+}
+
+/// Logs whether watchdog reset the board and which task made it, once per reset
+pub fn report_reset() {
+    match (bsp::take_watchdog_reset(), take_missed()) {
+        (true, Some(task)) => defmt::warn!("watchdog: reset after {} missed its deadline", task),
+        (true, None) => defmt::warn!("watchdog: unattributed watchdog reset"),
+        // Task recorded before reset of other cause did not stop the board
+        (false, _) => {}
+    }
+}
+
+/// Takes task which made watchdog reset the board, so it is reported once
+fn take_missed() -> Option<TaskId> {
+    let (magic, missed) = record_words();
+    // SAFETY: called once on boot before supervisor could write RECORD, words hold whatever
+    // RAM kept and any bit pattern is a valid u32
+    let task = unsafe {
+        if ptr::read_volatile(magic) != RECORD_MAGIC {
+            return None;
+        }
+        ptr::write_volatile(magic, 0);
+        ptr::read_volatile(missed)
+    };
+    TaskId::ALL.get(task as usize).copied()
+}
+
+pub struct Shared {
+    /// Deadline of next check-in of each task, `None` for tasks not registered
+    deadlines: CriticalSectionMutex<Cell<[Option<Instant>; TASK_COUNT]>>,
+}
+
+impl Shared {
+    pub const fn new() -> Self {
+        Self {
+            deadlines: CriticalSectionMutex::new(Cell::new([None; TASK_COUNT])),
+        }
+    }
+
+    fn set_deadline(&self, task: TaskId, deadline: Instant) {
+        self.deadlines.lock(|deadlines| {
+            let mut all = deadlines.get();
+            all[task as usize] = Some(deadline);
+            deadlines.set(all);
+        });
+    }
+
+    /// First task past its deadline
+    fn missed(&self, now: Instant) -> Option<TaskId> {
+        let deadlines = self.deadlines.lock(Cell::get);
+        TaskId::ALL
+            .into_iter()
+            .find(|&task| deadlines[task as usize].is_some_and(|deadline| deadline < now))
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registers tasks for supervision
+#[derive(Clone, Copy)]
+pub struct Supervisor<'a> {
+    shared: &'a Shared,
+}
+
+impl<'a> Supervisor<'a> {
+    /// Starts supervising task, which has [STARTUP_TIME] for its first check-in
+    pub fn register(&self, task: TaskId) -> CheckIn<'a> {
+        self.shared
+            .set_deadline(task, Instant::now() + STARTUP_TIME);
+        CheckIn {
+            shared: self.shared,
+            task,
+        }
+    }
+}
+
+/// Handle of one supervised task
+pub struct CheckIn<'a> {
+    shared: &'a Shared,
+    task: TaskId,
+}
+
+impl CheckIn<'_> {
+    /// Reports task alive, it checks in again within period and [SLACK]
+    pub fn check_in(&self, period: Duration) {
+        self.shared
+            .set_deadline(self.task, Instant::now() + period + SLACK);
+    }
+}
+
+/// Feeds watchdog while all tasks check in on time
+pub struct Runner<'a> {
+    watchdog: Watchdog,
+    shared: &'a Shared,
+}
+
+impl Runner<'_> {
+    pub async fn run(mut self) -> ! {
+        self.watchdog.unleash();
+        defmt::info!("watchdog: unleashed");
+
+        loop {
+            if let Some(task) = self.shared.missed(Instant::now()) {
+                defmt::error!("watchdog: {} missed its deadline, resetting", task);
+                record_missed(task);
+                // Not fed anymore, reset follows within timeout
+                loop {
+                    Timer::after(WATCHDOG_TIMEOUT).await;
+                }
+            }
+
+            self.watchdog.pet();
+            Timer::after(FEED_INTERVAL).await;
+        }
+    }
+}
+
+pub fn new(watchdog: Watchdog, shared: &Shared) -> (Supervisor<'_>, Runner<'_>) {
+    (Supervisor { shared }, Runner { watchdog, shared })
+}
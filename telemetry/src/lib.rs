@@ -0,0 +1,274 @@
+//!
+//! Binary telemetry format
+//!
+//! Every [Frame] is serialized with postcard and COBS-encoded, so frames contain no zero bytes
+//! and are delimited by a zero on both sides. A receiver joining in the middle of a stream, or
+//! reading text mixed into it, loses at most the frame being received.
+//!
+//! Frame starts with [VERSION], decoders reject frames of other versions before reading them.
+//! Temperatures are in Celsius and humidity in %.
+//!
+
+#![cfg_attr(not(test), no_std)]
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Version of format, changed whenever layout of [Frame] changes
+pub const VERSION: u8 = 1;
+
+/// Size of buffer fitting any encoded frame with its delimiters
+pub const MAX_FRAME_SIZE: usize = 96;
+
+/// Most sensors a reading can carry
+pub const MAX_SENSORS: usize = 4;
+
+/// Byte delimiting frames
+pub const DELIMITER: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sensor {
+    Lm75,
+    Dht22,
+}
+
+impl Sensor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sensor::Lm75 => "lm75",
+            Sensor::Dht22 => "dht22",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SensorStatus {
+    Unknown,
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SensorReading {
+    pub sensor: Sensor,
+    pub temperature: f32,
+    pub status: SensorStatus,
+}
+
+/// Readings of all sensors taken at one moment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    /// Unix time in seconds, `None` until clock is synchronized
+    pub timestamp: Option<i64>,
+    pub sensors: Vec<SensorReading, MAX_SENSORS>,
+    /// Temperature combined from all sensors
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Alarm {
+    OverTemperature,
+    Normal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MinMax {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Minimum and maximum since start, `None` while nothing was measured
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub temperature: Option<MinMax>,
+    pub humidity: Option<MinMax>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    Reading(Reading),
+    /// Status of sensor changed
+    Status {
+        sensor: Sensor,
+        status: SensorStatus,
+    },
+    Alarm(Alarm),
+    Statistics(Statistics),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    /// Always [VERSION] in encoded frames, kept first so it can be read alone
+    pub version: u8,
+    /// Time since start of firmware
+    pub uptime_ms: u64,
+    pub message: Message,
+}
+
+impl Frame {
+    pub fn new(uptime_ms: u64, message: Message) -> Self {
+        Self {
+            version: VERSION,
+            uptime_ms,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Buffer is too small for frame
+    BufferFull,
+    /// Bytes are not a valid COBS frame
+    Framing,
+    /// Frame of other version of format
+    UnsupportedVersion(u8),
+    /// Frame does not match format
+    Malformed,
+}
+
+/// Encodes frame with leading and trailing delimiters, returns used part of buffer
+pub fn encode<'a>(frame: &Frame, buffer: &'a mut [u8]) -> Result<&'a mut [u8], Error> {
+    let (first, rest) = buffer.split_first_mut().ok_or(Error::BufferFull)?;
+    *first = DELIMITER;
+    // Postcard adds trailing delimiter itself
+    let encoded = postcard::to_slice_cobs(frame, rest).map_err(|_| Error::BufferFull)?;
+    let len = encoded.len() + 1;
+    Ok(&mut buffer[..len])
+}
+
+/// Decodes frame found between delimiters, delimiters must not be included
+///
+/// Frame is decoded in place, so contents of buffer are changed.
+pub fn decode(frame: &mut [u8]) -> Result<Frame, Error> {
+    let len = cobs::decode_in_place(frame).map_err(|_| Error::Framing)?;
+    let bytes = &frame[..len];
+
+    let (version, _) = postcard::take_from_bytes::<u8>(bytes).map_err(|_| Error::Malformed)?;
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    postcard::from_bytes(bytes).map_err(|_| Error::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading() -> Reading {
+        let sensors = [
+            (Sensor::Lm75, 21.5, SensorStatus::Ok),
+            (Sensor::Dht22, -3.25, SensorStatus::Error),
+        ];
+        Reading {
+            timestamp: Some(1_700_000_000),
+            sensors: sensors
+                .into_iter()
+                .map(|(sensor, temperature, status)| SensorReading {
+                    sensor,
+                    temperature,
+                    status,
+                })
+                .collect(),
+            temperature: 21.5,
+            humidity: 45.2,
+        }
+    }
+
+    fn round_trip(frame: &Frame) -> Result<Frame, Error> {
+        let mut buffer = [0; MAX_FRAME_SIZE];
+        let encoded = encode(frame, &mut buffer)?;
+        assert_eq!(encoded.first(), Some(&DELIMITER));
+        assert_eq!(encoded.last(), Some(&DELIMITER));
+        let len = encoded.len();
+        assert!(!encoded[1..len - 1].contains(&DELIMITER));
+        decode(&mut encoded[1..len - 1])
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        let messages = [
+            Message::Reading(reading()),
+            Message::Reading(Reading {
+                timestamp: None,
+                sensors: Vec::new(),
+                ..reading()
+            }),
+            Message::Status {
+                sensor: Sensor::Dht22,
+                status: SensorStatus::Unknown,
+            },
+            Message::Alarm(Alarm::OverTemperature),
+            Message::Alarm(Alarm::Normal),
+            Message::Statistics(Statistics {
+                temperature: Some(MinMax {
+                    min: 18.0,
+                    max: 26.5,
+                }),
+                humidity: None,
+            }),
+        ];
+
+        for message in messages {
+            let frame = Frame::new(123_456, message);
+            assert_eq!(round_trip(&frame), Ok(frame.clone()), "{frame:?}");
+        }
+    }
+
+    #[test]
+    fn largest_frame_fits_buffer() {
+        let sensor = SensorReading {
+            sensor: Sensor::Dht22,
+            temperature: f32::MIN,
+            status: SensorStatus::Unknown,
+        };
+        let reading = Reading {
+            timestamp: Some(i64::MIN),
+            sensors: Vec::from_slice(&[sensor; MAX_SENSORS]).unwrap(),
+            temperature: f32::MIN,
+            humidity: f32::MIN,
+        };
+        let frame = Frame::new(u64::MAX, Message::Reading(reading));
+
+        assert_eq!(round_trip(&frame), Ok(frame.clone()));
+    }
+
+    #[test]
+    fn small_buffer_is_reported() {
+        let frame = Frame::new(0, Message::Reading(reading()));
+        assert_eq!(encode(&frame, &mut [0; 8]), Err(Error::BufferFull));
+        assert_eq!(encode(&frame, &mut []), Err(Error::BufferFull));
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let frame = Frame {
+            version: VERSION + 1,
+            ..Frame::new(0, Message::Alarm(Alarm::Normal))
+        };
+        assert_eq!(
+            round_trip(&frame),
+            Err(Error::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn damaged_frames_are_rejected() {
+        let mut buffer = [0; MAX_FRAME_SIZE];
+        let encoded = encode(&Frame::new(7, Message::Reading(reading())), &mut buffer).unwrap();
+        let len = encoded.len();
+
+        // Truncated frame decodes to fewer bytes than the message needs
+        let mut truncated = encoded[1..len / 2].to_vec();
+        assert!(matches!(
+            decode(&mut truncated),
+            Err(Error::Malformed | Error::Framing)
+        ));
+
+        // Text is not a valid frame of current version
+        let mut text = *b"OK\r\n";
+        assert!(decode(&mut text).is_err());
+    }
+}
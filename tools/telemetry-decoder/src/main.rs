@@ -0,0 +1,168 @@
+//!
+//! Decoder of telemetry stream
+//!
+//! Reads COBS frames from a file, serial device or stdin and prints them as CSV or JSON lines.
+//! Serial device must be configured beforehand, e.g. `stty -F /dev/ttyACM0 115200 raw`, and
+//! binary stream enabled with `stream binary` shell command.
+//!
+//! Firmware is built for the microcontroller by default, so the decoder is run with host target:
+//!
+//! ```sh
+//! cargo run -p telemetry-decoder --target x86_64-unknown-linux-gnu -- --json /dev/ttyACM0
+//! ```
+//!
+
+mod output;
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    process::ExitCode,
+};
+
+use output::{CsvOutput, JsonOutput, Output};
+
+const USAGE: &str = "usage: telemetry-decoder [--csv | --json] [path]";
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--csv" => json = false,
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let input: Box<dyn Read> = match &path {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(io::stdin()),
+    };
+
+    let stdout = io::stdout().lock();
+    let mut output: Box<dyn Output> = if json {
+        Box::new(JsonOutput::new(stdout))
+    } else {
+        Box::new(CsvOutput::new(stdout))
+    };
+
+    match decode_stream(BufReader::new(input), output.as_mut()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Decodes frames until end of input, frames failing to decode are reported and skipped
+fn decode_stream(mut input: impl BufRead, output: &mut dyn Output) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(telemetry::MAX_FRAME_SIZE);
+    loop {
+        frame.clear();
+        if input.read_until(telemetry::DELIMITER, &mut frame)? == 0 {
+            return Ok(());
+        }
+        if frame.last() == Some(&telemetry::DELIMITER) {
+            frame.pop();
+        }
+        // Empty frames appear between trailing and leading delimiters of adjacent frames
+        if frame.is_empty() {
+            continue;
+        }
+
+        match telemetry::decode(&mut frame) {
+            Ok(frame) => output.write(&frame)?,
+            Err(err) => eprintln!("skipped frame: {err:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use telemetry::{Alarm, Frame, Message, Sensor, SensorStatus};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Frames(Vec<Frame>);
+
+    impl Output for Frames {
+        fn write(&mut self, frame: &Frame) -> io::Result<()> {
+            self.0.push(frame.clone());
+            Ok(())
+        }
+    }
+
+    fn encoded(frame: &Frame) -> Vec<u8> {
+        let mut buffer = [0; telemetry::MAX_FRAME_SIZE];
+        telemetry::encode(frame, &mut buffer).unwrap().to_vec()
+    }
+
+    fn decoded(stream: &[u8]) -> Vec<Frame> {
+        let mut frames = Frames::default();
+        decode_stream(stream, &mut frames).unwrap();
+        frames.0
+    }
+
+    #[test]
+    fn frames_are_found_among_text() {
+        let alarm = Frame::new(1_000, Message::Alarm(Alarm::OverTemperature));
+        let status = Frame::new(
+            2_000,
+            Message::Status {
+                sensor: Sensor::Lm75,
+                status: SensorStatus::Error,
+            },
+        );
+
+        let mut stream = b"> stream binary\r\nOK\r\n".to_vec();
+        stream.extend(encoded(&alarm));
+        stream.extend(b"unknown command\r\n");
+        stream.extend(encoded(&status));
+        stream.extend(encoded(&alarm));
+        // Frame cut off at end of input
+        stream.extend(&encoded(&status)[..5]);
+
+        assert_eq!(decoded(&stream), [alarm.clone(), status, alarm]);
+    }
+
+    #[test]
+    fn frames_of_other_version_are_skipped() {
+        let current = Frame::new(5, Message::Alarm(Alarm::Normal));
+        let newer = Frame {
+            version: telemetry::VERSION + 1,
+            ..current.clone()
+        };
+
+        let mut stream = encoded(&newer);
+        stream.extend(encoded(&current));
+
+        assert_eq!(decoded(&stream), [current]);
+    }
+
+    #[test]
+    fn stream_may_start_in_middle_of_frame() {
+        let frame = Frame::new(9, Message::Alarm(Alarm::Normal));
+        let mut stream = encoded(&frame)[3..].to_vec();
+        stream.extend(encoded(&frame));
+
+        assert_eq!(decoded(&stream), [frame]);
+        assert_eq!(decoded(&[]), []);
+    }
+}
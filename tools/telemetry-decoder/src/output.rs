@@ -0,0 +1,148 @@
+//!
+//! Formats of decoded frames
+//!
+//! CSV is written in long form, one value per row, so every kind of message fits the same
+//! columns. JSON is written as one object per frame.
+//!
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+use telemetry::{Alarm, Frame, Message, SensorStatus};
+
+pub trait Output {
+    fn write(&mut self, frame: &Frame) -> io::Result<()>;
+}
+
+pub struct JsonOutput<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> Output for JsonOutput<W> {
+    fn write(&mut self, frame: &Frame) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, frame)?;
+        writeln!(self.writer)?;
+        self.writer.flush()
+    }
+}
+
+#[derive(Serialize)]
+struct Row<'a> {
+    uptime_ms: u64,
+    /// Unix time of reading, empty for other messages and before clock is synced
+    timestamp: Option<i64>,
+    kind: &'a str,
+    name: String,
+    value: String,
+}
+
+pub struct CsvOutput<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+
+    fn row(
+        &mut self,
+        frame: &Frame,
+        timestamp: Option<i64>,
+        kind: &str,
+        name: impl Into<String>,
+        value: impl ToString,
+    ) -> io::Result<()> {
+        let row = Row {
+            uptime_ms: frame.uptime_ms,
+            timestamp,
+            kind,
+            name: name.into(),
+            value: value.to_string(),
+        };
+        self.writer.serialize(row).map_err(io::Error::other)
+    }
+}
+
+fn status_name(status: SensorStatus) -> &'static str {
+    match status {
+        SensorStatus::Unknown => "unknown",
+        SensorStatus::Ok => "ok",
+        SensorStatus::Error => "error",
+    }
+}
+
+impl<W: Write> Output for CsvOutput<W> {
+    fn write(&mut self, frame: &Frame) -> io::Result<()> {
+        match &frame.message {
+            Message::Reading(reading) => {
+                let timestamp = reading.timestamp;
+                for sensor in &reading.sensors {
+                    let name = sensor.sensor.name();
+                    let temperature = format!("{name}.temperature");
+                    self.row(frame, timestamp, "reading", temperature, sensor.temperature)?;
+                    let status = status_name(sensor.status);
+                    self.row(
+                        frame,
+                        timestamp,
+                        "reading",
+                        format!("{name}.status"),
+                        status,
+                    )?;
+                }
+                self.row(
+                    frame,
+                    timestamp,
+                    "reading",
+                    "temperature",
+                    reading.temperature,
+                )?;
+                self.row(frame, timestamp, "reading", "humidity", reading.humidity)?;
+            }
+            Message::Status { sensor, status } => {
+                self.row(frame, None, "status", sensor.name(), status_name(*status))?;
+            }
+            Message::Alarm(alarm) => {
+                let value = match alarm {
+                    Alarm::OverTemperature => "over_temperature",
+                    Alarm::Normal => "normal",
+                };
+                self.row(frame, None, "alarm", "lm75", value)?;
+            }
+            Message::Statistics(statistics) => {
+                let series = [
+                    ("temperature", statistics.temperature),
+                    ("humidity", statistics.humidity),
+                ];
+                for (name, min_max) in series {
+                    let Some(min_max) = min_max else {
+                        continue;
+                    };
+                    self.row(
+                        frame,
+                        None,
+                        "statistics",
+                        format!("{name}.min"),
+                        min_max.min,
+                    )?;
+                    self.row(
+                        frame,
+                        None,
+                        "statistics",
+                        format!("{name}.max"),
+                        min_max.max,
+                    )?;
+                }
+            }
+        }
+        self.writer.flush()
+    }
+}